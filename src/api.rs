@@ -0,0 +1,104 @@
+//! Helpers for the `api` subcommand, which sends raw requests to the bridge.
+
+use philips_hue_lab::HueError;
+use serde_json::Value;
+use std::io::Read;
+
+/// Read a JSON request body given as JSON text, `@FILE` for a file, or `-` for standard input.
+pub fn read_body(arg: &str) -> Result<Value, HueError> {
+    let text = match arg {
+        "-" => {
+            let mut text = String::new();
+            std::io::stdin().read_to_string(&mut text).map_err(|e| {
+                HueError::Usage(format!("Could not read the body from stdin: {}", e))
+            })?;
+            text
+        }
+        _ => match arg.strip_prefix('@') {
+            Some(path) => std::fs::read_to_string(path).map_err(|e| {
+                HueError::Usage(format!("Could not read the body from {}: {}", path, e))
+            })?,
+            None => arg.to_string(),
+        },
+    };
+    serde_json::from_str(&text)
+        .map_err(|e| HueError::Usage(format!("The request body is not valid JSON: {}", e)))
+}
+
+/// Convert a filter to a JSON pointer.
+///
+/// The filter is either a JSON pointer, e.g. `/data/0/on`, or a simple JSONPath
+/// of names and indexes, e.g. `$.data[0].on`.
+pub fn json_pointer(filter: &str) -> Result<String, HueError> {
+    if filter.is_empty() || filter.starts_with('/') {
+        return Ok(filter.to_string());
+    }
+    let invalid = || {
+        HueError::Usage(format!(
+            "Invalid filter '{}'. Use a JSON pointer like /data/0/on or a path like $.data[0].on",
+            filter
+        ))
+    };
+    let path = filter.strip_prefix('$').ok_or_else(invalid)?;
+    let mut pointer = String::new();
+    let mut rest = path;
+    while !rest.is_empty() {
+        let (token, tail) = match rest.as_bytes()[0] {
+            b'.' => {
+                let name = &rest[1..];
+                let end = name.find(['.', '[']).unwrap_or(name.len());
+                (&name[..end], &name[end..])
+            }
+            b'[' => {
+                let end = rest.find(']').ok_or_else(invalid)?;
+                let index = rest[1..end].trim_matches(['\'', '"']);
+                (index, &rest[end + 1..])
+            }
+            _ => return Err(invalid()),
+        };
+        if token.is_empty() {
+            return Err(invalid());
+        }
+        pointer.push('/');
+        pointer.push_str(&token.replace('~', "~0").replace('/', "~1"));
+        rest = tail;
+    }
+    Ok(pointer)
+}
+
+/// Select the part of the response matching the filter.
+pub fn select(response: Value, filter: &str) -> Result<Value, HueError> {
+    let pointer = json_pointer(filter)?;
+    response.pointer(&pointer).cloned().ok_or_else(|| {
+        HueError::ResourceNotFound(format!("Nothing in the response matches '{}'", filter))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn json_pointer_accepts_pointers_and_paths() {
+        assert_eq!(json_pointer("/data/0/on").unwrap(), "/data/0/on");
+        assert_eq!(json_pointer("$.data[0].on.on").unwrap(), "/data/0/on/on");
+        assert_eq!(json_pointer("$['data'][1]").unwrap(), "/data/1");
+        assert_eq!(json_pointer("$").unwrap(), "");
+        assert!(json_pointer("data.0").is_err());
+        assert!(json_pointer("$.data[0").is_err());
+    }
+
+    #[test]
+    fn select_returns_the_matching_part() {
+        let response = json!({"errors": [], "data": [{"on": {"on": true}}]});
+        assert_eq!(
+            select(response.clone(), "$.data[0].on").unwrap(),
+            json!({"on": true})
+        );
+        assert!(matches!(
+            select(response, "/data/1"),
+            Err(HueError::ResourceNotFound(_))
+        ));
+    }
+}
@@ -0,0 +1,570 @@
+//! The asynchronous client, enabled with the `async` feature.
+//!
+//! [`AsyncHueClient`] mirrors the blocking [`HueClient`](crate::HueClient) and
+//! shares its request and response types, so the two can be used side by side.
+//!
+//! ```no_run
+//! # async fn example() -> Result<(), philips_hue_lab::HueError> {
+//! use philips_hue_lab::{AppKey, BridgeIp, HueClient};
+//!
+//! let client = HueClient::builder(BridgeIp(String::from("192.168.1.2")))
+//!     .app_key(AppKey(String::from("my-key")))
+//!     .build_async()
+//!     .await?;
+//! let light_id = client.find_light_by_id_or_name("Kitchen").await?;
+//! client.control_light(&light_id, true, Some(50), None).await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::color::{CieXy, Color, ColorTemperature, Gamut, MirekSchema};
+use crate::effect::{AlertAction, Effect, Signal, TimedEffect};
+use crate::events::{AsyncEventStream, HueEvent, ResourceRef};
+use crate::group::{check_grouped_update, select_group, Group, GroupType, HueApiGroupData};
+use crate::light::{HueApiLightData, LightState, LightUpdate};
+use crate::resource::{GroupedLightId, ResourceId, ResourceType};
+use crate::tls::BridgeCertificateVerifier;
+use crate::transition::{RATE_LIMIT_BACKOFF, RATE_LIMIT_RETRIES};
+use crate::{
+    check_bridge_id, checked_light_update, delta_update, fade_plan, light_gamut,
+    light_mirek_schema, light_update, log_request, parse_create_key_response,
+    parse_list_devices_response, parse_resources_response, parse_response, parse_write_response,
+    required_app_key, resource_path, secrets, select_light, single_resource, AppKey, BridgeConfig,
+    BridgeIp, BridgeKey, CreateUserRequestBody, HueClientBuilder, HueDevice, HueError, LightId,
+    HUE_API_APP_NAME, HUE_API_USER_NAME, LINK_BUTTON_POLL_INTERVAL, POOL_IDLE_TIMEOUT,
+    TCP_KEEPALIVE,
+};
+use log::{debug, info, warn};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::ops::ControlFlow;
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// An asynchronous client for a single Hue Bridge.
+///
+/// Like the blocking client, it holds one HTTP client with a connection pool
+/// for all requests. Create it with [`HueClientBuilder::build_async`].
+pub struct AsyncHueClient {
+    bridge_ip: BridgeIp,
+    app_key: Option<AppKey>,
+    http: reqwest::Client,
+    request_timeout: Duration,
+}
+
+impl HueClientBuilder {
+    /// Build an [`AsyncHueClient`].
+    /// If the bridge ID is not given, it is read from the bridge configuration.
+    pub async fn build_async(self) -> Result<AsyncHueClient, HueError> {
+        let (verifier, common_name) = self.verifier()?;
+        let client = AsyncHueClient {
+            bridge_ip: self.bridge_ip,
+            app_key: self.app_key,
+            http: create_reqwest_client(verifier, self.connect_timeout)?,
+            request_timeout: self.request_timeout,
+        };
+        if let (Some(common_name), None) = (common_name, self.bridge_id) {
+            check_bridge_id(&common_name, &client.bridge_config().await?.bridge_id)?;
+        }
+        Ok(client)
+    }
+}
+
+impl AsyncHueClient {
+    /// Create a client for the bridge, authenticating with the given application key.
+    /// The bridge ID for verifying the certificate is read from the bridge.
+    pub async fn new(bridge_ip: BridgeIp, app_key: AppKey) -> Result<Self, HueError> {
+        crate::HueClient::builder(bridge_ip)
+            .app_key(app_key)
+            .build_async()
+            .await
+    }
+
+    pub fn bridge_ip(&self) -> &BridgeIp {
+        &self.bridge_ip
+    }
+
+    /// Ask the bridge to generate an application key and an entertainment client key.
+    /// The Link button on the bridge must be pressed shortly before calling this.
+    pub async fn create_key(&self) -> Result<BridgeKey, HueError> {
+        let body = CreateUserRequestBody::from(HUE_API_APP_NAME, HUE_API_USER_NAME);
+        let response = self.post_request("/api", &body).await?;
+        let parsed = parse_create_key_response(&response)?;
+        Ok(BridgeKey {
+            user_name: parsed.user_name,
+            client_key: parsed.client_key,
+        })
+    }
+
+    /// Like [`AsyncHueClient::create_key`], but while the link button has not been pressed,
+    /// keep retrying for up to `wait`, so the user can press it after starting.
+    pub async fn create_key_waiting(&self, wait: Duration) -> Result<BridgeKey, HueError> {
+        let deadline = Instant::now() + wait;
+        loop {
+            match self.create_key().await {
+                Err(HueError::LinkButtonNotPressed) if Instant::now() < deadline => {
+                    tokio::time::sleep(LINK_BUTTON_POLL_INTERVAL).await;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    /// Get the public bridge configuration.
+    /// This does not require an application key.
+    pub async fn bridge_config(&self) -> Result<BridgeConfig, HueError> {
+        let url = format!("https://{}/api/0/config", self.bridge_ip.0);
+        let request = self
+            .http
+            .get(&url)
+            .timeout(self.request_timeout)
+            .header("Accept", "application/json");
+        let response = self.send(request).await?;
+        serde_json::from_value::<BridgeConfig>(response)
+            .map_err(|e| HueError::parse("Invalid bridge configuration", e))
+    }
+
+    /// List all devices on the bridge.
+    pub async fn list_devices(&self) -> Result<Vec<HueDevice>, HueError> {
+        let response = self.get_request("/clip/v2/resource/device").await?;
+        parse_list_devices_response(&response)
+    }
+
+    /// Get a single resource, see [`HueClient::get_resource`](crate::HueClient::get_resource).
+    pub async fn get_resource<T>(&self, rtype: &ResourceType, id: &str) -> Result<T, HueError>
+    where
+        T: DeserializeOwned,
+    {
+        let response = self.get_request(&resource_path(rtype, Some(id))).await?;
+        single_resource(parse_resources_response(&response)?, rtype, id)
+    }
+
+    /// Get a single resource by its typed ID, e.g. a [`LightId`].
+    pub async fn get_resource_by_id<T, I>(&self, id: &I) -> Result<T, HueError>
+    where
+        T: DeserializeOwned,
+        I: ResourceId,
+    {
+        self.get_resource(&I::RESOURCE_TYPE, id.as_str()).await
+    }
+
+    /// List all resources of a type, deserialized as `T`.
+    pub async fn list_resources<T>(&self, rtype: &ResourceType) -> Result<Vec<T>, HueError>
+    where
+        T: DeserializeOwned,
+    {
+        let response = self.get_request(&resource_path(rtype, None)).await?;
+        parse_resources_response(&response)
+    }
+
+    /// Read the state of a light.
+    pub async fn light_state(&self, light_id: &LightId) -> Result<LightState, HueError> {
+        Ok(LightState::from(
+            self.get_resource_by_id::<HueApiLightData, _>(light_id)
+                .await?,
+        ))
+    }
+
+    /// Read the state of all the lights.
+    pub async fn light_states(&self) -> Result<Vec<LightState>, HueError> {
+        let lights = self
+            .list_resources::<HueApiLightData>(&ResourceType::Light)
+            .await?;
+        Ok(lights.into_iter().map(LightState::from).collect())
+    }
+
+    /// Update a light with only the properties set in the update,
+    /// see [`HueClient::update_light`](crate::HueClient::update_light).
+    pub async fn update_light(
+        &self,
+        light_id: &LightId,
+        update: &LightUpdate,
+    ) -> Result<Vec<ResourceRef>, HueError> {
+        update.check()?;
+        match update.needs_capabilities() {
+            true => {
+                let light = self.get_resource_by_id(light_id).await?;
+                let update = checked_light_update(light_id, light, update)?;
+                self.put_light_update(light_id, &update).await
+            }
+            false => self.put_light_update(light_id, update).await,
+        }
+    }
+
+    /// Turn a light on or off, optionally setting the brightness (0-100) and the transition time.
+    /// Returns the resources updated by the bridge.
+    pub async fn control_light(
+        &self,
+        light_id: &LightId,
+        on: bool,
+        dimming_level: Option<u8>,
+        transition: Option<Duration>,
+    ) -> Result<Vec<ResourceRef>, HueError> {
+        let update = light_update(Some(on), dimming_level, transition);
+        self.update_light(light_id, &update).await
+    }
+
+    /// The gamut of a color light, see [`HueClient::light_gamut`](crate::HueClient::light_gamut).
+    pub async fn light_gamut(&self, light_id: &LightId) -> Result<Option<Gamut>, HueError> {
+        light_gamut(light_id, &self.get_resource_by_id(light_id).await?)
+    }
+
+    /// Turn a light on with the given color, clamped to the gamut of the light,
+    /// optionally setting the brightness (0-100).
+    pub async fn set_light_color(
+        &self,
+        light_id: &LightId,
+        color: &Color,
+        dimming_level: Option<u8>,
+        transition: Option<Duration>,
+    ) -> Result<Vec<ResourceRef>, HueError> {
+        let update = light_update(Some(true), dimming_level, transition).color(color.to_xy());
+        self.update_light(light_id, &update).await
+    }
+
+    /// The range of color temperatures of a white light,
+    /// see [`HueClient::light_mirek_schema`](crate::HueClient::light_mirek_schema).
+    pub async fn light_mirek_schema(&self, light_id: &LightId) -> Result<MirekSchema, HueError> {
+        light_mirek_schema(light_id, &self.get_resource_by_id(light_id).await?)
+    }
+
+    /// Turn a light on with the given color temperature,
+    /// see [`HueClient::set_light_color_temperature`](crate::HueClient::set_light_color_temperature).
+    pub async fn set_light_color_temperature(
+        &self,
+        light_id: &LightId,
+        temperature: ColorTemperature,
+        dimming_level: Option<u8>,
+        clamp: bool,
+        transition: Option<Duration>,
+    ) -> Result<Vec<ResourceRef>, HueError> {
+        let update = light_update(Some(true), dimming_level, transition)
+            .color_temperature(temperature, clamp);
+        self.update_light(light_id, &update).await
+    }
+
+    /// Play an effect on a light, see [`HueClient::set_light_effect`](crate::HueClient::set_light_effect).
+    pub async fn set_light_effect(
+        &self,
+        light_id: &LightId,
+        effect: Effect,
+    ) -> Result<Vec<ResourceRef>, HueError> {
+        self.update_light(light_id, &LightUpdate::new().effect(effect))
+            .await
+    }
+
+    /// Play a timed effect on a light,
+    /// see [`HueClient::set_light_timed_effect`](crate::HueClient::set_light_timed_effect).
+    pub async fn set_light_timed_effect(
+        &self,
+        light_id: &LightId,
+        effect: TimedEffect,
+        duration: Duration,
+    ) -> Result<Vec<ResourceRef>, HueError> {
+        let update = LightUpdate::new().timed_effect(effect, duration);
+        self.update_light(light_id, &update).await
+    }
+
+    /// Play an alert on a light once, see [`HueClient::alert_light`](crate::HueClient::alert_light).
+    pub async fn alert_light(
+        &self,
+        light_id: &LightId,
+        action: AlertAction,
+    ) -> Result<Vec<ResourceRef>, HueError> {
+        self.update_light(light_id, &LightUpdate::new().alert(action))
+            .await
+    }
+
+    /// Show a signal on a light, see [`HueClient::signal_light`](crate::HueClient::signal_light).
+    pub async fn signal_light(
+        &self,
+        light_id: &LightId,
+        signal: Signal,
+        duration: Duration,
+        colors: &[Color],
+    ) -> Result<Vec<ResourceRef>, HueError> {
+        let colors: Vec<CieXy> = colors.iter().map(Color::to_xy).collect();
+        let update = LightUpdate::new().signal(signal, duration, &colors);
+        self.update_light(light_id, &update).await
+    }
+
+    /// Turn a light off if it is on, or on if it is off.
+    pub async fn toggle_light(
+        &self,
+        light_id: &LightId,
+        transition: Option<Duration>,
+    ) -> Result<Vec<ResourceRef>, HueError> {
+        let on = !self.light_state(light_id).await?.on;
+        info!(
+            "Turning light {} {}",
+            light_id,
+            if on { "on" } else { "off" }
+        );
+        self.control_light(light_id, on, None, transition).await
+    }
+
+    /// Change the brightness and color temperature of a light relative to the current values,
+    /// see [`HueClient::adjust_light`](crate::HueClient::adjust_light).
+    pub async fn adjust_light(
+        &self,
+        light_id: &LightId,
+        brightness_delta: Option<i8>,
+        mirek_delta: Option<i16>,
+        transition: Option<Duration>,
+    ) -> Result<Vec<ResourceRef>, HueError> {
+        let update = delta_update(brightness_delta, mirek_delta, transition);
+        self.update_light(light_id, &update).await
+    }
+
+    /// Fade the brightness of a light step by step,
+    /// see [`HueClient::fade_light`](crate::HueClient::fade_light).
+    pub async fn fade_light(
+        &self,
+        light_id: &LightId,
+        on: bool,
+        dimming_level: Option<u8>,
+        duration: Duration,
+    ) -> Result<Vec<ResourceRef>, HueError> {
+        let steps = fade_plan(
+            light_id,
+            self.get_resource_by_id(light_id).await?,
+            on,
+            dimming_level,
+            duration,
+        )?;
+        let path = resource_path(&ResourceType::Light, Some(light_id.as_str()));
+        let start = Instant::now();
+        let mut updated = Vec::new();
+        for (body, at) in steps {
+            tokio::time::sleep_until(start + at).await;
+            updated = self.put_resource_with_retries(&path, &body).await?;
+        }
+        Ok(updated)
+    }
+
+    /// Find a light by ID or name, see [`HueClient::find_light_by_id_or_name`](crate::HueClient::find_light_by_id_or_name).
+    pub async fn find_light_by_id_or_name(&self, id_or_name: &str) -> Result<LightId, HueError> {
+        select_light(self.list_devices().await?, id_or_name)
+    }
+
+    /// List the rooms or the zones, with their members and `grouped_light` service.
+    pub async fn list_groups(&self, group_type: GroupType) -> Result<Vec<Group>, HueError> {
+        let groups = self
+            .list_resources::<HueApiGroupData>(&group_type.resource_type())
+            .await?;
+        Ok(groups.into_iter().map(Group::from).collect())
+    }
+
+    /// Find the `grouped_light` of a room or zone,
+    /// see [`HueClient::find_group_by_id_or_name`](crate::HueClient::find_group_by_id_or_name).
+    pub async fn find_group_by_id_or_name(
+        &self,
+        group_type: GroupType,
+        id_or_name: &str,
+    ) -> Result<GroupedLightId, HueError> {
+        select_group(self.list_groups(group_type).await?, group_type, id_or_name)
+    }
+
+    /// Update all the lights of a room or zone in one request,
+    /// see [`HueClient::update_grouped_light`](crate::HueClient::update_grouped_light).
+    pub async fn update_grouped_light(
+        &self,
+        grouped_light_id: &GroupedLightId,
+        update: &LightUpdate,
+    ) -> Result<Vec<ResourceRef>, HueError> {
+        check_grouped_update(update)?;
+        debug!("Updating grouped light {}: {}", grouped_light_id, update);
+        let path = resource_path(&ResourceType::GroupedLight, Some(grouped_light_id.as_str()));
+        self.put_resource(&path, update).await
+    }
+
+    /// Open the CLIP v2 event stream.
+    pub async fn event_stream(&self) -> Result<AsyncEventStream, HueError> {
+        let url = format!("https://{}/eventstream/clip/v2", self.bridge_ip.0);
+        let request = self
+            .http
+            .get(&url)
+            .header("Accept", "text/event-stream")
+            .header("hue-application-key", String::from(self.app_key()?));
+        let response = self.execute(request).await?;
+        if !response.status().is_success() {
+            let status = response.status().as_u16();
+            return Err(HueError::from_status(
+                status,
+                &response.text().await.unwrap_or_default(),
+            ));
+        }
+        Ok(AsyncEventStream::new(response))
+    }
+
+    /// Call `callback` for every event from the bridge until it returns
+    /// [`ControlFlow::Break`] or the bridge closes the event stream.
+    pub async fn watch_events<F>(&self, mut callback: F) -> Result<(), HueError>
+    where
+        F: FnMut(HueEvent) -> ControlFlow<()>,
+    {
+        let mut events = self.event_stream().await?;
+        while let Some(event) = events.next_event().await {
+            match event {
+                Ok(event) => {
+                    if callback(event).is_break() {
+                        break;
+                    }
+                }
+                // A message this library cannot decode does not end the stream.
+                Err(e @ HueError::Parse { .. }) => warn!("Skipping event stream message: {}", e),
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(())
+    }
+
+    /// Send a GET request to the Hue Bridge.
+    pub async fn get_request(&self, path: &str) -> Result<serde_json::Value, HueError> {
+        let url = format!("https://{}{}", self.bridge_ip.0, path);
+        let request = self
+            .http
+            .get(&url)
+            .timeout(self.request_timeout)
+            .header("Accept", "application/json")
+            .header("hue-application-key", String::from(self.app_key()?));
+        self.send(request).await
+    }
+
+    /// Send a POST request to the Hue Bridge.
+    pub async fn post_request<T>(&self, path: &str, body: &T) -> Result<serde_json::Value, HueError>
+    where
+        T: ?Sized + Serialize,
+    {
+        let url = format!("https://{}{}", self.bridge_ip.0, path);
+        let body_str = serde_json::to_string(body)
+            .map_err(|e| HueError::parse("Could not serialize the request body", e))?;
+        let mut request = self
+            .http
+            .post(&url)
+            .timeout(self.request_timeout)
+            .header("Accept", "application/json")
+            .body(body_str);
+        // Creating a key does not need one, but creating CLIP resources does.
+        if let Some(app_key) = &self.app_key {
+            request = request.header("hue-application-key", String::from(app_key));
+        }
+        self.send(request).await
+    }
+
+    /// Send a PUT request to the Hue Bridge.
+    pub async fn put_request<T>(&self, path: &str, body: &T) -> Result<serde_json::Value, HueError>
+    where
+        T: ?Sized + Serialize,
+    {
+        let url = format!("https://{}{}", self.bridge_ip.0, path);
+        let body_str = serde_json::to_string(body)
+            .map_err(|e| HueError::parse("Could not serialize the request body", e))?;
+        let request = self
+            .http
+            .put(&url)
+            .timeout(self.request_timeout)
+            .header("Accept", "application/json")
+            .header("hue-application-key", String::from(self.app_key()?))
+            .body(body_str);
+        self.send(request).await
+    }
+
+    /// Send a DELETE request to the Hue Bridge.
+    pub async fn delete_request(&self, path: &str) -> Result<serde_json::Value, HueError> {
+        let url = format!("https://{}{}", self.bridge_ip.0, path);
+        let request = self
+            .http
+            .delete(&url)
+            .timeout(self.request_timeout)
+            .header("Accept", "application/json")
+            .header("hue-application-key", String::from(self.app_key()?));
+        self.send(request).await
+    }
+
+    /// Update a CLIP v2 resource and return the affected resources.
+    async fn put_resource<T>(&self, path: &str, body: &T) -> Result<Vec<ResourceRef>, HueError>
+    where
+        T: ?Sized + Serialize,
+    {
+        let response = self.put_request(path, body).await?;
+        parse_write_response(&response)
+    }
+
+    async fn put_light_update(
+        &self,
+        light_id: &LightId,
+        update: &LightUpdate,
+    ) -> Result<Vec<ResourceRef>, HueError> {
+        debug!("Updating light {}: {}", light_id, update);
+        let path = resource_path(&ResourceType::Light, Some(light_id.as_str()));
+        self.put_resource(&path, update).await
+    }
+
+    async fn put_resource_with_retries<T>(
+        &self,
+        path: &str,
+        body: &T,
+    ) -> Result<Vec<ResourceRef>, HueError>
+    where
+        T: ?Sized + Serialize,
+    {
+        let mut retries = 0;
+        loop {
+            match self.put_resource(path, body).await {
+                Err(HueError::RateLimited) if retries < RATE_LIMIT_RETRIES => {
+                    retries += 1;
+                    info!(
+                        "The bridge is rate limiting requests, retrying in {:?}",
+                        RATE_LIMIT_BACKOFF
+                    );
+                    tokio::time::sleep(RATE_LIMIT_BACKOFF).await;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    async fn send(&self, request: reqwest::RequestBuilder) -> Result<serde_json::Value, HueError> {
+        let response = self.execute(request).await?;
+        let status = response.status();
+        let body = response.text().await?;
+        parse_response(status, &body, &self.secrets())
+    }
+
+    async fn execute(
+        &self,
+        request: reqwest::RequestBuilder,
+    ) -> Result<reqwest::Response, HueError> {
+        let request = request.build()?;
+        log_request(
+            request.method(),
+            request.url(),
+            request.headers(),
+            request.body().and_then(|body| body.as_bytes()),
+            &self.secrets(),
+        );
+        Ok(self.http.execute(request).await?)
+    }
+
+    fn app_key(&self) -> Result<&AppKey, HueError> {
+        required_app_key(self.app_key.as_ref())
+    }
+
+    fn secrets(&self) -> Vec<&str> {
+        secrets(self.app_key.as_ref())
+    }
+}
+
+fn create_reqwest_client(
+    verifier: BridgeCertificateVerifier,
+    connect_timeout: Duration,
+) -> Result<reqwest::Client, HueError> {
+    let client = reqwest::ClientBuilder::new()
+        .use_preconfigured_tls(verifier.into_client_config())
+        .connect_timeout(connect_timeout)
+        .pool_idle_timeout(POOL_IDLE_TIMEOUT)
+        .tcp_keepalive(TCP_KEEPALIVE)
+        .build()?;
+    Ok(client)
+}
@@ -0,0 +1,842 @@
+//! Colors for lights.
+//!
+//! Hue lights take their color as a point in the CIE 1931 xy color space.
+//! A [`Color`] can be given as sRGB, named CSS color, HSV or xy, and is
+//! converted to xy with the wide gamut conversion from the Hue documentation.
+//! Each light can only show the colors inside its [`Gamut`], so colors are
+//! clamped to the closest color the light can show before they are sent.
+//!
+//! White lights take a [`ColorTemperature`] in mirek instead, within the
+//! [`MirekSchema`] of the light.
+//! See documentation at <https://developers.meethue.com/develop/application-design-guidance/color-conversion-formulas-rgb-to-xy-and-back/>
+
+use crate::HueError;
+use serde::{Deserialize, Serialize};
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+/// A point in the CIE 1931 color space.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct CieXy {
+    pub x: f64,
+    pub y: f64,
+}
+
+/// The D65 white point, used for black which has no color.
+const WHITE_POINT: CieXy = CieXy {
+    x: 0.3127,
+    y: 0.3290,
+};
+
+impl Display for CieXy {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "xy({:.4}, {:.4})", self.x, self.y)
+    }
+}
+
+impl CieXy {
+    /// The sRGB color for display, at the given brightness (0-1).
+    pub fn to_rgb(&self, brightness: f64) -> (u8, u8, u8) {
+        xy_to_rgb(*self, brightness)
+    }
+
+    /// The color as `#rrggbb` at full brightness.
+    pub fn to_hex(&self) -> String {
+        let (red, green, blue) = self.to_rgb(1.0);
+        format!("#{:02x}{:02x}{:02x}", red, green, blue)
+    }
+
+    fn minus(self, other: CieXy) -> CieXy {
+        CieXy {
+            x: self.x - other.x,
+            y: self.y - other.y,
+        }
+    }
+
+    fn cross(self, other: CieXy) -> f64 {
+        self.x * other.y - self.y * other.x
+    }
+
+    fn distance(self, other: CieXy) -> f64 {
+        let d = self.minus(other);
+        (d.x * d.x + d.y * d.y).sqrt()
+    }
+}
+
+/// The triangle of colors a light can show, from the `color.gamut` of the light.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct Gamut {
+    pub red: CieXy,
+    pub green: CieXy,
+    pub blue: CieXy,
+}
+
+impl Gamut {
+    /// Gamut A, of older LivingColors lights such as Bloom and Iris.
+    pub const A: Gamut = Gamut {
+        red: CieXy { x: 0.704, y: 0.296 },
+        green: CieXy {
+            x: 0.2151,
+            y: 0.7106,
+        },
+        blue: CieXy { x: 0.138, y: 0.08 },
+    };
+
+    /// Gamut B, of the first Hue bulbs.
+    pub const B: Gamut = Gamut {
+        red: CieXy { x: 0.675, y: 0.322 },
+        green: CieXy { x: 0.409, y: 0.518 },
+        blue: CieXy { x: 0.167, y: 0.04 },
+    };
+
+    /// Gamut C, of current Hue color lights.
+    pub const C: Gamut = Gamut {
+        red: CieXy {
+            x: 0.6915,
+            y: 0.3083,
+        },
+        green: CieXy { x: 0.17, y: 0.7 },
+        blue: CieXy {
+            x: 0.1532,
+            y: 0.0475,
+        },
+    };
+
+    /// True if the light can show the color.
+    pub fn contains(&self, xy: CieXy) -> bool {
+        let sides = [
+            self.green.minus(self.red).cross(xy.minus(self.red)),
+            self.blue.minus(self.green).cross(xy.minus(self.green)),
+            self.red.minus(self.blue).cross(xy.minus(self.blue)),
+        ];
+        sides.iter().all(|side| *side >= 0.0) || sides.iter().all(|side| *side <= 0.0)
+    }
+
+    /// The closest color the light can show.
+    pub fn clamp(&self, xy: CieXy) -> CieXy {
+        if self.contains(xy) {
+            return xy;
+        }
+        [
+            closest_point_on_line(self.red, self.green, xy),
+            closest_point_on_line(self.green, self.blue, xy),
+            closest_point_on_line(self.blue, self.red, xy),
+        ]
+        .into_iter()
+        .min_by(|a, b| a.distance(xy).total_cmp(&b.distance(xy)))
+        .expect("a triangle has three sides")
+    }
+}
+
+/// The gamut type of a light, from its `color.gamut_type`.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum GamutType {
+    A,
+    B,
+    C,
+    #[serde(other, rename = "other")]
+    Other,
+}
+
+impl GamutType {
+    /// The gamut of this type, or `None` for other gamuts.
+    pub fn gamut(&self) -> Option<Gamut> {
+        match self {
+            GamutType::A => Some(Gamut::A),
+            GamutType::B => Some(Gamut::B),
+            GamutType::C => Some(Gamut::C),
+            GamutType::Other => None,
+        }
+    }
+}
+
+/// The point on the line segment from `a` to `b` closest to `p`.
+fn closest_point_on_line(a: CieXy, b: CieXy, p: CieXy) -> CieXy {
+    let ab = b.minus(a);
+    let ap = p.minus(a);
+    let t = ((ap.x * ab.x + ap.y * ab.y) / (ab.x * ab.x + ab.y * ab.y)).clamp(0.0, 1.0);
+    CieXy {
+        x: a.x + ab.x * t,
+        y: a.y + ab.y * t,
+    }
+}
+
+/// A color given by the user.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Color {
+    /// An sRGB color, e.g. from `#ff8000` or a CSS color name.
+    Rgb { red: u8, green: u8, blue: u8 },
+    /// Hue in degrees (0-360), saturation and value (0-1).
+    Hsv {
+        hue: f64,
+        saturation: f64,
+        value: f64,
+    },
+    /// A point in the CIE xy color space.
+    Xy(CieXy),
+}
+
+impl Color {
+    /// Parse a color: `#rrggbb`, `#rgb`, a CSS color name such as `orange`,
+    /// `hsv(h, s%, v%)` with the hue in degrees, or `xy(x, y)`.
+    pub fn parse(text: &str) -> Result<Self, HueError> {
+        let invalid = || {
+            HueError::Usage(format!(
+                "Invalid color '{}'. Use #rrggbb, a CSS color name, hsv(h, s%, v%) or xy(x, y).",
+                text
+            ))
+        };
+        let trimmed = text.trim().to_lowercase();
+        if let Some(hex) = trimmed.strip_prefix('#') {
+            return parse_hex(hex).ok_or_else(invalid);
+        }
+        if let Some(args) = function_args(&trimmed, "hsv") {
+            let [hue, saturation, value] = parse_numbers::<3>(&args).ok_or_else(invalid)?;
+            return match (0.0..=360.0).contains(&hue)
+                && (0.0..=100.0).contains(&saturation)
+                && (0.0..=100.0).contains(&value)
+            {
+                true => Ok(Color::Hsv {
+                    hue,
+                    saturation: saturation / 100.0,
+                    value: value / 100.0,
+                }),
+                false => Err(invalid()),
+            };
+        }
+        if let Some(args) = function_args(&trimmed, "xy") {
+            let [x, y] = parse_numbers::<2>(&args).ok_or_else(invalid)?;
+            return match (0.0..=1.0).contains(&x) && (0.0..=1.0).contains(&y) && y > 0.0 {
+                true => Ok(Color::Xy(CieXy { x, y })),
+                false => Err(invalid()),
+            };
+        }
+        CSS_COLORS
+            .iter()
+            .find(|(name, _)| *name == trimmed)
+            .map(|(_, rgb)| rgb_from_u32(*rgb))
+            .ok_or_else(invalid)
+    }
+
+    /// The color as sRGB, or `None` for an xy color.
+    pub fn to_rgb(&self) -> Option<(u8, u8, u8)> {
+        match *self {
+            Color::Rgb { red, green, blue } => Some((red, green, blue)),
+            Color::Hsv {
+                hue,
+                saturation,
+                value,
+            } => Some(hsv_to_rgb(hue, saturation, value)),
+            Color::Xy(_) => None,
+        }
+    }
+
+    /// The color in the CIE xy color space, without regard to what a light can show.
+    pub fn to_xy(&self) -> CieXy {
+        match *self {
+            Color::Rgb { red, green, blue } => rgb_to_xy(red, green, blue),
+            Color::Hsv {
+                hue,
+                saturation,
+                value,
+            } => {
+                let (red, green, blue) = hsv_to_rgb(hue, saturation, value);
+                rgb_to_xy(red, green, blue)
+            }
+            Color::Xy(xy) => xy,
+        }
+    }
+}
+
+impl FromStr for Color {
+    type Err = HueError;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        Color::parse(text)
+    }
+}
+
+/// A white color temperature.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ColorTemperature {
+    Kelvin(u32),
+    /// Micro reciprocal degrees, one million divided by the Kelvin.
+    Mirek(u16),
+}
+
+impl ColorTemperature {
+    /// Parse a color temperature: Kelvin as `2700K`, mirek as `370mirek`, or a
+    /// plain number, which is Kelvin from 1000 and mirek below.
+    pub fn parse(text: &str) -> Result<Self, HueError> {
+        let invalid = || {
+            HueError::Usage(format!(
+                "Invalid color temperature '{}'. Use Kelvin, e.g. 2700K, or mirek, e.g. 370mirek.",
+                text
+            ))
+        };
+        let trimmed = text.trim().to_lowercase();
+        let (number, unit) = match (trimmed.strip_suffix("mirek"), trimmed.strip_suffix('k')) {
+            (Some(number), _) => (number, Some("mirek")),
+            (_, Some(number)) => (number, Some("k")),
+            _ => (trimmed.as_str(), None),
+        };
+        let number = number.trim().parse::<u32>().map_err(|_| invalid())?;
+        let temperature = match (unit, number) {
+            (_, 0) => return Err(invalid()),
+            (Some("k"), kelvin) | (None, kelvin @ 1000..) => ColorTemperature::Kelvin(kelvin),
+            (_, mirek) => ColorTemperature::Mirek(u16::try_from(mirek).map_err(|_| invalid())?),
+        };
+        Ok(temperature)
+    }
+
+    /// The color temperature in mirek.
+    pub fn mirek(&self) -> u16 {
+        match *self {
+            ColorTemperature::Kelvin(kelvin) => (1_000_000.0 / f64::from(kelvin))
+                .round()
+                .min(f64::from(u16::MAX)) as u16,
+            ColorTemperature::Mirek(mirek) => mirek,
+        }
+    }
+
+    /// The color temperature in Kelvin.
+    pub fn kelvin(&self) -> u32 {
+        match *self {
+            ColorTemperature::Kelvin(kelvin) => kelvin,
+            ColorTemperature::Mirek(mirek) => {
+                (1_000_000.0 / f64::from(mirek.max(1))).round() as u32
+            }
+        }
+    }
+
+    /// The color of a black body at this temperature, for display.
+    /// Uses the approximation of the Planckian locus by Kim et al., for 1667K to 25000K.
+    pub fn to_xy(&self) -> CieXy {
+        let t = f64::from(self.kelvin().clamp(1667, 25000));
+        let x = match t <= 4000.0 {
+            true => -0.2661239e9 / t.powi(3) - 0.2343589e6 / t.powi(2) + 0.8776956e3 / t + 0.179910,
+            false => {
+                -3.0258469e9 / t.powi(3) + 2.1070379e6 / t.powi(2) + 0.2226347e3 / t + 0.240390
+            }
+        };
+        let y = match t {
+            ..=2222.0 => {
+                -1.1063814 * x.powi(3) - 1.34811020 * x.powi(2) + 2.18555832 * x - 0.20219683
+            }
+            ..=4000.0 => {
+                -0.9549476 * x.powi(3) - 1.37418593 * x.powi(2) + 2.09137015 * x - 0.16748867
+            }
+            _ => 3.0817580 * x.powi(3) - 5.87338670 * x.powi(2) + 3.75112997 * x - 0.37001483,
+        };
+        CieXy { x, y }
+    }
+}
+
+impl Display for ColorTemperature {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ColorTemperature::Kelvin(kelvin) => write!(f, "{}K", kelvin),
+            ColorTemperature::Mirek(mirek) => write!(f, "{} mirek", mirek),
+        }
+    }
+}
+
+impl FromStr for ColorTemperature {
+    type Err = HueError;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        ColorTemperature::parse(text)
+    }
+}
+
+/// The range of color temperatures a light can show, from its `color_temperature.mirek_schema`.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct MirekSchema {
+    pub mirek_minimum: u16,
+    pub mirek_maximum: u16,
+}
+
+impl MirekSchema {
+    pub fn contains(&self, mirek: u16) -> bool {
+        (self.mirek_minimum..=self.mirek_maximum).contains(&mirek)
+    }
+
+    /// The closest color temperature the light can show.
+    pub fn clamp(&self, mirek: u16) -> u16 {
+        mirek.clamp(self.mirek_minimum, self.mirek_maximum)
+    }
+
+    /// The range in Kelvin, warmest first, for messages.
+    fn kelvin_range(&self) -> String {
+        let kelvin = |mirek: u16| (1_000_000.0 / f64::from(mirek.max(1))).round();
+        format!(
+            "{}K-{}K",
+            kelvin(self.mirek_maximum),
+            kelvin(self.mirek_minimum)
+        )
+    }
+
+    /// Check that the light can show the color temperature and return it in mirek.
+    /// If it cannot, fail, or when `clamp` is set use the closest one with a warning.
+    pub fn check(&self, temperature: ColorTemperature, clamp: bool) -> Result<u16, HueError> {
+        let mirek = temperature.mirek();
+        if self.contains(mirek) {
+            return Ok(mirek);
+        }
+        let range = format!(
+            "{}-{} mirek ({})",
+            self.mirek_minimum,
+            self.mirek_maximum,
+            self.kelvin_range()
+        );
+        match clamp {
+            true => {
+                let clamped = self.clamp(mirek);
+                log::warn!(
+                    "The light cannot show {}, using {} mirek, the closest in its range of {}",
+                    temperature,
+                    clamped,
+                    range
+                );
+                Ok(clamped)
+            }
+            false => Err(HueError::InvalidParameter(format!(
+                "The light cannot show {}. Its range is {}.",
+                temperature, range
+            ))),
+        }
+    }
+}
+
+/// Convert an sRGB color to xy.
+/// Black has no color, so it is converted to the white point.
+pub fn rgb_to_xy(red: u8, green: u8, blue: u8) -> CieXy {
+    let [r, g, b] = [red, green, blue].map(|c| gamma_expand(f64::from(c) / 255.0));
+    // Wide gamut RGB D65 conversion.
+    let x = r * 0.664511 + g * 0.154324 + b * 0.162028;
+    let y = r * 0.283881 + g * 0.668433 + b * 0.047685;
+    let z = r * 0.000088 + g * 0.072310 + b * 0.986039;
+    let sum = x + y + z;
+    match sum > 0.0 {
+        true => CieXy {
+            x: x / sum,
+            y: y / sum,
+        },
+        false => WHITE_POINT,
+    }
+}
+
+/// Convert xy at the given brightness (0-1) to sRGB, for display.
+/// Colors outside sRGB are scaled down until they fit.
+pub fn xy_to_rgb(xy: CieXy, brightness: f64) -> (u8, u8, u8) {
+    let y = brightness;
+    let x = (y / xy.y) * xy.x;
+    let z = (y / xy.y) * (1.0 - xy.x - xy.y);
+    // Inverse of the wide gamut RGB D65 conversion.
+    let rgb = [
+        x * 1.656492 - y * 0.354851 - z * 0.255038,
+        -x * 0.707196 + y * 1.655397 + z * 0.036152,
+        x * 0.051713 - y * 0.121364 + z * 1.011530,
+    ]
+    .map(|c| c.max(0.0));
+    let max = rgb.iter().copied().fold(1.0, f64::max);
+    let [r, g, b] = rgb.map(|c| (gamma_compress(c / max) * 255.0).round() as u8);
+    (r, g, b)
+}
+
+/// Apply the sRGB gamma correction to a linear component (0-1).
+fn gamma_compress(c: f64) -> f64 {
+    match c <= 0.0031308 {
+        true => 12.92 * c,
+        false => 1.055 * c.powf(1.0 / 2.4) - 0.055,
+    }
+}
+
+/// Undo the sRGB gamma correction of a component (0-1).
+fn gamma_expand(c: f64) -> f64 {
+    match c > 0.04045 {
+        true => ((c + 0.055) / 1.055).powf(2.4),
+        false => c / 12.92,
+    }
+}
+
+fn hsv_to_rgb(hue: f64, saturation: f64, value: f64) -> (u8, u8, u8) {
+    let chroma = value * saturation;
+    let sector = (hue % 360.0) / 60.0;
+    let second = chroma * (1.0 - (sector % 2.0 - 1.0).abs());
+    let (r, g, b) = match sector as u8 {
+        0 => (chroma, second, 0.0),
+        1 => (second, chroma, 0.0),
+        2 => (0.0, chroma, second),
+        3 => (0.0, second, chroma),
+        4 => (second, 0.0, chroma),
+        _ => (chroma, 0.0, second),
+    };
+    let m = value - chroma;
+    let to_u8 = |c: f64| ((c + m) * 255.0).round() as u8;
+    (to_u8(r), to_u8(g), to_u8(b))
+}
+
+fn parse_hex(hex: &str) -> Option<Color> {
+    if !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    match hex.len() {
+        6 => u32::from_str_radix(hex, 16).ok().map(rgb_from_u32),
+        3 => {
+            let doubled: String = hex.chars().flat_map(|c| [c, c]).collect();
+            u32::from_str_radix(&doubled, 16).ok().map(rgb_from_u32)
+        }
+        _ => None,
+    }
+}
+
+fn rgb_from_u32(rgb: u32) -> Color {
+    Color::Rgb {
+        red: (rgb >> 16) as u8,
+        green: (rgb >> 8) as u8,
+        blue: rgb as u8,
+    }
+}
+
+/// The arguments of a function-like color, e.g. `240, 100%, 50%` for `hsv(240, 100%, 50%)`.
+fn function_args(text: &str, name: &str) -> Option<String> {
+    text.strip_prefix(name)?
+        .trim_start()
+        .strip_prefix('(')?
+        .strip_suffix(')')
+        .map(String::from)
+}
+
+/// Parse exactly `N` comma-separated numbers, each with an optional `%` or `°` suffix.
+fn parse_numbers<const N: usize>(args: &str) -> Option<[f64; N]> {
+    let numbers: Vec<f64> = args
+        .split(',')
+        .map(|n| n.trim().trim_end_matches(['%', '°']).parse::<f64>())
+        .collect::<Result<_, _>>()
+        .ok()?;
+    numbers.try_into().ok()
+}
+
+/// The CSS named colors, see <https://www.w3.org/TR/css-color-4/#named-colors>
+const CSS_COLORS: &[(&str, u32)] = &[
+    ("aliceblue", 0xf0f8ff),
+    ("antiquewhite", 0xfaebd7),
+    ("aqua", 0x00ffff),
+    ("aquamarine", 0x7fffd4),
+    ("azure", 0xf0ffff),
+    ("beige", 0xf5f5dc),
+    ("bisque", 0xffe4c4),
+    ("black", 0x000000),
+    ("blanchedalmond", 0xffebcd),
+    ("blue", 0x0000ff),
+    ("blueviolet", 0x8a2be2),
+    ("brown", 0xa52a2a),
+    ("burlywood", 0xdeb887),
+    ("cadetblue", 0x5f9ea0),
+    ("chartreuse", 0x7fff00),
+    ("chocolate", 0xd2691e),
+    ("coral", 0xff7f50),
+    ("cornflowerblue", 0x6495ed),
+    ("cornsilk", 0xfff8dc),
+    ("crimson", 0xdc143c),
+    ("cyan", 0x00ffff),
+    ("darkblue", 0x00008b),
+    ("darkcyan", 0x008b8b),
+    ("darkgoldenrod", 0xb8860b),
+    ("darkgray", 0xa9a9a9),
+    ("darkgreen", 0x006400),
+    ("darkgrey", 0xa9a9a9),
+    ("darkkhaki", 0xbdb76b),
+    ("darkmagenta", 0x8b008b),
+    ("darkolivegreen", 0x556b2f),
+    ("darkorange", 0xff8c00),
+    ("darkorchid", 0x9932cc),
+    ("darkred", 0x8b0000),
+    ("darksalmon", 0xe9967a),
+    ("darkseagreen", 0x8fbc8f),
+    ("darkslateblue", 0x483d8b),
+    ("darkslategray", 0x2f4f4f),
+    ("darkslategrey", 0x2f4f4f),
+    ("darkturquoise", 0x00ced1),
+    ("darkviolet", 0x9400d3),
+    ("deeppink", 0xff1493),
+    ("deepskyblue", 0x00bfff),
+    ("dimgray", 0x696969),
+    ("dimgrey", 0x696969),
+    ("dodgerblue", 0x1e90ff),
+    ("firebrick", 0xb22222),
+    ("floralwhite", 0xfffaf0),
+    ("forestgreen", 0x228b22),
+    ("fuchsia", 0xff00ff),
+    ("gainsboro", 0xdcdcdc),
+    ("ghostwhite", 0xf8f8ff),
+    ("gold", 0xffd700),
+    ("goldenrod", 0xdaa520),
+    ("gray", 0x808080),
+    ("green", 0x008000),
+    ("greenyellow", 0xadff2f),
+    ("grey", 0x808080),
+    ("honeydew", 0xf0fff0),
+    ("hotpink", 0xff69b4),
+    ("indianred", 0xcd5c5c),
+    ("indigo", 0x4b0082),
+    ("ivory", 0xfffff0),
+    ("khaki", 0xf0e68c),
+    ("lavender", 0xe6e6fa),
+    ("lavenderblush", 0xfff0f5),
+    ("lawngreen", 0x7cfc00),
+    ("lemonchiffon", 0xfffacd),
+    ("lightblue", 0xadd8e6),
+    ("lightcoral", 0xf08080),
+    ("lightcyan", 0xe0ffff),
+    ("lightgoldenrodyellow", 0xfafad2),
+    ("lightgray", 0xd3d3d3),
+    ("lightgreen", 0x90ee90),
+    ("lightgrey", 0xd3d3d3),
+    ("lightpink", 0xffb6c1),
+    ("lightsalmon", 0xffa07a),
+    ("lightseagreen", 0x20b2aa),
+    ("lightskyblue", 0x87cefa),
+    ("lightslategray", 0x778899),
+    ("lightslategrey", 0x778899),
+    ("lightsteelblue", 0xb0c4de),
+    ("lightyellow", 0xffffe0),
+    ("lime", 0x00ff00),
+    ("limegreen", 0x32cd32),
+    ("linen", 0xfaf0e6),
+    ("magenta", 0xff00ff),
+    ("maroon", 0x800000),
+    ("mediumaquamarine", 0x66cdaa),
+    ("mediumblue", 0x0000cd),
+    ("mediumorchid", 0xba55d3),
+    ("mediumpurple", 0x9370db),
+    ("mediumseagreen", 0x3cb371),
+    ("mediumslateblue", 0x7b68ee),
+    ("mediumspringgreen", 0x00fa9a),
+    ("mediumturquoise", 0x48d1cc),
+    ("mediumvioletred", 0xc71585),
+    ("midnightblue", 0x191970),
+    ("mintcream", 0xf5fffa),
+    ("mistyrose", 0xffe4e1),
+    ("moccasin", 0xffe4b5),
+    ("navajowhite", 0xffdead),
+    ("navy", 0x000080),
+    ("oldlace", 0xfdf5e6),
+    ("olive", 0x808000),
+    ("olivedrab", 0x6b8e23),
+    ("orange", 0xffa500),
+    ("orangered", 0xff4500),
+    ("orchid", 0xda70d6),
+    ("palegoldenrod", 0xeee8aa),
+    ("palegreen", 0x98fb98),
+    ("paleturquoise", 0xafeeee),
+    ("palevioletred", 0xdb7093),
+    ("papayawhip", 0xffefd5),
+    ("peachpuff", 0xffdab9),
+    ("peru", 0xcd853f),
+    ("pink", 0xffc0cb),
+    ("plum", 0xdda0dd),
+    ("powderblue", 0xb0e0e6),
+    ("purple", 0x800080),
+    ("rebeccapurple", 0x663399),
+    ("red", 0xff0000),
+    ("rosybrown", 0xbc8f8f),
+    ("royalblue", 0x4169e1),
+    ("saddlebrown", 0x8b4513),
+    ("salmon", 0xfa8072),
+    ("sandybrown", 0xf4a460),
+    ("seagreen", 0x2e8b57),
+    ("seashell", 0xfff5ee),
+    ("sienna", 0xa0522d),
+    ("silver", 0xc0c0c0),
+    ("skyblue", 0x87ceeb),
+    ("slateblue", 0x6a5acd),
+    ("slategray", 0x708090),
+    ("slategrey", 0x708090),
+    ("snow", 0xfffafa),
+    ("springgreen", 0x00ff7f),
+    ("steelblue", 0x4682b4),
+    ("tan", 0xd2b48c),
+    ("teal", 0x008080),
+    ("thistle", 0xd8bfd8),
+    ("tomato", 0xff6347),
+    ("turquoise", 0x40e0d0),
+    ("violet", 0xee82ee),
+    ("wheat", 0xf5deb3),
+    ("white", 0xffffff),
+    ("whitesmoke", 0xf5f5f5),
+    ("yellow", 0xffff00),
+    ("yellowgreen", 0x9acd32),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_xy(actual: CieXy, x: f64, y: f64) {
+        assert!(
+            (actual.x - x).abs() < 0.0005 && (actual.y - y).abs() < 0.0005,
+            "expected xy({}, {}), got {}",
+            x,
+            y,
+            actual
+        );
+    }
+
+    #[test]
+    fn parse_accepts_all_color_notations() {
+        let orange = Color::Rgb {
+            red: 255,
+            green: 165,
+            blue: 0,
+        };
+        assert_eq!(Color::parse("#ffa500").unwrap(), orange);
+        assert_eq!(Color::parse("#FFA500").unwrap(), orange);
+        assert_eq!(Color::parse("Orange").unwrap(), orange);
+        assert_eq!(Color::parse("#f00").unwrap().to_rgb(), Some((255, 0, 0)));
+        assert_eq!(
+            Color::parse("hsv(240, 100%, 50%)").unwrap().to_rgb(),
+            Some((0, 0, 128))
+        );
+        assert_eq!(
+            Color::parse("hsv(39, 100, 100)").unwrap().to_rgb(),
+            Some((255, 166, 0))
+        );
+        assert_eq!(
+            Color::parse("xy(0.3, 0.4)").unwrap(),
+            Color::Xy(CieXy { x: 0.3, y: 0.4 })
+        );
+        for invalid in [
+            "#ff00",
+            "#gg0000",
+            "mauve",
+            "hsv(400, 0, 0)",
+            "xy(0.3)",
+            "xy(2, 0.5)",
+        ] {
+            assert!(
+                Color::parse(invalid).is_err(),
+                "{} should be invalid",
+                invalid
+            );
+        }
+    }
+
+    #[test]
+    fn rgb_to_xy_uses_the_wide_gamut_conversion() {
+        assert_xy(rgb_to_xy(255, 0, 0), 0.7006, 0.2993);
+        assert_xy(rgb_to_xy(0, 255, 0), 0.1724, 0.7468);
+        assert_xy(rgb_to_xy(0, 0, 255), 0.1355, 0.0399);
+        assert_xy(rgb_to_xy(255, 255, 255), 0.3227, 0.3290);
+        assert_eq!(rgb_to_xy(0, 0, 0), WHITE_POINT);
+    }
+
+    #[test]
+    fn gamut_clamps_to_the_closest_color_the_light_can_show() {
+        let red = rgb_to_xy(255, 0, 0);
+        let blue = rgb_to_xy(0, 0, 255);
+        // Pure red and blue are outside every gamut, and closest to its corners.
+        assert_xy(Gamut::B.clamp(red), 0.675, 0.322);
+        assert_xy(Gamut::C.clamp(red), 0.6915, 0.3083);
+        assert_xy(Gamut::A.clamp(blue), 0.138, 0.08);
+        assert_xy(Gamut::C.clamp(blue), 0.1532, 0.0475);
+
+        // Orange is beyond the red-green side of gamut B, and moves onto it.
+        let orange = rgb_to_xy(255, 165, 0);
+        assert!(!Gamut::B.contains(orange));
+        let clamped = Gamut::B.clamp(orange);
+        assert!(clamped.distance(orange) < 0.05);
+        assert!(Gamut::B.contains(clamped));
+        let warm_white = CieXy { x: 0.4573, y: 0.41 };
+        assert_eq!(Gamut::C.clamp(warm_white), warm_white);
+
+        let white = rgb_to_xy(255, 255, 255);
+        for gamut in [Gamut::A, Gamut::B, Gamut::C] {
+            assert!(gamut.contains(white));
+            assert_eq!(gamut.clamp(white), white);
+            assert!(gamut.contains(gamut.red) && gamut.contains(gamut.blue));
+        }
+        assert_eq!(GamutType::C.gamut(), Some(Gamut::C));
+        assert_eq!(
+            serde_json::from_value::<GamutType>(serde_json::json!("other")).unwrap(),
+            GamutType::Other
+        );
+    }
+
+    #[test]
+    fn color_temperature_parses_kelvin_and_mirek() {
+        assert_eq!(
+            ColorTemperature::parse("2700K").unwrap(),
+            ColorTemperature::Kelvin(2700)
+        );
+        assert_eq!(
+            ColorTemperature::parse("6500").unwrap(),
+            ColorTemperature::Kelvin(6500)
+        );
+        assert_eq!(
+            ColorTemperature::parse("370mirek").unwrap(),
+            ColorTemperature::Mirek(370)
+        );
+        assert_eq!(
+            ColorTemperature::parse("153").unwrap(),
+            ColorTemperature::Mirek(153)
+        );
+        assert_eq!(ColorTemperature::Kelvin(2700).mirek(), 370);
+        assert_eq!(ColorTemperature::Kelvin(6500).mirek(), 154);
+        for invalid in ["warm", "0", "-2700K", "70000mirek"] {
+            assert!(ColorTemperature::parse(invalid).is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn color_temperature_converts_to_xy_on_the_planckian_locus() {
+        assert_eq!(ColorTemperature::Mirek(370).kelvin(), 2703);
+        assert_xy(ColorTemperature::Kelvin(2700).to_xy(), 0.4593, 0.4107);
+        assert_xy(ColorTemperature::Kelvin(6500).to_xy(), 0.3135, 0.3236);
+    }
+
+    #[test]
+    fn mirek_schema_checks_or_clamps_the_range() {
+        let schema = MirekSchema {
+            mirek_minimum: 153,
+            mirek_maximum: 454,
+        };
+        assert_eq!(
+            schema.check(ColorTemperature::Kelvin(2700), false).unwrap(),
+            370
+        );
+        assert!(matches!(
+            schema.check(ColorTemperature::Kelvin(1800), false),
+            Err(HueError::InvalidParameter(message)) if message.contains("153-454 mirek (2203K-6536K)")
+        ));
+        assert_eq!(
+            schema.check(ColorTemperature::Kelvin(1800), true).unwrap(),
+            454
+        );
+        assert_eq!(
+            schema.check(ColorTemperature::Mirek(100), true).unwrap(),
+            153
+        );
+    }
+
+    #[test]
+    fn xy_to_rgb_converts_back_for_display() {
+        for rgb in [
+            (255, 0, 0),
+            (0, 255, 0),
+            (0, 0, 255),
+            (255, 165, 0),
+            (255, 255, 255),
+        ] {
+            let (r, g, b) = xy_to_rgb(rgb_to_xy(rgb.0, rgb.1, rgb.2), 1.0);
+            assert!(
+                r.abs_diff(rgb.0) <= 2 && g.abs_diff(rgb.1) <= 2 && b.abs_diff(rgb.2) <= 2,
+                "{:?} converted back to {:?}",
+                rgb,
+                (r, g, b)
+            );
+        }
+        assert_eq!(rgb_to_xy(255, 0, 0).to_hex(), "#ff0000");
+    }
+}
@@ -0,0 +1,188 @@
+//! The configuration file with named bridge profiles.
+//!
+//! A profile holds everything needed to talk to one bridge: its address,
+//! bridge ID, application key and client key. The file is TOML and lives in
+//! the user configuration directory, e.g. `~/.config/philips_hue_lab/config.toml`
+//! on Linux. Set `HUE_CONFIG` to use another file.
+//!
+//! ```toml
+//! active_profile = "home"
+//!
+//! [profiles.home]
+//! bridge = "192.168.1.2"
+//! bridge_id = "001788fffe4f8a2b"
+//! app_key = "your-api-key"
+//! client_key = "0123456789ABCDEF0123456789ABCDEF"
+//! ```
+//!
+//! For older bridges with a self-signed certificate, the profile can also hold
+//! the pinned `cert_fingerprint`, see [`crate::tls`].
+
+use crate::HueError;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// Environment variable overriding the location of the configuration file.
+pub const CONFIG_PATH_ENV: &str = "HUE_CONFIG";
+
+/// The profile used when no profile is selected.
+pub const DEFAULT_PROFILE: &str = "default";
+
+/// The settings for one bridge.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct Profile {
+    /// The IP address (or host name) of the bridge.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bridge: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bridge_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub app_key: Option<String>,
+    /// The entertainment client key.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub client_key: Option<String>,
+    /// The SHA-256 fingerprint of the bridge certificate, when it is pinned
+    /// instead of verified against the Hue root CA.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cert_fingerprint: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct Config {
+    /// The profile used when no profile is selected explicitly.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub active_profile: Option<String>,
+    #[serde(default)]
+    pub profiles: BTreeMap<String, Profile>,
+}
+
+impl Config {
+    /// The configuration file location: `HUE_CONFIG` if set, otherwise
+    /// `philips_hue_lab/config.toml` in the user configuration directory.
+    pub fn default_path() -> Option<PathBuf> {
+        match std::env::var_os(CONFIG_PATH_ENV) {
+            Some(path) => Some(PathBuf::from(path)),
+            None => dirs::config_dir().map(|dir| dir.join("philips_hue_lab").join("config.toml")),
+        }
+    }
+
+    /// Load the configuration file. A missing file gives an empty configuration.
+    pub fn load(path: &Path) -> Result<Config, HueError> {
+        if !path.exists() {
+            return Ok(Config::default());
+        }
+        let text = fs::read_to_string(path).map_err(|e| HueError::Config {
+            message: format!("Could not read configuration file {}", path.display()),
+            source: Box::new(e),
+        })?;
+        toml::from_str(&text).map_err(|e| HueError::Config {
+            message: format!("Invalid configuration file {}", path.display()),
+            source: Box::new(e),
+        })
+    }
+
+    /// Save the configuration file, creating the directory if needed.
+    /// The file holds application keys, so on Unix it is only readable by the owner.
+    pub fn save(&self, path: &Path) -> Result<(), HueError> {
+        let io_error = |e: std::io::Error| HueError::Config {
+            message: format!("Could not write configuration file {}", path.display()),
+            source: Box::new(e),
+        };
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).map_err(io_error)?;
+        }
+        let text = toml::to_string_pretty(self).map_err(|e| HueError::Config {
+            message: String::from("Could not serialize the configuration"),
+            source: Box::new(e),
+        })?;
+        let mut options = fs::OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+        let mut file = options.open(path).map_err(io_error)?;
+        // The mode only applies to new files, so restrict an existing file before writing the keys.
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            file.set_permissions(fs::Permissions::from_mode(0o600))
+                .map_err(io_error)?;
+        }
+        file.write_all(text.as_bytes()).map_err(io_error)
+    }
+
+    /// The name of the profile to use: the given name, the active profile, or the default profile.
+    pub fn profile_name<'a>(&'a self, name: Option<&'a str>) -> &'a str {
+        name.or(self.active_profile.as_deref())
+            .unwrap_or(DEFAULT_PROFILE)
+    }
+
+    pub fn profile(&self, name: &str) -> Option<&Profile> {
+        self.profiles.get(name)
+    }
+
+    /// Store a profile and make it the active profile.
+    pub fn set_profile(&mut self, name: &str, profile: Profile) {
+        self.profiles.insert(name.to_string(), profile);
+        self.active_profile = Some(name.to_string());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn config_round_trips_through_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("nested").join("config.toml");
+        let mut config = Config::default();
+        config.set_profile(
+            "home",
+            Profile {
+                bridge: Some(String::from("192.168.1.2")),
+                bridge_id: Some(String::from("001788fffe4f8a2b")),
+                app_key: Some(String::from("key")),
+                client_key: None,
+                cert_fingerprint: None,
+            },
+        );
+        config.save(&path).unwrap();
+
+        let loaded = Config::load(&path).unwrap();
+        assert_eq!(loaded, config);
+        assert_eq!(loaded.profile_name(None), "home");
+        assert_eq!(loaded.profile_name(Some("office")), "office");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn saved_file_is_only_readable_by_the_owner() {
+        use std::os::unix::fs::PermissionsExt;
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        fs::write(&path, "").unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o644)).unwrap();
+        Config::default().save(&path).unwrap();
+        let mode = fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+
+        let new_path = dir.path().join("new.toml");
+        Config::default().save(&new_path).unwrap();
+        let mode = fs::metadata(&new_path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+
+    #[test]
+    fn load_missing_file_gives_empty_config() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = Config::load(&dir.path().join("config.toml")).unwrap();
+        assert_eq!(config, Config::default());
+        assert_eq!(config.profile_name(None), DEFAULT_PROFILE);
+    }
+}
@@ -0,0 +1,175 @@
+//! Discovery of Hue Bridges on the local network.
+//!
+//! Bridges announce themselves with mDNS as `_hue._tcp` services, with the
+//! bridge ID and model in the TXT record.
+//! See documentation at <https://developers.meethue.com/develop/application-design-guidance/hue-bridge-discovery/>
+
+use crate::{BridgeIp, HueError};
+use mdns_sd::{ServiceDaemon, ServiceEvent, ServiceInfo};
+use serde::Serialize;
+use std::net::IpAddr;
+use std::time::{Duration, Instant};
+
+const HUE_SERVICE_TYPE: &str = "_hue._tcp.local.";
+
+/// A Hue Bridge found on the network.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct DiscoveredBridge {
+    /// The bridge ID, e.g. `001788fffe4f8a2b`.
+    pub id: String,
+    /// The service instance name, e.g. `Philips Hue - 4F8A2B`.
+    pub name: String,
+    pub addresses: Vec<IpAddr>,
+    pub port: u16,
+    /// The model ID, e.g. `BSB002`.
+    pub model_id: Option<String>,
+}
+
+impl DiscoveredBridge {
+    /// The address to use for the bridge, preferring IPv4.
+    pub fn bridge_ip(&self) -> Option<BridgeIp> {
+        let address = self
+            .addresses
+            .iter()
+            .find(|a| a.is_ipv4())
+            .or(self.addresses.first())?;
+        Some(BridgeIp(match address {
+            IpAddr::V4(v4) => v4.to_string(),
+            IpAddr::V6(v6) => format!("[{}]", v6),
+        }))
+    }
+}
+
+/// Browse the network for Hue Bridges for the given duration.
+pub fn discover_bridges(timeout: Duration) -> Result<Vec<DiscoveredBridge>, HueError> {
+    let mdns = ServiceDaemon::new()
+        .map_err(|e| HueError::Discovery(format!("Could not start mDNS discovery: {}", e)))?;
+    let receiver = mdns
+        .browse(HUE_SERVICE_TYPE)
+        .map_err(|e| HueError::Discovery(format!("Could not browse for Hue Bridges: {}", e)))?;
+
+    let mut bridges: Vec<DiscoveredBridge> = Vec::new();
+    let deadline = Instant::now() + timeout;
+    while let Some(remaining) = deadline.checked_duration_since(Instant::now()) {
+        match receiver.recv_timeout(remaining) {
+            Ok(ServiceEvent::ServiceResolved(info)) => {
+                let bridge = bridge_from_service_info(&info);
+                match bridges.iter_mut().find(|b| b.id == bridge.id) {
+                    Some(existing) => *existing = bridge,
+                    None => bridges.push(bridge),
+                }
+            }
+            Ok(_) => {}
+            Err(_) => break,
+        }
+    }
+    let _ = mdns.shutdown();
+    bridges.sort_by(|a, b| a.id.cmp(&b.id));
+    Ok(bridges)
+}
+
+/// Find the single Hue Bridge on the network.
+/// Fails if there are no bridges or more than one.
+pub fn discover_single_bridge(timeout: Duration) -> Result<DiscoveredBridge, HueError> {
+    let mut bridges = discover_bridges(timeout)?;
+    match bridges.len() {
+        0 => Err(HueError::Discovery(String::from(
+            "No Hue Bridge found on the network.",
+        ))),
+        1 => Ok(bridges.remove(0)),
+        _ => {
+            let bridge_info: Vec<String> = bridges
+                .iter()
+                .map(|b| match b.bridge_ip() {
+                    Some(ip) => format!("{} ({})", b.id, ip.0),
+                    None => b.id.clone(),
+                })
+                .collect();
+            Err(HueError::Discovery(format!(
+                "Multiple Hue Bridges found on the network. Please select one with --bridge: {}",
+                bridge_info.join(", ")
+            )))
+        }
+    }
+}
+
+fn bridge_from_service_info(info: &ServiceInfo) -> DiscoveredBridge {
+    let mut addresses: Vec<IpAddr> = info.get_addresses().iter().copied().collect();
+    addresses.sort();
+    bridge_from_service(
+        info.get_fullname(),
+        addresses,
+        info.get_port(),
+        info.get_property_val_str("bridgeid"),
+        info.get_property_val_str("modelid"),
+    )
+}
+
+fn bridge_from_service(
+    fullname: &str,
+    addresses: Vec<IpAddr>,
+    port: u16,
+    bridge_id: Option<&str>,
+    model_id: Option<&str>,
+) -> DiscoveredBridge {
+    let name = fullname
+        .strip_suffix(HUE_SERVICE_TYPE)
+        .map(|n| n.trim_end_matches('.'))
+        .unwrap_or(fullname)
+        .to_string();
+    DiscoveredBridge {
+        // Older firmware does not publish the bridge ID, fall back to the instance name.
+        id: bridge_id
+            .map(|id| id.to_lowercase())
+            .unwrap_or_else(|| name.clone()),
+        name,
+        addresses,
+        port,
+        model_id: model_id.map(String::from),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::{Ipv4Addr, Ipv6Addr};
+
+    #[test]
+    fn bridge_from_service_reads_txt_properties() {
+        let bridge = bridge_from_service(
+            "Philips Hue - 4F8A2B._hue._tcp.local.",
+            vec![
+                IpAddr::V4(Ipv4Addr::new(192, 168, 1, 2)),
+                IpAddr::V6(Ipv6Addr::new(
+                    0xfe80, 0, 0, 0, 0x217, 0x88ff, 0xfe4f, 0x8a2b,
+                )),
+            ],
+            443,
+            Some("001788FFFE4F8A2B"),
+            Some("BSB002"),
+        );
+        assert_eq!(bridge.id, "001788fffe4f8a2b");
+        assert_eq!(bridge.name, "Philips Hue - 4F8A2B");
+        assert_eq!(bridge.model_id, Some(String::from("BSB002")));
+        assert_eq!(
+            bridge.bridge_ip(),
+            Some(BridgeIp(String::from("192.168.1.2")))
+        );
+    }
+
+    #[test]
+    fn bridge_ip_brackets_ipv6_addresses() {
+        let bridge = bridge_from_service(
+            "Philips Hue - 4F8A2B._hue._tcp.local.",
+            vec![IpAddr::V6(Ipv6Addr::new(0xfe80, 0, 0, 0, 0, 0, 0, 1))],
+            443,
+            None,
+            None,
+        );
+        assert_eq!(bridge.id, "Philips Hue - 4F8A2B");
+        assert_eq!(
+            bridge.bridge_ip(),
+            Some(BridgeIp(String::from("[fe80::1]")))
+        );
+    }
+}
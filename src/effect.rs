@@ -0,0 +1,211 @@
+//! The built-in behaviors of lights: effects, timed effects, alerts and signals.
+//!
+//! Each light advertises the values it supports, e.g. in `effects.effect_values`,
+//! and the client checks the requested value against them before sending it.
+//! See documentation at <https://developers.meethue.com/develop/hue-api-v2/api-reference/#resource_light_get>
+
+use crate::HueError;
+use serde::{Serialize, Serializer};
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+use std::time::Duration;
+
+/// The longest duration of a signal the bridge accepts.
+pub const MAX_SIGNAL_DURATION: Duration = Duration::from_millis(65_534_000);
+
+macro_rules! light_values {
+    ($(#[$meta:meta])* $type:ident, $what:literal { $($variant:ident => $name:literal,)* }) => {
+        $(#[$meta])*
+        #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+        pub enum $type {
+            $(
+                #[doc = concat!("`", $name, "`")]
+                $variant,
+            )*
+        }
+
+        impl $type {
+            /// All the values.
+            pub const ALL: &'static [$type] = &[$($type::$variant),*];
+
+            /// The name of the value in the API, e.g. `no_effect`.
+            pub fn as_str(&self) -> &'static str {
+                match self {
+                    $($type::$variant => $name,)*
+                }
+            }
+        }
+
+        impl Display for $type {
+            fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+                f.write_str(self.as_str())
+            }
+        }
+
+        impl FromStr for $type {
+            type Err = HueError;
+
+            fn from_str(name: &str) -> Result<Self, Self::Err> {
+                $type::ALL
+                    .iter()
+                    .find(|value| value.as_str() == name)
+                    .copied()
+                    .ok_or_else(|| {
+                        let names: Vec<&str> = $type::ALL.iter().map($type::as_str).collect();
+                        HueError::Usage(format!(
+                            concat!("Invalid ", $what, " '{}'. Use one of: {}"),
+                            name,
+                            names.join(", ")
+                        ))
+                    })
+            }
+        }
+
+        impl Serialize for $type {
+            fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                serializer.serialize_str(self.as_str())
+            }
+        }
+    };
+}
+
+light_values!(
+    /// An effect that plays on the light until it is stopped with `no_effect`.
+    Effect, "effect" {
+        NoEffect => "no_effect",
+        Candle => "candle",
+        Fire => "fire",
+        Sparkle => "sparkle",
+        Prism => "prism",
+        Opal => "opal",
+        Glisten => "glisten",
+        Underwater => "underwater",
+        Cosmos => "cosmos",
+        Sunbeam => "sunbeam",
+        Enchant => "enchant",
+    }
+);
+
+light_values!(
+    /// An effect that plays on the light for a given duration.
+    TimedEffect, "timed effect" {
+        NoEffect => "no_effect",
+        Sunrise => "sunrise",
+        Sunset => "sunset",
+    }
+);
+
+light_values!(
+    /// An alert that plays on the light once, e.g. to identify it.
+    AlertAction, "alert" {
+        Breathe => "breathe",
+    }
+);
+
+light_values!(
+    /// A signal that the light shows for a given duration.
+    Signal, "signal" {
+        NoSignal => "no_signal",
+        OnOff => "on_off",
+        OnOffColor => "on_off_color",
+        Alternating => "alternating",
+    }
+);
+
+impl Signal {
+    /// How many colors the signal takes: one for `on_off_color` and two for `alternating`.
+    pub fn color_count(&self) -> usize {
+        match self {
+            Signal::NoSignal | Signal::OnOff => 0,
+            Signal::OnOffColor => 1,
+            Signal::Alternating => 2,
+        }
+    }
+
+    /// Check that the signal is given the colors it takes.
+    pub fn check_colors<T>(&self, colors: &[T]) -> Result<(), HueError> {
+        match colors.len() == self.color_count() {
+            true => Ok(()),
+            false => Err(HueError::Usage(format!(
+                "The {} signal takes {} color(s), but {} were given",
+                self,
+                self.color_count(),
+                colors.len()
+            ))),
+        }
+    }
+}
+
+/// The duration of a signal, in milliseconds.
+pub(crate) fn signal_millis(duration: Duration) -> Result<u32, HueError> {
+    match duration <= MAX_SIGNAL_DURATION {
+        true => Ok(duration.as_millis() as u32),
+        false => Err(HueError::InvalidParameter(format!(
+            "The signal duration of {:?} is longer than the bridge allows ({:?})",
+            duration, MAX_SIGNAL_DURATION
+        ))),
+    }
+}
+
+/// Check that a light advertises the value in `supported`, or `None` if it lacks the feature.
+pub(crate) fn check_supported<T: Display>(
+    light_id: &str,
+    what: &str,
+    value: &T,
+    supported: Option<&[String]>,
+) -> Result<(), HueError> {
+    let Some(supported) = supported else {
+        return Err(HueError::InvalidParameter(format!(
+            "Light {} does not support {}s",
+            light_id, what
+        )));
+    };
+    let value = value.to_string();
+    match supported.contains(&value) {
+        true => Ok(()),
+        false => Err(HueError::InvalidParameter(format!(
+            "Light {} does not support the {} {}. It supports: {}",
+            light_id,
+            what,
+            value,
+            supported.join(", ")
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn values_parse_from_their_api_names() {
+        assert_eq!("candle".parse::<Effect>().unwrap(), Effect::Candle);
+        assert_eq!(
+            "sunrise".parse::<TimedEffect>().unwrap(),
+            TimedEffect::Sunrise
+        );
+        assert_eq!(
+            "on_off_color".parse::<Signal>().unwrap(),
+            Signal::OnOffColor
+        );
+        assert_eq!(
+            serde_json::to_value(Effect::NoEffect).unwrap(),
+            serde_json::json!("no_effect")
+        );
+        assert!(matches!(
+            "disco".parse::<Effect>(),
+            Err(HueError::Usage(message)) if message.contains("candle, fire")
+        ));
+    }
+
+    #[test]
+    fn check_supported_uses_the_advertised_values() {
+        let supported = vec![String::from("no_effect"), String::from("candle")];
+        assert!(check_supported("light", "effect", &Effect::Candle, Some(&supported)).is_ok());
+        assert!(matches!(
+            check_supported("light", "effect", &Effect::Prism, Some(&supported)),
+            Err(HueError::InvalidParameter(message)) if message.contains("no_effect, candle")
+        ));
+        assert!(check_supported("light", "effect", &Effect::Candle, None).is_err());
+    }
+}
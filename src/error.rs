@@ -0,0 +1,321 @@
+//! Errors from the Hue client.
+
+use serde::Deserialize;
+use serde_json::Value;
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+
+type BoxError = Box<dyn Error + Send + Sync>;
+
+/// An error from talking to the Hue Bridge.
+///
+/// Each variant has a distinct process exit code, see [`HueError::exit_code`].
+#[derive(Debug)]
+pub enum HueError {
+    /// The bridge could not be reached, or the connection failed.
+    Transport(BoxError),
+    /// The TLS connection to the bridge could not be established or verified.
+    Tls(BoxError),
+    /// The bridge answered with an unexpected HTTP status.
+    HttpStatus {
+        status: u16,
+        description: Option<String>,
+    },
+    /// The application key is missing or not accepted by the bridge
+    /// (HTTP 401/403 or Hue error type 1).
+    Unauthorized(String),
+    /// The link button on the bridge has not been pressed (Hue error type 101).
+    LinkButtonNotPressed,
+    /// The resource does not exist (HTTP 404 or Hue error type 3).
+    ResourceNotFound(String),
+    /// The bridge is rejecting requests because too many were sent (HTTP 429).
+    RateLimited,
+    /// The request has an invalid parameter or value
+    /// (HTTP 400 or Hue error types 2, 5, 6, 7, 8 and 201).
+    InvalidParameter(String),
+    /// Errors reported by the CLIP v2 API in the `errors` array of a response.
+    Clip(Vec<String>),
+    /// Any other error reported by the Hue API.
+    Api {
+        type_code: i64,
+        address: String,
+        description: String,
+    },
+    /// The response from the bridge could not be parsed.
+    Parse {
+        message: String,
+        source: Option<serde_json::Error>,
+    },
+    /// The configuration file could not be read or written.
+    Config { message: String, source: BoxError },
+    /// Bridges could not be discovered on the network.
+    Discovery(String),
+    /// The command-line arguments or library inputs are incomplete or inconsistent.
+    Usage(String),
+}
+
+impl HueError {
+    /// The process exit code for the error.
+    ///
+    /// | Code | Error                  |
+    /// |------|------------------------|
+    /// | 2    | `Usage`                |
+    /// | 3    | `Transport`            |
+    /// | 4    | `Tls`                  |
+    /// | 5    | `HttpStatus`           |
+    /// | 6    | `Unauthorized`         |
+    /// | 7    | `LinkButtonNotPressed` |
+    /// | 8    | `ResourceNotFound`     |
+    /// | 9    | `RateLimited`          |
+    /// | 10   | `InvalidParameter`     |
+    /// | 11   | `Api`, `Clip`          |
+    /// | 12   | `Parse`                |
+    /// | 13   | `Config`               |
+    /// | 14   | `Discovery`            |
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            HueError::Usage(_) => 2,
+            HueError::Transport(_) => 3,
+            HueError::Tls(_) => 4,
+            HueError::HttpStatus { .. } => 5,
+            HueError::Unauthorized(_) => 6,
+            HueError::LinkButtonNotPressed => 7,
+            HueError::ResourceNotFound(_) => 8,
+            HueError::RateLimited => 9,
+            HueError::InvalidParameter(_) => 10,
+            HueError::Api { .. } | HueError::Clip(_) => 11,
+            HueError::Parse { .. } => 12,
+            HueError::Config { .. } => 13,
+            HueError::Discovery(_) => 14,
+        }
+    }
+
+    pub(crate) fn parse(message: impl Into<String>, source: serde_json::Error) -> Self {
+        HueError::Parse {
+            message: message.into(),
+            source: Some(source),
+        }
+    }
+
+    /// Map an unsuccessful HTTP response to an error.
+    /// The CLIP v2 error descriptions in the body are included, if present.
+    pub(crate) fn from_status(status: u16, body: &str) -> Self {
+        let description = serde_json::from_str::<Value>(body).ok().and_then(|json| {
+            let descriptions: Vec<String> = json
+                .get("errors")?
+                .as_array()?
+                .iter()
+                .filter_map(|e| e.get("description")?.as_str().map(String::from))
+                .collect();
+            (!descriptions.is_empty()).then(|| descriptions.join("; "))
+        });
+        let message = description
+            .clone()
+            .unwrap_or_else(|| format!("HTTP status {}", status));
+        match status {
+            400 => HueError::InvalidParameter(message),
+            401 | 403 => HueError::Unauthorized(message),
+            404 => HueError::ResourceNotFound(message),
+            429 => HueError::RateLimited,
+            _ => HueError::HttpStatus {
+                status,
+                description,
+            },
+        }
+    }
+}
+
+impl Display for HueError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            HueError::Transport(e) => write!(f, "Could not communicate with the Hue Bridge: {}", e),
+            HueError::Tls(e) => write!(f, "Secure connection to the Hue Bridge failed: {}", e),
+            HueError::HttpStatus {
+                status,
+                description: None,
+            } => write!(f, "The Hue Bridge answered with HTTP status {}", status),
+            HueError::HttpStatus {
+                status,
+                description: Some(description),
+            } => write!(
+                f,
+                "The Hue Bridge answered with HTTP status {}: {}",
+                status, description
+            ),
+            HueError::Unauthorized(description) => write!(f, "Unauthorized: {}", description),
+            HueError::LinkButtonNotPressed => {
+                write!(f, "The link button on the Hue Bridge has not been pressed.")
+            }
+            HueError::ResourceNotFound(description) => write!(f, "Not found: {}", description),
+            HueError::RateLimited => write!(
+                f,
+                "The Hue Bridge is rate limiting requests. Please slow down."
+            ),
+            HueError::InvalidParameter(description) => {
+                write!(f, "Invalid parameter: {}", description)
+            }
+            HueError::Clip(descriptions) => {
+                write!(f, "The Hue Bridge reported: {}", descriptions.join("; "))
+            }
+            HueError::Api {
+                type_code,
+                address,
+                description,
+            } => write!(
+                f,
+                "Hue API error {} at '{}': {}",
+                type_code, address, description
+            ),
+            HueError::Parse {
+                message,
+                source: None,
+            } => write!(f, "{}", message),
+            HueError::Parse {
+                message,
+                source: Some(source),
+            } => write!(f, "{}: {}", message, source),
+            HueError::Config { message, source } => write!(f, "{}: {}", message, source),
+            HueError::Discovery(message) => write!(f, "{}", message),
+            HueError::Usage(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl Error for HueError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            HueError::Transport(e) | HueError::Tls(e) => Some(e.as_ref()),
+            HueError::Config { source, .. } => Some(source.as_ref()),
+            HueError::Parse {
+                source: Some(source),
+                ..
+            } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+impl From<reqwest::Error> for HueError {
+    fn from(e: reqwest::Error) -> Self {
+        if e.is_decode() {
+            return HueError::Parse {
+                message: format!("Invalid response from the Hue Bridge: {}", e),
+                source: None,
+            };
+        }
+        match is_tls_error(&e) {
+            true => HueError::Tls(Box::new(e)),
+            false => HueError::Transport(Box::new(e)),
+        }
+    }
+}
+
+/// True if the error, or any error in its source chain, is a TLS error from rustls.
+/// The TLS connector reports them wrapped in I/O errors, whose `source()` skips
+/// the wrapped error, so the chain follows the wrapped error of those instead.
+fn is_tls_error(e: &(dyn Error + 'static)) -> bool {
+    let mut current = Some(e);
+    while let Some(error) = current {
+        if error.is::<rustls::Error>() {
+            return true;
+        }
+        current = match error.downcast_ref::<std::io::Error>() {
+            Some(io_error) => io_error
+                .get_ref()
+                .map(|wrapped| wrapped as &(dyn Error + 'static)),
+            None => error.source(),
+        };
+    }
+    false
+}
+
+/// This is the API wire format of the Hue Error message details.
+#[derive(Deserialize, Debug, PartialEq)]
+pub(crate) struct HueApiErrorMessage {
+    #[serde(rename = "type")]
+    pub(crate) type_value: i64,
+    #[serde(default)]
+    pub(crate) address: String,
+    #[serde(default)]
+    pub(crate) description: String,
+}
+
+/// This is the API wire format of a CLIP v2 error.
+#[derive(Deserialize, Debug, PartialEq)]
+pub(crate) struct HueApiClipError {
+    pub(crate) description: String,
+}
+
+impl From<HueApiErrorMessage> for HueError {
+    /// Map the Hue API error types.
+    /// See <https://developers.meethue.com/develop/hue-api/error-messages/>
+    fn from(e: HueApiErrorMessage) -> Self {
+        match e.type_value {
+            1 => HueError::Unauthorized(e.description),
+            3 => HueError::ResourceNotFound(format!("{} ({})", e.description, e.address)),
+            2 | 5 | 6 | 7 | 8 | 201 => {
+                HueError::InvalidParameter(format!("{} ({})", e.description, e.address))
+            }
+            101 => HueError::LinkButtonNotPressed,
+            _ => HueError::Api {
+                type_code: e.type_value,
+                address: e.address,
+                description: e.description,
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tls_errors_are_found_by_type_not_by_message() {
+        let tls = std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            rustls::Error::InvalidCertificate(rustls::CertificateError::UnknownIssuer),
+        );
+        assert!(is_tls_error(&tls));
+        let nested = std::io::Error::other(tls);
+        assert!(is_tls_error(&nested));
+        let other = std::io::Error::other("no certificate in the handshake");
+        assert!(!is_tls_error(&other));
+    }
+
+    #[test]
+    fn api_error_types_are_mapped() {
+        let error = |type_value| {
+            HueError::from(HueApiErrorMessage {
+                type_value,
+                address: String::from("/lights/1/state/bri"),
+                description: String::from("description"),
+            })
+        };
+        assert!(matches!(error(1), HueError::Unauthorized(_)));
+        assert!(matches!(error(3), HueError::ResourceNotFound(_)));
+        assert!(matches!(error(7), HueError::InvalidParameter(_)));
+        assert!(matches!(error(101), HueError::LinkButtonNotPressed));
+        assert!(matches!(error(901), HueError::Api { type_code: 901, .. }));
+    }
+
+    #[test]
+    fn http_status_errors_include_clip_descriptions() {
+        let body = r#"{"errors": [{"description": "unauthorized user"}], "data": []}"#;
+        let error = HueError::from_status(403, body);
+        assert!(matches!(error, HueError::Unauthorized(ref d) if d == "unauthorized user"));
+        assert_eq!(error.exit_code(), 6);
+
+        assert!(matches!(
+            HueError::from_status(429, ""),
+            HueError::RateLimited
+        ));
+        assert!(matches!(
+            HueError::from_status(503, "Service Unavailable"),
+            HueError::HttpStatus {
+                status: 503,
+                description: None
+            }
+        ));
+    }
+}
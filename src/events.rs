@@ -0,0 +1,478 @@
+//! The CLIP v2 event stream.
+//!
+//! The bridge pushes changes to resources as server-sent events on
+//! `/eventstream/clip/v2`. Each message carries a JSON array of events, and
+//! each event carries the changed resources.
+//! See documentation at <https://developers.meethue.com/develop/hue-api-v2/core-concepts/#events>
+
+use crate::resource::ResourceType;
+use crate::HueError;
+use reqwest::blocking;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::VecDeque;
+use std::fmt::{Display, Formatter};
+use std::io::{BufRead, BufReader};
+
+/// A single event from the event stream.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct HueEvent {
+    pub id: String,
+    #[serde(rename = "creationtime")]
+    pub creation_time: String,
+    #[serde(rename = "type")]
+    pub event_type: HueEventType,
+    #[serde(deserialize_with = "deserialize_event_resources")]
+    pub data: Vec<EventResource>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum HueEventType {
+    Add,
+    Update,
+    Delete,
+    Error,
+    #[serde(other)]
+    Unknown,
+}
+
+impl Display for HueEventType {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            HueEventType::Add => "add",
+            HueEventType::Update => "update",
+            HueEventType::Delete => "delete",
+            HueEventType::Error => "error",
+            HueEventType::Unknown => "unknown",
+        };
+        f.write_str(name)
+    }
+}
+
+/// A reference to a resource on the bridge.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ResourceRef {
+    pub rid: String,
+    pub rtype: ResourceType,
+}
+
+/// A resource that was added, changed or deleted.
+/// Only the changed properties are present in an update event.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct EventResource {
+    pub id: String,
+    /// The resource type, e.g. `light` or `motion`.
+    pub rtype: ResourceType,
+    pub id_v1: Option<String>,
+    pub owner: Option<ResourceRef>,
+    pub change: ResourceChange,
+}
+
+/// The changed properties of a resource, for the resource types we know about.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(untagged)]
+pub enum ResourceChange {
+    /// A `light` or `grouped_light` change.
+    Light(LightChange),
+    Motion(MotionChange),
+    Button(ButtonChange),
+    Temperature(TemperatureChange),
+    LightLevel(LightLevelChange),
+    DevicePower(DevicePowerChange),
+    /// Any other resource type, or a change we could not decode, as raw JSON.
+    Other(Value),
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct LightChange {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub on: Option<OnChange>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dimming: Option<DimmingChange>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub color: Option<ColorChange>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub color_temperature: Option<ColorTemperatureChange>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct OnChange {
+    pub on: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct DimmingChange {
+    pub brightness: f32,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ColorChange {
+    pub xy: XyChange,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct XyChange {
+    pub x: f32,
+    pub y: f32,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ColorTemperatureChange {
+    /// `None` when the light is not in color temperature mode.
+    pub mirek: Option<u16>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct MotionChange {
+    pub motion: MotionReport,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct MotionReport {
+    pub motion: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ButtonChange {
+    pub button: ButtonReport,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ButtonReport {
+    /// The button event, e.g. `initial_press`, `short_release` or `long_press`.
+    pub last_event: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct TemperatureChange {
+    pub temperature: TemperatureReport,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct TemperatureReport {
+    /// Temperature in degrees Celsius.
+    pub temperature: f32,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct LightLevelChange {
+    pub light: LightLevelReport,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct LightLevelReport {
+    /// Light level as 10000*log10(lux)+1.
+    pub light_level: u32,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct DevicePowerChange {
+    pub power_state: PowerState,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct PowerState {
+    pub battery_state: Option<String>,
+    pub battery_level: Option<u8>,
+}
+
+impl EventResource {
+    /// Decode a resource from the `data` array of an event.
+    /// Resources with an unknown type or unexpected properties are kept as [`ResourceChange::Other`].
+    fn from_json(value: Value) -> Result<Self, serde_json::Error> {
+        #[derive(Deserialize)]
+        struct Header {
+            id: String,
+            #[serde(rename = "type")]
+            rtype: ResourceType,
+            id_v1: Option<String>,
+            owner: Option<ResourceRef>,
+        }
+        let header = serde_json::from_value::<Header>(value.clone())?;
+        let change = match header.rtype {
+            ResourceType::Light | ResourceType::GroupedLight => {
+                serde_json::from_value(value.clone()).map(ResourceChange::Light)
+            }
+            ResourceType::Motion | ResourceType::CameraMotion => {
+                serde_json::from_value(value.clone()).map(ResourceChange::Motion)
+            }
+            ResourceType::Button => {
+                serde_json::from_value(value.clone()).map(ResourceChange::Button)
+            }
+            ResourceType::Temperature => {
+                serde_json::from_value(value.clone()).map(ResourceChange::Temperature)
+            }
+            ResourceType::LightLevel => {
+                serde_json::from_value(value.clone()).map(ResourceChange::LightLevel)
+            }
+            ResourceType::DevicePower => {
+                serde_json::from_value(value.clone()).map(ResourceChange::DevicePower)
+            }
+            _ => Ok(ResourceChange::Other(value.clone())),
+        }
+        .unwrap_or(ResourceChange::Other(value));
+        Ok(EventResource {
+            id: header.id,
+            rtype: header.rtype,
+            id_v1: header.id_v1,
+            owner: header.owner,
+            change,
+        })
+    }
+}
+
+fn deserialize_event_resources<'de, D>(deserializer: D) -> Result<Vec<EventResource>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    Vec::<Value>::deserialize(deserializer)?
+        .into_iter()
+        .map(|v| EventResource::from_json(v).map_err(serde::de::Error::custom))
+        .collect()
+}
+
+impl Display for ResourceChange {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ResourceChange::Light(light) => {
+                let mut parts = Vec::new();
+                if let Some(on) = &light.on {
+                    parts.push(format!("on={}", on.on));
+                }
+                if let Some(dimming) = &light.dimming {
+                    parts.push(format!("brightness={}", dimming.brightness));
+                }
+                if let Some(color) = &light.color {
+                    parts.push(format!("xy=({}, {})", color.xy.x, color.xy.y));
+                }
+                if let Some(ColorTemperatureChange { mirek: Some(mirek) }) =
+                    &light.color_temperature
+                {
+                    parts.push(format!("mirek={}", mirek));
+                }
+                f.write_str(&parts.join(" "))
+            }
+            ResourceChange::Motion(m) => write!(f, "motion={}", m.motion.motion),
+            ResourceChange::Button(b) => write!(f, "button={}", b.button.last_event),
+            ResourceChange::Temperature(t) => {
+                write!(f, "temperature={}", t.temperature.temperature)
+            }
+            ResourceChange::LightLevel(l) => write!(f, "light_level={}", l.light.light_level),
+            ResourceChange::DevicePower(p) => match p.power_state.battery_level {
+                Some(level) => write!(f, "battery_level={}", level),
+                None => write!(f, "battery_state={:?}", p.power_state.battery_state),
+            },
+            ResourceChange::Other(value) => {
+                // Show only the changed properties, not the identifying ones.
+                let mut value = value.clone();
+                if let Some(object) = value.as_object_mut() {
+                    for key in ["id", "id_v1", "owner", "type"] {
+                        object.remove(key);
+                    }
+                }
+                write!(f, "{}", value)
+            }
+        }
+    }
+}
+
+/// Incremental parser for the server-sent events wire format.
+/// Feed it one line at a time; it returns the data of each complete message.
+#[derive(Debug, Default)]
+pub(crate) struct SseParser {
+    data: Vec<String>,
+}
+
+impl SseParser {
+    /// Process a line (without the line terminator).
+    /// Returns the message data when the line completes a message.
+    pub(crate) fn push_line(&mut self, line: &str) -> Option<String> {
+        if line.is_empty() {
+            return match self.data.is_empty() {
+                true => None,
+                false => Some(std::mem::take(&mut self.data).join("\n")),
+            };
+        }
+        // Lines starting with a colon are comments, the bridge uses them as keep-alives.
+        if line.starts_with(':') {
+            return None;
+        }
+        let (field, value) = line.split_once(':').unwrap_or((line, ""));
+        if field == "data" {
+            self.data
+                .push(value.strip_prefix(' ').unwrap_or(value).to_string());
+        }
+        None
+    }
+}
+
+/// Parse the data of an event stream message into events.
+pub(crate) fn parse_event_message(data: &str) -> Result<Vec<HueEvent>, HueError> {
+    serde_json::from_str::<Vec<HueEvent>>(data)
+        .map_err(|e| HueError::parse(format!("Invalid event stream message {}", data), e))
+}
+
+/// A blocking iterator over the events from the bridge.
+/// The iterator ends when the bridge closes the connection.
+pub struct EventStream {
+    reader: BufReader<blocking::Response>,
+    parser: SseParser,
+    pending: VecDeque<HueEvent>,
+}
+
+impl EventStream {
+    pub(crate) fn new(response: blocking::Response) -> Self {
+        EventStream {
+            reader: BufReader::new(response),
+            parser: SseParser::default(),
+            pending: VecDeque::new(),
+        }
+    }
+}
+
+impl Iterator for EventStream {
+    type Item = Result<HueEvent, HueError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(event) = self.pending.pop_front() {
+                return Some(Ok(event));
+            }
+            let mut line = String::new();
+            match self.reader.read_line(&mut line) {
+                Ok(0) => return None,
+                Ok(_) => {
+                    if let Err(e) = queue_events(&mut self.parser, &mut self.pending, &line) {
+                        return Some(Err(e));
+                    }
+                }
+                Err(e) => return Some(Err(HueError::Transport(Box::new(e)))),
+            }
+        }
+    }
+}
+
+/// Feed a line of the event stream to the parser, queueing the events of a completed message.
+fn queue_events(
+    parser: &mut SseParser,
+    pending: &mut VecDeque<HueEvent>,
+    line: &str,
+) -> Result<(), HueError> {
+    let line = line.trim_end_matches(['\r', '\n']);
+    if let Some(data) = parser.push_line(line) {
+        log::debug!("Event stream message: {}", data);
+        pending.extend(parse_event_message(&data)?);
+    }
+    Ok(())
+}
+
+/// An asynchronous stream of the events from the bridge.
+/// The stream ends when the bridge closes the connection.
+#[cfg(feature = "async")]
+pub struct AsyncEventStream {
+    response: reqwest::Response,
+    buffer: Vec<u8>,
+    parser: SseParser,
+    pending: VecDeque<HueEvent>,
+}
+
+#[cfg(feature = "async")]
+impl AsyncEventStream {
+    pub(crate) fn new(response: reqwest::Response) -> Self {
+        AsyncEventStream {
+            response,
+            buffer: Vec::new(),
+            parser: SseParser::default(),
+            pending: VecDeque::new(),
+        }
+    }
+
+    /// Wait for the next event. Returns `None` when the bridge closes the event stream.
+    pub async fn next_event(&mut self) -> Option<Result<HueEvent, HueError>> {
+        loop {
+            if let Some(event) = self.pending.pop_front() {
+                return Some(Ok(event));
+            }
+            if let Some(end) = self.buffer.iter().position(|byte| *byte == b'\n') {
+                let line: Vec<u8> = self.buffer.drain(..=end).collect();
+                let line = String::from_utf8_lossy(&line);
+                if let Err(e) = queue_events(&mut self.parser, &mut self.pending, &line) {
+                    return Some(Err(e));
+                }
+                continue;
+            }
+            match self.response.chunk().await {
+                Ok(Some(chunk)) => self.buffer.extend_from_slice(&chunk),
+                Ok(None) => return None,
+                Err(e) => return Some(Err(HueError::from(e))),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sse_parser_returns_data_of_complete_messages() {
+        let mut parser = SseParser::default();
+        assert_eq!(parser.push_line(": hi"), None);
+        assert_eq!(parser.push_line(""), None);
+        assert_eq!(parser.push_line("id: 1700000000:0"), None);
+        assert_eq!(parser.push_line("data: [1,"), None);
+        assert_eq!(parser.push_line("data:2]"), None);
+        assert_eq!(parser.push_line(""), Some(String::from("[1,\n2]")));
+    }
+
+    #[test]
+    fn parse_event_message_decodes_typed_changes() {
+        let data = serde_json::json!([
+            {
+                "creationtime": "2024-01-01T12:00:00Z",
+                "id": "b9e6f5a4-3c2d-4e1f-8a7b-6c5d4e3f2a1b",
+                "type": "update",
+                "data": [
+                    {
+                        "id": "53ca6e61-5e40-4760-9e2e-6d2f48594901",
+                        "id_v1": "/lights/2",
+                        "owner": {"rid": "94860050-1d86-4b79-8583-1be7dce05197", "rtype": "device"},
+                        "on": {"on": true},
+                        "dimming": {"brightness": 42.5},
+                        "type": "light"
+                    },
+                    {
+                        "id": "f0e1d2c3-b4a5-4697-8877-665544332211",
+                        "motion": {"motion": true, "motion_valid": true},
+                        "type": "motion"
+                    },
+                    {
+                        "id": "7d5545be-626a-4d63-a2f4-4347e43b50f6",
+                        "status": "connected",
+                        "type": "zigbee_connectivity"
+                    }
+                ]
+            }
+        ])
+        .to_string();
+
+        let events = parse_event_message(&data).unwrap();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].event_type, HueEventType::Update);
+        let resources = &events[0].data;
+        assert_eq!(
+            resources[0].change,
+            ResourceChange::Light(LightChange {
+                on: Some(OnChange { on: true }),
+                dimming: Some(DimmingChange { brightness: 42.5 }),
+                color: None,
+                color_temperature: None,
+            })
+        );
+        assert_eq!(resources[0].change.to_string(), "on=true brightness=42.5");
+        assert_eq!(resources[1].change.to_string(), "motion=true");
+        assert_eq!(resources[2].rtype, ResourceType::ZigbeeConnectivity);
+        assert_eq!(resources[2].change.to_string(), r#"{"status":"connected"}"#);
+    }
+}
@@ -0,0 +1,206 @@
+//! Rooms and zones, which group lights, and the `grouped_light` service that controls them.
+//!
+//! A room holds devices and a zone holds lights. The bridge gives each a
+//! `grouped_light` service, which takes the same updates as a single light and
+//! applies them to all the lights of the group in one request. The lights of a
+//! group can differ in the colors and color temperatures they show, so the
+//! bridge checks those values and each light shows the closest one it can.
+//! See documentation at <https://developers.meethue.com/develop/hue-api-v2/api-reference/#resource_room_get>
+
+use crate::events::ResourceRef;
+use crate::light::LightUpdate;
+use crate::resource::{GroupedLightId, ResourceType};
+use crate::HueError;
+use log::{debug, info};
+use serde::{Deserialize, Serialize};
+use std::fmt::{Display, Formatter};
+
+/// The kind of a group of lights.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GroupType {
+    Room,
+    Zone,
+}
+
+impl GroupType {
+    /// The name of the type in the API, `room` or `zone`.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            GroupType::Room => "room",
+            GroupType::Zone => "zone",
+        }
+    }
+
+    /// The resource type of the groups.
+    pub fn resource_type(&self) -> ResourceType {
+        match self {
+            GroupType::Room => ResourceType::Room,
+            GroupType::Zone => ResourceType::Zone,
+        }
+    }
+}
+
+impl Display for GroupType {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// A room or zone with its members.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Group {
+    pub id: String,
+    /// `room` or `zone`.
+    pub rtype: ResourceType,
+    pub name: String,
+    /// The archetype chosen by the user, e.g. `kitchen` or `living_room`.
+    pub archetype: String,
+    /// The devices in a room, or the lights in a zone.
+    pub children: Vec<ResourceRef>,
+    /// The service that controls all the lights of the group, or `None` if it has no lights.
+    pub grouped_light_id: Option<GroupedLightId>,
+}
+
+/// Hue API representation of a room or zone
+#[derive(Deserialize, Debug)]
+pub(crate) struct HueApiGroupData {
+    id: String,
+    #[serde(rename = "type")]
+    rtype: ResourceType,
+    metadata: HueApiGroupMetadata,
+    children: Vec<ResourceRef>,
+    services: Vec<ResourceRef>,
+}
+
+#[derive(Deserialize, Debug)]
+struct HueApiGroupMetadata {
+    name: String,
+    archetype: String,
+}
+
+impl From<HueApiGroupData> for Group {
+    fn from(g: HueApiGroupData) -> Self {
+        let grouped_light_id = g
+            .services
+            .iter()
+            .find(|s| s.rtype == ResourceType::GroupedLight)
+            .map(|s| GroupedLightId(s.rid.clone()));
+        Group {
+            id: g.id,
+            rtype: g.rtype,
+            name: g.metadata.name,
+            archetype: g.metadata.archetype,
+            children: g.children,
+            grouped_light_id,
+        }
+    }
+}
+
+/// Find the `grouped_light` of the group with the given ID, `grouped_light` ID,
+/// or a part of its name (case-insensitive), like a light is found by its name.
+pub(crate) fn select_group(
+    groups: Vec<Group>,
+    group_type: GroupType,
+    id_or_name: &str,
+) -> Result<GroupedLightId, HueError> {
+    let name_query = id_or_name.to_lowercase();
+    let mut matches: Vec<Group> = match groups.iter().find(|group| {
+        group.id == id_or_name
+            || group
+                .grouped_light_id
+                .as_ref()
+                .is_some_and(|id| id.0 == id_or_name)
+    }) {
+        Some(group) => vec![group.clone()],
+        None => groups
+            .into_iter()
+            .filter(|group| group.name.to_lowercase().contains(&name_query))
+            .inspect(|group| {
+                debug!(
+                    "Found matching {}: {} ({})",
+                    group_type, group.name, group.id
+                )
+            })
+            .collect(),
+    };
+
+    match matches.len() {
+        0 => Err(HueError::ResourceNotFound(format!(
+            "No {} found with ID or name matching '{}'",
+            group_type, id_or_name
+        ))),
+        1 => {
+            let group = matches.remove(0);
+            info!("Using {}: {} ({})", group_type, group.name, group.id);
+            group.grouped_light_id.ok_or_else(|| {
+                HueError::InvalidParameter(format!(
+                    "The {} {} has no lights to control",
+                    group_type, group.name
+                ))
+            })
+        }
+        _ => {
+            let match_info: Vec<String> = matches
+                .iter()
+                .map(|group| format!("{} ({})", group.name, group.id))
+                .collect();
+            Err(HueError::Usage(format!(
+                "Multiple {}s found matching '{}'. Please be more specific or use the {} ID directly: {}",
+                group_type,
+                id_or_name,
+                group_type,
+                match_info.join(", ")
+            )))
+        }
+    }
+}
+
+/// Check that a grouped light can apply the update. Effects differ between the
+/// lights of a group, so they are only played on single lights.
+pub(crate) fn check_grouped_update(update: &LightUpdate) -> Result<(), HueError> {
+    update.check()?;
+    match update.effects.is_none() && update.timed_effects.is_none() {
+        true => Ok(()),
+        false => Err(HueError::InvalidParameter(String::from(
+            "Rooms and zones do not support effects. Play them on each light instead.",
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn group(id: &str, name: &str, grouped_light: Option<&str>) -> Group {
+        Group {
+            id: id.to_string(),
+            rtype: ResourceType::Room,
+            name: name.to_string(),
+            archetype: String::from("kitchen"),
+            children: Vec::new(),
+            grouped_light_id: grouped_light.map(|id| GroupedLightId(id.to_string())),
+        }
+    }
+
+    #[test]
+    fn select_group_matches_ids_before_names() {
+        let groups = vec![
+            group("room-1", "Kitchen", Some("grouped-1")),
+            group("room-2", "Kitchen garden", Some("grouped-2")),
+            group("room-3", "Attic", None),
+        ];
+        let select = |id_or_name| select_group(groups.clone(), GroupType::Room, id_or_name);
+        assert_eq!(select("room-2").unwrap().0, "grouped-2");
+        assert_eq!(select("grouped-1").unwrap().0, "grouped-1");
+        assert_eq!(select("GARDEN").unwrap().0, "grouped-2");
+        assert!(matches!(select("kitchen"), Err(HueError::Usage(_))));
+        assert!(matches!(
+            select("cellar"),
+            Err(HueError::ResourceNotFound(_))
+        ));
+        assert!(matches!(
+            select("attic"),
+            Err(HueError::InvalidParameter(_))
+        ));
+    }
+}
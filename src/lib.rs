@@ -0,0 +1,1584 @@
+//! Client library for the Philips Hue Bridge.
+//!
+//! The [`HueClient`] holds the bridge address, the application key and a
+//! shared HTTP client, and exposes typed methods for the supported operations.
+//! The bridge certificate is verified against the Hue root CA, see [`tls`].
+//!
+//! ```no_run
+//! use philips_hue_lab::{AppKey, BridgeIp, HueClient};
+//!
+//! let client = HueClient::new(BridgeIp(String::from("192.168.1.2")), AppKey(String::from("my-key")))?;
+//! for device in client.list_devices()? {
+//!     println!("{:?}", device);
+//! }
+//! # Ok::<(), philips_hue_lab::HueError>(())
+//! ```
+//!
+//! With the `async` feature, `AsyncHueClient` offers the same operations for
+//! async code running on tokio.
+
+#[cfg(feature = "async")]
+mod async_client;
+pub mod color;
+pub mod config;
+pub mod discovery;
+pub mod effect;
+mod error;
+pub mod events;
+pub mod group;
+pub mod light;
+mod redact;
+pub mod resource;
+pub mod tls;
+pub mod transition;
+
+#[cfg(feature = "async")]
+pub use async_client::AsyncHueClient;
+pub use error::HueError;
+pub use resource::LightId;
+
+use color::{CieXy, Color, ColorTemperature, Gamut, GamutType, MirekSchema};
+use effect::{check_supported, AlertAction, Effect, Signal, TimedEffect};
+use error::{HueApiClipError, HueApiErrorMessage};
+use events::{EventStream, HueEvent, ResourceRef};
+use group::{check_grouped_update, select_group, Group, GroupType, HueApiGroupData};
+use light::{HueApiLightData, LightState, LightUpdate};
+use log::{debug, info, warn};
+use reqwest::blocking;
+use resource::{GroupedLightId, ResourceId, ResourceType};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::ops::ControlFlow;
+use std::time::{Duration, Instant};
+use tls::{BridgeCertificateVerifier, CertificateFingerprint, SharedBridgeId};
+use transition::{fade_steps, FADE_STEP, RATE_LIMIT_BACKOFF, RATE_LIMIT_RETRIES};
+
+const HUE_API_APP_NAME: &str = "philips_hue_lab";
+const HUE_API_USER_NAME: &str = "hue_lab_user";
+
+/// How often to retry creating a key while waiting for the link button.
+const LINK_BUTTON_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Default timeout for a single request to the bridge.
+/// The event stream is long-lived and is not subject to this timeout.
+pub const DEFAULT_REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// The largest relative change of the brightness, in percent.
+pub const MAX_BRIGHTNESS_DELTA: u8 = 100;
+
+/// The largest relative change of the color temperature, in mirek.
+pub const MAX_MIREK_DELTA: u16 = 347;
+
+/// Default timeout for connecting to the bridge.
+pub const DEFAULT_CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How long an idle connection is kept open for the next request.
+const POOL_IDLE_TIMEOUT: Duration = Duration::from_secs(50);
+
+/// Interval of TCP keep-alive probes, so a lost connection is noticed.
+const TCP_KEEPALIVE: Duration = Duration::from_secs(30);
+
+/// IP Address of the Hue Bridge
+#[derive(Debug, Clone, PartialEq)]
+pub struct BridgeIp(pub String);
+
+/// The keys generated by the bridge for this application.
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct BridgeKey {
+    /// The application key, sent in the `hue-application-key` header.
+    #[serde(rename = "username")]
+    pub user_name: String,
+    /// The client key for the entertainment API (hex-encoded PSK).
+    /// Older bridge firmware does not generate one.
+    #[serde(rename = "clientkey")]
+    pub client_key: Option<String>,
+}
+impl BridgeKey {
+    pub fn app_key(&self) -> AppKey {
+        AppKey(self.user_name.clone())
+    }
+}
+
+/// App key for the Hue API
+#[derive(Debug, Clone, PartialEq)]
+pub struct AppKey(pub String);
+impl From<&AppKey> for String {
+    fn from(key: &AppKey) -> Self {
+        key.0.clone()
+    }
+}
+
+/// A client for a single Hue Bridge.
+///
+/// The underlying HTTP client is created once and shared by all requests, so
+/// consecutive requests reuse the same keep-alive connection instead of
+/// connecting and negotiating TLS again. Create one client per bridge and
+/// reuse it.
+pub struct HueClient {
+    bridge_ip: BridgeIp,
+    app_key: Option<AppKey>,
+    http: blocking::Client,
+    request_timeout: Duration,
+}
+
+/// Builds a [`HueClient`], choosing how the bridge certificate is verified.
+///
+/// By default the certificate must be signed by the Hue root CA and its common
+/// name must be the bridge ID. If the bridge ID is not given, it is read from
+/// the bridge configuration when the client is built.
+pub struct HueClientBuilder {
+    bridge_ip: BridgeIp,
+    app_key: Option<AppKey>,
+    bridge_id: Option<String>,
+    pinned_certificate: Option<CertificateFingerprint>,
+    root_certificates: Vec<Vec<u8>>,
+    connect_timeout: Duration,
+    request_timeout: Duration,
+}
+
+impl HueClientBuilder {
+    pub fn app_key(mut self, app_key: AppKey) -> Self {
+        self.app_key = Some(app_key);
+        self
+    }
+
+    /// The bridge ID that the certificate common name must match, e.g. `001788fffe4f8a2b`.
+    pub fn bridge_id(mut self, bridge_id: impl Into<String>) -> Self {
+        self.bridge_id = Some(bridge_id.into());
+        self
+    }
+
+    /// Only accept the bridge certificate with this fingerprint, instead of
+    /// verifying it against the root CAs. Use this for older bridges with a
+    /// self-signed certificate, see [`tls::fetch_certificate_fingerprint`].
+    pub fn pinned_certificate(mut self, fingerprint: CertificateFingerprint) -> Self {
+        self.pinned_certificate = Some(fingerprint);
+        self
+    }
+
+    /// Also trust the root CA certificates in this PEM data.
+    pub fn root_certificate_pem(mut self, pem: &[u8]) -> Self {
+        self.root_certificates.push(pem.to_vec());
+        self
+    }
+
+    /// How long to wait for a connection to the bridge. Defaults to [`DEFAULT_CONNECT_TIMEOUT`].
+    pub fn connect_timeout(mut self, timeout: Duration) -> Self {
+        self.connect_timeout = timeout;
+        self
+    }
+
+    /// How long to wait for the response to a request, including connecting.
+    /// Defaults to [`DEFAULT_REQUEST_TIMEOUT`]. The event stream is not subject to it.
+    pub fn request_timeout(mut self, timeout: Duration) -> Self {
+        self.request_timeout = timeout;
+        self
+    }
+
+    pub fn build(self) -> Result<HueClient, HueError> {
+        let (verifier, common_name) = self.verifier()?;
+        let client = HueClient {
+            bridge_ip: self.bridge_ip,
+            app_key: self.app_key,
+            http: create_reqwest_client(verifier, self.connect_timeout)?,
+            request_timeout: self.request_timeout,
+        };
+        if let (Some(common_name), None) = (common_name, self.bridge_id) {
+            check_bridge_id(&common_name, &client.bridge_config()?.bridge_id)?;
+        }
+        Ok(client)
+    }
+
+    /// The certificate verifier, and for root CA verification the bridge ID it expects.
+    fn verifier(&self) -> Result<(BridgeCertificateVerifier, Option<SharedBridgeId>), HueError> {
+        match &self.pinned_certificate {
+            Some(fingerprint) => Ok((BridgeCertificateVerifier::pinned(fingerprint.clone()), None)),
+            None => {
+                let mut extra_roots = Vec::new();
+                for pem in &self.root_certificates {
+                    extra_roots.extend(tls::parse_pem_certificates(pem)?);
+                }
+                let (verifier, bridge_id) = BridgeCertificateVerifier::root_ca(&extra_roots)?;
+                *bridge_id.lock().unwrap() = self.bridge_id.clone();
+                Ok((verifier, Some(bridge_id)))
+            }
+        }
+    }
+}
+
+/// Check the certificate common name recorded on the first connection against
+/// the bridge ID from the bridge configuration.
+fn check_bridge_id(common_name: &SharedBridgeId, bridge_id: &str) -> Result<(), HueError> {
+    let common_name = common_name.lock().unwrap().clone().unwrap_or_default();
+    match common_name.eq_ignore_ascii_case(bridge_id) {
+        true => Ok(()),
+        false => Err(HueError::Tls(Box::from(format!(
+            "The certificate common name '{}' does not match the bridge ID {}",
+            common_name, bridge_id
+        )))),
+    }
+}
+
+impl HueClient {
+    /// Start building a client for the bridge.
+    pub fn builder(bridge_ip: BridgeIp) -> HueClientBuilder {
+        HueClientBuilder {
+            bridge_ip,
+            app_key: None,
+            bridge_id: None,
+            pinned_certificate: None,
+            root_certificates: Vec::new(),
+            connect_timeout: DEFAULT_CONNECT_TIMEOUT,
+            request_timeout: DEFAULT_REQUEST_TIMEOUT,
+        }
+    }
+
+    /// Create a client for the bridge, authenticating with the given application key.
+    /// The bridge ID for verifying the certificate is read from the bridge.
+    pub fn new(bridge_ip: BridgeIp, app_key: AppKey) -> Result<Self, HueError> {
+        HueClient::builder(bridge_ip).app_key(app_key).build()
+    }
+
+    /// Create a client without an application key.
+    /// Only operations that do not require authentication, such as [`HueClient::create_key`], can be used.
+    pub fn unauthenticated(bridge_ip: BridgeIp) -> Result<Self, HueError> {
+        HueClient::builder(bridge_ip).build()
+    }
+
+    pub fn bridge_ip(&self) -> &BridgeIp {
+        &self.bridge_ip
+    }
+
+    fn app_key(&self) -> Result<&AppKey, HueError> {
+        required_app_key(self.app_key.as_ref())
+    }
+
+    /// Ask the bridge to generate an application key and an entertainment client key.
+    /// The Link button on the bridge must be pressed shortly before calling this.
+    pub fn create_key(&self) -> Result<BridgeKey, HueError> {
+        let body = CreateUserRequestBody::from(HUE_API_APP_NAME, HUE_API_USER_NAME);
+        let response = self.post_request("/api", &body)?;
+        let parsed = parse_create_key_response(&response)?;
+        Ok(BridgeKey {
+            user_name: parsed.user_name,
+            client_key: parsed.client_key,
+        })
+    }
+
+    /// Like [`HueClient::create_key`], but while the link button has not been pressed,
+    /// keep retrying for up to `wait`, so the user can press it after starting.
+    pub fn create_key_waiting(&self, wait: Duration) -> Result<BridgeKey, HueError> {
+        let deadline = Instant::now() + wait;
+        loop {
+            match self.create_key() {
+                Err(HueError::LinkButtonNotPressed) if Instant::now() < deadline => {
+                    std::thread::sleep(LINK_BUTTON_POLL_INTERVAL);
+                }
+                result => return result,
+            }
+        }
+    }
+
+    /// Get the public bridge configuration.
+    /// This does not require an application key.
+    pub fn bridge_config(&self) -> Result<BridgeConfig, HueError> {
+        let url = format!("https://{}/api/0/config", self.bridge_ip.0);
+        let request = self
+            .http
+            .get(&url)
+            .timeout(self.request_timeout)
+            .header("Accept", "application/json");
+        let response = self.send(request)?;
+        serde_json::from_value::<BridgeConfig>(response)
+            .map_err(|e| HueError::parse("Invalid bridge configuration", e))
+    }
+
+    /// List all devices on the bridge.
+    pub fn list_devices(&self) -> Result<Vec<HueDevice>, HueError> {
+        let response = self.get_request("/clip/v2/resource/device")?;
+        let parsed = parse_list_devices_response(&response)?;
+        Ok(parsed)
+    }
+
+    /// Get a single resource, deserialized as `T`.
+    /// Use [`serde_json::Value`] for `T` to get the raw JSON of the resource.
+    pub fn get_resource<T>(&self, rtype: &ResourceType, id: &str) -> Result<T, HueError>
+    where
+        T: DeserializeOwned,
+    {
+        let response = self.get_request(&resource_path(rtype, Some(id)))?;
+        single_resource(parse_resources_response(&response)?, rtype, id)
+    }
+
+    /// Get a single resource by its typed ID, e.g. a [`LightId`].
+    pub fn get_resource_by_id<T, I>(&self, id: &I) -> Result<T, HueError>
+    where
+        T: DeserializeOwned,
+        I: ResourceId,
+    {
+        self.get_resource(&I::RESOURCE_TYPE, id.as_str())
+    }
+
+    /// List all resources of a type, deserialized as `T`.
+    pub fn list_resources<T>(&self, rtype: &ResourceType) -> Result<Vec<T>, HueError>
+    where
+        T: DeserializeOwned,
+    {
+        let response = self.get_request(&resource_path(rtype, None))?;
+        parse_resources_response(&response)
+    }
+
+    /// Read the state of a light.
+    pub fn light_state(&self, light_id: &LightId) -> Result<LightState, HueError> {
+        Ok(LightState::from(
+            self.get_resource_by_id::<HueApiLightData, _>(light_id)?,
+        ))
+    }
+
+    /// Read the state of all the lights.
+    pub fn light_states(&self) -> Result<Vec<LightState>, HueError> {
+        let lights = self.list_resources::<HueApiLightData>(&ResourceType::Light)?;
+        Ok(lights.into_iter().map(LightState::from).collect())
+    }
+
+    /// Update a light with only the properties set in the update, e.g. the
+    /// brightness without turning the light on.
+    /// Colors are clamped to the gamut of the light, and color temperatures,
+    /// effects, alerts and signals are checked against what the light supports
+    /// before the update is sent, failing with [`HueError::InvalidParameter`].
+    /// Returns the resources updated by the bridge.
+    pub fn update_light(
+        &self,
+        light_id: &LightId,
+        update: &LightUpdate,
+    ) -> Result<Vec<ResourceRef>, HueError> {
+        update.check()?;
+        match update.needs_capabilities() {
+            true => {
+                let light = self.get_resource_by_id(light_id)?;
+                self.put_light_update(light_id, &checked_light_update(light_id, light, update)?)
+            }
+            false => self.put_light_update(light_id, update),
+        }
+    }
+
+    /// Turn a light on or off, optionally setting the brightness (0-100).
+    /// With a `transition`, the bridge fades the light to the new state over that time,
+    /// up to [`transition::MAX_TRANSITION`].
+    /// Returns the resources updated by the bridge.
+    pub fn control_light(
+        &self,
+        light_id: &LightId,
+        on: bool,
+        dimming_level: Option<u8>,
+        transition: Option<Duration>,
+    ) -> Result<Vec<ResourceRef>, HueError> {
+        self.update_light(light_id, &light_update(Some(on), dimming_level, transition))
+    }
+
+    /// The gamut of a color light, or `None` if the bridge does not know it.
+    /// Fails with [`HueError::InvalidParameter`] if the light does not support color.
+    pub fn light_gamut(&self, light_id: &LightId) -> Result<Option<Gamut>, HueError> {
+        light_gamut(light_id, &self.get_resource_by_id(light_id)?)
+    }
+
+    /// Turn a light on with the given color, optionally setting the brightness (0-100).
+    /// The color is clamped to the closest color in the gamut of the light.
+    /// Returns the resources updated by the bridge.
+    pub fn set_light_color(
+        &self,
+        light_id: &LightId,
+        color: &Color,
+        dimming_level: Option<u8>,
+        transition: Option<Duration>,
+    ) -> Result<Vec<ResourceRef>, HueError> {
+        let update = light_update(Some(true), dimming_level, transition).color(color.to_xy());
+        self.update_light(light_id, &update)
+    }
+
+    /// The range of color temperatures of a white light.
+    /// Fails with [`HueError::InvalidParameter`] if the light does not support color temperature.
+    pub fn light_mirek_schema(&self, light_id: &LightId) -> Result<MirekSchema, HueError> {
+        light_mirek_schema(light_id, &self.get_resource_by_id(light_id)?)
+    }
+
+    /// Turn a light on with the given color temperature, optionally setting the brightness (0-100).
+    /// If the light cannot show the temperature, fail, or when `clamp` is set use the closest one.
+    /// Returns the resources updated by the bridge.
+    pub fn set_light_color_temperature(
+        &self,
+        light_id: &LightId,
+        temperature: ColorTemperature,
+        dimming_level: Option<u8>,
+        clamp: bool,
+        transition: Option<Duration>,
+    ) -> Result<Vec<ResourceRef>, HueError> {
+        let update = light_update(Some(true), dimming_level, transition)
+            .color_temperature(temperature, clamp);
+        self.update_light(light_id, &update)
+    }
+
+    /// Play an effect on a light, e.g. candle, until it is stopped with [`Effect::NoEffect`].
+    /// Fails with [`HueError::InvalidParameter`] if the light does not support the effect.
+    pub fn set_light_effect(
+        &self,
+        light_id: &LightId,
+        effect: Effect,
+    ) -> Result<Vec<ResourceRef>, HueError> {
+        self.update_light(light_id, &LightUpdate::new().effect(effect))
+    }
+
+    /// Play a timed effect on a light, e.g. a sunrise over the given duration.
+    /// Fails with [`HueError::InvalidParameter`] if the light does not support the effect.
+    pub fn set_light_timed_effect(
+        &self,
+        light_id: &LightId,
+        effect: TimedEffect,
+        duration: Duration,
+    ) -> Result<Vec<ResourceRef>, HueError> {
+        self.update_light(light_id, &LightUpdate::new().timed_effect(effect, duration))
+    }
+
+    /// Play an alert on a light once, e.g. to breathe so it can be identified.
+    /// Fails with [`HueError::InvalidParameter`] if the light does not support the alert.
+    pub fn alert_light(
+        &self,
+        light_id: &LightId,
+        action: AlertAction,
+    ) -> Result<Vec<ResourceRef>, HueError> {
+        self.update_light(light_id, &LightUpdate::new().alert(action))
+    }
+
+    /// Show a signal on a light for the given duration, with the colors the signal takes,
+    /// see [`Signal::color_count`]. The colors are clamped to the gamut of the light.
+    /// Fails with [`HueError::InvalidParameter`] if the light does not support the signal.
+    pub fn signal_light(
+        &self,
+        light_id: &LightId,
+        signal: Signal,
+        duration: Duration,
+        colors: &[Color],
+    ) -> Result<Vec<ResourceRef>, HueError> {
+        let colors: Vec<CieXy> = colors.iter().map(Color::to_xy).collect();
+        self.update_light(
+            light_id,
+            &LightUpdate::new().signal(signal, duration, &colors),
+        )
+    }
+
+    /// Turn a light off if it is on, or on if it is off.
+    /// Returns the resources updated by the bridge.
+    pub fn toggle_light(
+        &self,
+        light_id: &LightId,
+        transition: Option<Duration>,
+    ) -> Result<Vec<ResourceRef>, HueError> {
+        let on = !self.light_state(light_id)?.on;
+        info!(
+            "Turning light {} {}",
+            light_id,
+            if on { "on" } else { "off" }
+        );
+        self.control_light(light_id, on, None, transition)
+    }
+
+    /// Change the brightness of a light by `brightness_delta` percent and its color
+    /// temperature by `mirek_delta` mirek, relative to the current values.
+    /// Positive values make the light brighter and warmer, negative ones dimmer
+    /// and cooler, and zero stops an ongoing change.
+    /// Returns the resources updated by the bridge.
+    pub fn adjust_light(
+        &self,
+        light_id: &LightId,
+        brightness_delta: Option<i8>,
+        mirek_delta: Option<i16>,
+        transition: Option<Duration>,
+    ) -> Result<Vec<ResourceRef>, HueError> {
+        let update = delta_update(brightness_delta, mirek_delta, transition);
+        self.update_light(light_id, &update)
+    }
+
+    /// Fade the brightness of a light to `dimming_level` (0-100), or to off when `on` is false,
+    /// over a duration longer than the bridge allows in a single transition.
+    /// The fade is sent as a request per step, each with its own transition,
+    /// retrying steps the bridge rejects because of rate limiting.
+    /// Blocks until the last step has been sent and returns the resources it updated.
+    pub fn fade_light(
+        &self,
+        light_id: &LightId,
+        on: bool,
+        dimming_level: Option<u8>,
+        duration: Duration,
+    ) -> Result<Vec<ResourceRef>, HueError> {
+        let steps = fade_plan(
+            light_id,
+            self.get_resource_by_id(light_id)?,
+            on,
+            dimming_level,
+            duration,
+        )?;
+        let path = resource_path(&ResourceType::Light, Some(light_id.as_str()));
+        let start = Instant::now();
+        let mut updated = Vec::new();
+        for (body, at) in steps {
+            if let Some(wait) = (start + at).checked_duration_since(Instant::now()) {
+                std::thread::sleep(wait);
+            }
+            updated = self.put_resource_with_retries(&path, &body)?;
+        }
+        Ok(updated)
+    }
+
+    /// Find a light by ID or name.
+    /// First tries to match the input as a light ID.
+    /// If no match is found, queries the bridge for all devices and searches for a name match.
+    /// Returns the light ID if a single match is found.
+    pub fn find_light_by_id_or_name(&self, id_or_name: &str) -> Result<LightId, HueError> {
+        select_light(self.list_devices()?, id_or_name)
+    }
+
+    /// List the rooms or the zones, with their members and `grouped_light` service.
+    pub fn list_groups(&self, group_type: GroupType) -> Result<Vec<Group>, HueError> {
+        let groups = self.list_resources::<HueApiGroupData>(&group_type.resource_type())?;
+        Ok(groups.into_iter().map(Group::from).collect())
+    }
+
+    /// Find the `grouped_light` of a room or zone by the ID of the group or of its
+    /// `grouped_light`, or by a part of its name (case-insensitive substring search).
+    pub fn find_group_by_id_or_name(
+        &self,
+        group_type: GroupType,
+        id_or_name: &str,
+    ) -> Result<GroupedLightId, HueError> {
+        select_group(self.list_groups(group_type)?, group_type, id_or_name)
+    }
+
+    /// Update all the lights of a room or zone in one request, e.g. turn them on
+    /// with a color temperature. The bridge adapts colors and color temperatures
+    /// to what each light can show. Effects are refused with [`HueError::InvalidParameter`].
+    /// Returns the resources updated by the bridge.
+    pub fn update_grouped_light(
+        &self,
+        grouped_light_id: &GroupedLightId,
+        update: &LightUpdate,
+    ) -> Result<Vec<ResourceRef>, HueError> {
+        check_grouped_update(update)?;
+        debug!("Updating grouped light {}: {}", grouped_light_id, update);
+        let path = resource_path(&ResourceType::GroupedLight, Some(grouped_light_id.as_str()));
+        self.put_resource(&path, update)
+    }
+
+    /// Open the CLIP v2 event stream.
+    /// The returned iterator blocks until the bridge sends the next event.
+    pub fn event_stream(&self) -> Result<EventStream, HueError> {
+        let url = format!("https://{}/eventstream/clip/v2", self.bridge_ip.0);
+        let request = self
+            .http
+            .get(&url)
+            .header("Accept", "text/event-stream")
+            .header("hue-application-key", String::from(self.app_key()?));
+        let response = self.execute(request)?;
+        if !response.status().is_success() {
+            let status = response.status().as_u16();
+            return Err(HueError::from_status(
+                status,
+                &response.text().unwrap_or_default(),
+            ));
+        }
+        Ok(EventStream::new(response))
+    }
+
+    /// Call `callback` for every event from the bridge until it returns
+    /// [`ControlFlow::Break`] or the bridge closes the event stream.
+    pub fn watch_events<F>(&self, mut callback: F) -> Result<(), HueError>
+    where
+        F: FnMut(HueEvent) -> ControlFlow<()>,
+    {
+        for event in self.event_stream()? {
+            match event {
+                Ok(event) => {
+                    if callback(event).is_break() {
+                        break;
+                    }
+                }
+                // A message this library cannot decode does not end the stream.
+                Err(e @ HueError::Parse { .. }) => warn!("Skipping event stream message: {}", e),
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(())
+    }
+
+    /// Send a GET request to the Hue Bridge.
+    pub fn get_request(&self, path: &str) -> Result<serde_json::Value, HueError> {
+        let url = format!("https://{}{}", self.bridge_ip.0, path);
+        let request = self
+            .http
+            .get(&url)
+            .timeout(self.request_timeout)
+            .header("Accept", "application/json")
+            .header("hue-application-key", String::from(self.app_key()?));
+        self.send(request)
+    }
+
+    /// Send a POST request to the Hue Bridge.
+    pub fn post_request<T>(&self, path: &str, body: &T) -> Result<serde_json::Value, HueError>
+    where
+        T: ?Sized + Serialize,
+    {
+        let url = format!("https://{}{}", self.bridge_ip.0, path);
+        let body_str = serde_json::to_string(body)
+            .map_err(|e| HueError::parse("Could not serialize the request body", e))?;
+        let mut request = self
+            .http
+            .post(&url)
+            .timeout(self.request_timeout)
+            .header("Accept", "application/json")
+            .body(body_str);
+        // Creating a key does not need one, but creating CLIP resources does.
+        if let Some(app_key) = &self.app_key {
+            request = request.header("hue-application-key", String::from(app_key));
+        }
+        self.send(request)
+    }
+
+    /// Send a PUT request to the Hue Bridge.
+    pub fn put_request<T>(&self, path: &str, body: &T) -> Result<serde_json::Value, HueError>
+    where
+        T: ?Sized + Serialize,
+    {
+        let url = format!("https://{}{}", self.bridge_ip.0, path);
+        let body_str = serde_json::to_string(body)
+            .map_err(|e| HueError::parse("Could not serialize the request body", e))?;
+        let request = self
+            .http
+            .put(&url)
+            .timeout(self.request_timeout)
+            .header("Accept", "application/json")
+            .header("hue-application-key", String::from(self.app_key()?))
+            .body(body_str);
+        self.send(request)
+    }
+
+    /// Send a DELETE request to the Hue Bridge.
+    pub fn delete_request(&self, path: &str) -> Result<serde_json::Value, HueError> {
+        let url = format!("https://{}{}", self.bridge_ip.0, path);
+        let request = self
+            .http
+            .delete(&url)
+            .timeout(self.request_timeout)
+            .header("Accept", "application/json")
+            .header("hue-application-key", String::from(self.app_key()?));
+        self.send(request)
+    }
+
+    /// Update a CLIP v2 resource and return the affected resources.
+    /// Errors reported in the response envelope are returned as [`HueError::Clip`].
+    fn put_resource<T>(&self, path: &str, body: &T) -> Result<Vec<ResourceRef>, HueError>
+    where
+        T: ?Sized + Serialize,
+    {
+        let response = self.put_request(path, body)?;
+        parse_write_response(&response)
+    }
+
+    /// Send a light update as it is.
+    fn put_light_update(
+        &self,
+        light_id: &LightId,
+        update: &LightUpdate,
+    ) -> Result<Vec<ResourceRef>, HueError> {
+        debug!("Updating light {}: {}", light_id, update);
+        let path = resource_path(&ResourceType::Light, Some(light_id.as_str()));
+        self.put_resource(&path, update)
+    }
+
+    /// Update a resource, retrying after a pause when the bridge is rate limiting requests.
+    fn put_resource_with_retries<T>(
+        &self,
+        path: &str,
+        body: &T,
+    ) -> Result<Vec<ResourceRef>, HueError>
+    where
+        T: ?Sized + Serialize,
+    {
+        let mut retries = 0;
+        loop {
+            match self.put_resource(path, body) {
+                Err(HueError::RateLimited) if retries < RATE_LIMIT_RETRIES => {
+                    retries += 1;
+                    info!(
+                        "The bridge is rate limiting requests, retrying in {:?}",
+                        RATE_LIMIT_BACKOFF
+                    );
+                    std::thread::sleep(RATE_LIMIT_BACKOFF);
+                }
+                result => return result,
+            }
+        }
+    }
+
+    /// Send a request and parse the JSON response, mapping unsuccessful HTTP statuses to errors.
+    fn send(&self, request: blocking::RequestBuilder) -> Result<serde_json::Value, HueError> {
+        let response = self.execute(request)?;
+        let status = response.status();
+        let body = response.text()?;
+        parse_response(status, &body, &self.secrets())
+    }
+
+    /// Send a request, logging it at debug level with the keys redacted.
+    fn execute(&self, request: blocking::RequestBuilder) -> Result<blocking::Response, HueError> {
+        let request = request.build()?;
+        log_request(
+            request.method(),
+            request.url(),
+            request.headers(),
+            request.body().and_then(|body| body.as_bytes()),
+            &self.secrets(),
+        );
+        Ok(self.http.execute(request)?)
+    }
+
+    fn secrets(&self) -> Vec<&str> {
+        secrets(self.app_key.as_ref())
+    }
+}
+
+fn required_app_key(app_key: Option<&AppKey>) -> Result<&AppKey, HueError> {
+    app_key.ok_or_else(|| {
+        HueError::Unauthorized(String::from(
+            "An application key is required for this operation.",
+        ))
+    })
+}
+
+/// The keys to redact from logged output.
+fn secrets(app_key: Option<&AppKey>) -> Vec<&str> {
+    app_key.iter().map(|key| key.0.as_str()).collect()
+}
+
+/// Log a request at debug level with the keys redacted.
+fn log_request(
+    method: &reqwest::Method,
+    url: &reqwest::Url,
+    headers: &reqwest::header::HeaderMap,
+    body: Option<&[u8]>,
+    secrets: &[&str],
+) {
+    if !log::log_enabled!(log::Level::Debug) {
+        return;
+    }
+    debug!(
+        "Request: {} {}",
+        method,
+        redact::text(url.as_str(), secrets)
+    );
+    for (name, value) in headers {
+        debug!(
+            "Request header: {}: {}",
+            name,
+            redact::header(name.as_str(), value.as_bytes())
+        );
+    }
+    if let Some(body) = body {
+        debug!(
+            "Request body: {}",
+            redact::body(&String::from_utf8_lossy(body), secrets)
+        );
+    }
+}
+
+/// Parse the JSON body of a response, mapping unsuccessful HTTP statuses to errors.
+fn parse_response(
+    status: reqwest::StatusCode,
+    body: &str,
+    secrets: &[&str],
+) -> Result<serde_json::Value, HueError> {
+    debug!("Response: {} {}", status, redact::body(body, secrets));
+    if !status.is_success() {
+        return Err(HueError::from_status(status.as_u16(), body));
+    }
+    serde_json::from_str(body)
+        .map_err(|e| HueError::parse("Invalid JSON response from the Hue Bridge", e))
+}
+
+/// The public bridge configuration from the GET /api/0/config endpoint.
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct BridgeConfig {
+    pub name: String,
+    /// The bridge ID, e.g. `001788FFFE4F8A2B`.
+    #[serde(rename = "bridgeid")]
+    pub bridge_id: String,
+    #[serde(rename = "modelid")]
+    pub model_id: String,
+    #[serde(rename = "swversion")]
+    pub software_version: String,
+    #[serde(rename = "apiversion")]
+    pub api_version: String,
+}
+
+/// The body for the POST /api endpoint (create a user)
+#[derive(Serialize, Debug)]
+struct CreateUserRequestBody {
+    #[serde(rename = "devicetype")]
+    device_type: String,
+    /// Also generate a client key for the entertainment API.
+    #[serde(rename = "generateclientkey")]
+    generate_client_key: bool,
+}
+impl CreateUserRequestBody {
+    fn from(app_name: &str, user_name: &str) -> Self {
+        CreateUserRequestBody {
+            device_type: format!("{}#{}", app_name, user_name),
+            generate_client_key: true,
+        }
+    }
+}
+
+fn parse_create_key_response(
+    response: &serde_json::Value,
+) -> Result<HueApiCreateKeySuccessDetails, HueError> {
+    if let Some(error) = parse_api_response_errors(response).into_iter().next() {
+        return Err(HueError::from(error));
+    }
+    let success_details = response
+        .as_array()
+        .and_then(|elements| elements.first())
+        .and_then(|element| element.get("success"))
+        .ok_or_else(|| HueError::Parse {
+            message: format!(
+                "Could not create key. success element not found in response: {}",
+                response
+            ),
+            source: None,
+        })?;
+    serde_json::from_value::<HueApiCreateKeySuccessDetails>(success_details.clone())
+        .map_err(|e| HueError::parse("Could not create key", e))
+}
+
+/// This is the API wire format of the Hue response for a successful create-key operation.
+#[derive(Deserialize, Debug, PartialEq)]
+struct HueApiCreateKeySuccessDetails {
+    #[serde(rename = "username")]
+    user_name: String,
+    #[serde(rename = "clientkey")]
+    client_key: Option<String>,
+}
+
+/// Parse and extract all API response errors.
+/// Returns an empty vec if there are no errors in the response.
+/// Error objects that do not have the expected format are kept with type 0
+/// and the raw JSON as the description.
+fn parse_api_response_errors(response: &serde_json::Value) -> Vec<HueApiErrorMessage> {
+    match response.as_array() {
+        Some(elements) => elements
+            .iter()
+            .filter_map(|element| element.get("error"))
+            .map(|details| {
+                serde_json::from_value::<HueApiErrorMessage>(details.clone()).unwrap_or_else(|_| {
+                    HueApiErrorMessage {
+                        type_value: 0,
+                        address: String::new(),
+                        description: details.to_string(),
+                    }
+                })
+            })
+            .collect(),
+        None => vec![],
+    }
+}
+
+fn create_reqwest_client(
+    verifier: BridgeCertificateVerifier,
+    connect_timeout: Duration,
+) -> Result<blocking::Client, HueError> {
+    let client = blocking::ClientBuilder::new()
+        .use_preconfigured_tls(verifier.into_client_config())
+        .connect_timeout(connect_timeout)
+        // Timeouts are set per request, so the event stream can stay open.
+        .timeout(None)
+        .pool_idle_timeout(POOL_IDLE_TIMEOUT)
+        .tcp_keepalive(TCP_KEEPALIVE)
+        .build()?;
+    Ok(client)
+}
+
+/// This is the API wire format of the CLIP v2 response to a write (PUT, POST or DELETE).
+#[derive(Deserialize, Debug)]
+struct HueApiWriteResponse {
+    #[serde(default)]
+    errors: Vec<HueApiClipError>,
+    #[serde(default)]
+    data: Vec<ResourceRef>,
+}
+
+/// Parse the `{errors, data}` envelope of a write response.
+/// The bridge can report errors with a successful HTTP status (e.g. 207 Multi-Status),
+/// so any error fails the write.
+fn parse_write_response(response: &serde_json::Value) -> Result<Vec<ResourceRef>, HueError> {
+    let parsed = serde_json::from_value::<HueApiWriteResponse>(response.clone())
+        .map_err(|e| HueError::parse("Invalid response to update", e))?;
+    match parsed.errors.is_empty() {
+        true => Ok(parsed.data),
+        false => Err(HueError::Clip(
+            parsed.errors.into_iter().map(|e| e.description).collect(),
+        )),
+    }
+}
+
+/// The CLIP v2 path of a resource, or of all resources of the type.
+fn resource_path(rtype: &ResourceType, id: Option<&str>) -> String {
+    match id {
+        Some(id) => format!("/clip/v2/resource/{}/{}", rtype, id),
+        None => format!("/clip/v2/resource/{}", rtype),
+    }
+}
+
+/// This is the API wire format of the CLIP v2 response to a read.
+#[derive(Deserialize, Debug)]
+struct HueApiResourceResponse<T> {
+    #[serde(default)]
+    errors: Vec<HueApiClipError>,
+    #[serde(default = "Vec::new")]
+    data: Vec<T>,
+}
+
+/// Parse the `{errors, data}` envelope of a read response.
+fn parse_resources_response<T>(response: &Value) -> Result<Vec<T>, HueError>
+where
+    T: DeserializeOwned,
+{
+    let parsed = serde_json::from_value::<HueApiResourceResponse<T>>(response.clone())
+        .map_err(|e| HueError::parse("Invalid resource response", e))?;
+    match parsed.errors.is_empty() {
+        true => Ok(parsed.data),
+        false => Err(HueError::Clip(
+            parsed.errors.into_iter().map(|e| e.description).collect(),
+        )),
+    }
+}
+
+/// The resource from a response for a single resource.
+fn single_resource<T>(resources: Vec<T>, rtype: &ResourceType, id: &str) -> Result<T, HueError> {
+    resources
+        .into_iter()
+        .next()
+        .ok_or_else(|| HueError::ResourceNotFound(format!("No {} found with ID '{}'", rtype, id)))
+}
+
+/// Standard HUE device information.
+/// See documentation at <https://developers.meethue.com/develop/hue-api-v2/api-reference/#resource_device>
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct DeviceInfo {
+    pub id: String,
+    /// The ID in the v1 API, e.g. `/lights/2`, if the device is available there.
+    pub id_v1: Option<String>,
+    pub name: String,
+    /// The archetype chosen by the user, e.g. `sultan_bulb` or `ceiling_round`.
+    pub archetype: String,
+    #[serde(flatten)]
+    pub product_data: ProductData,
+    /// True if the device can be asked to identify itself, e.g. by blinking.
+    pub identify: bool,
+    /// The services of the device, e.g. its `light`, `motion` or `device_power` service.
+    pub services: Vec<ResourceRef>,
+    /// The service ID for a light device (for light devices only)
+    pub light_id: Option<LightId>,
+}
+
+impl DeviceInfo {
+    /// The services of the given type, e.g. `motion`.
+    pub fn services_of_type<'a>(
+        &'a self,
+        rtype: &'a ResourceType,
+    ) -> impl Iterator<Item = &'a ResourceRef> {
+        self.services.iter().filter(move |s| s.rtype == *rtype)
+    }
+
+    pub fn has_service(&self, rtype: &ResourceType) -> bool {
+        self.services_of_type(rtype).next().is_some()
+    }
+}
+
+/// The product information of a device.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ProductData {
+    pub model_id: String,
+    pub manufacturer_name: String,
+    pub product_name: String,
+    /// The archetype of the product, e.g. `sultan_bulb`.
+    pub product_archetype: String,
+    /// True if the product is certified by Philips Hue (Friends of Hue).
+    pub certified: bool,
+    pub software_version: String,
+    pub hardware_platform_type: Option<String>,
+}
+
+/// A Hue device on the bridge
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct HueDevice(pub DeviceInfo);
+
+/// Hue API representation of a device
+#[derive(Deserialize, Debug)]
+struct HueApiDeviceData {
+    id: String,
+    id_v1: Option<String>,
+    product_data: ProductData,
+    metadata: HueApiDeviceMetadata,
+    /// Present, as an empty object, if the device supports identify.
+    identify: Option<Value>,
+    services: Vec<ResourceRef>,
+}
+
+/// Hue API representation of device metadata
+#[derive(Deserialize, Debug)]
+struct HueApiDeviceMetadata {
+    name: String,
+    archetype: String,
+}
+
+impl From<HueApiDeviceData> for DeviceInfo {
+    fn from(d: HueApiDeviceData) -> Self {
+        let light_id = d
+            .services
+            .iter()
+            .find(|s| s.rtype == ResourceType::Light)
+            .map(|s| LightId(s.rid.clone()));
+        DeviceInfo {
+            id: d.id,
+            id_v1: d.id_v1,
+            name: d.metadata.name,
+            archetype: d.metadata.archetype,
+            product_data: d.product_data,
+            identify: d.identify.is_some(),
+            services: d.services,
+            light_id,
+        }
+    }
+}
+
+/// Hue API representation of the color and effect capabilities of a light
+#[derive(Deserialize, Debug)]
+struct HueApiLightCapabilities {
+    color: Option<HueApiLightColor>,
+    color_temperature: Option<HueApiLightColorTemperature>,
+    effects: Option<HueApiLightEffects>,
+    timed_effects: Option<HueApiLightEffects>,
+    alert: Option<HueApiLightAlert>,
+    signaling: Option<HueApiLightSignaling>,
+}
+
+#[derive(Deserialize, Debug)]
+struct HueApiLightColor {
+    gamut: Option<Gamut>,
+    gamut_type: Option<GamutType>,
+}
+
+impl HueApiLightColor {
+    /// The gamut the light reports, or else the gamut of its gamut type.
+    fn gamut(&self) -> Option<Gamut> {
+        self.gamut
+            .or_else(|| self.gamut_type.and_then(|gamut_type| gamut_type.gamut()))
+    }
+}
+
+#[derive(Deserialize, Debug)]
+struct HueApiLightEffects {
+    #[serde(default)]
+    effect_values: Vec<String>,
+}
+
+#[derive(Deserialize, Debug)]
+struct HueApiLightAlert {
+    #[serde(default)]
+    action_values: Vec<String>,
+}
+
+#[derive(Deserialize, Debug)]
+struct HueApiLightSignaling {
+    #[serde(default)]
+    signal_values: Vec<String>,
+}
+
+#[derive(Deserialize, Debug)]
+struct HueApiLightColorTemperature {
+    mirek_schema: MirekSchema,
+}
+
+/// The gamut of a light, from the gamut the light reports or else its gamut type.
+fn light_gamut(
+    light_id: &LightId,
+    light: &HueApiLightCapabilities,
+) -> Result<Option<Gamut>, HueError> {
+    let color = light.color.as_ref().ok_or_else(|| {
+        HueError::InvalidParameter(format!("Light {} does not support color", light_id))
+    })?;
+    Ok(color.gamut())
+}
+
+/// A light update from the common arguments of the light methods.
+fn light_update(
+    on: Option<bool>,
+    dimming_level: Option<u8>,
+    transition: Option<Duration>,
+) -> LightUpdate {
+    let mut update = LightUpdate::new();
+    if let Some(on) = on {
+        update = update.on(on);
+    }
+    if let Some(level) = dimming_level {
+        update = update.brightness(f32::from(level.clamp(0, 100)));
+    }
+    if let Some(transition) = transition {
+        update = update.transition(transition);
+    }
+    update
+}
+
+/// A light update with relative changes of the brightness and color temperature.
+fn delta_update(
+    brightness_delta: Option<i8>,
+    mirek_delta: Option<i16>,
+    transition: Option<Duration>,
+) -> LightUpdate {
+    let mut update = light_update(None, None, transition);
+    if let Some(delta) = brightness_delta {
+        update = update.brightness_delta(delta);
+    }
+    if let Some(delta) = mirek_delta {
+        update = update.mirek_delta(delta);
+    }
+    update
+}
+
+/// Check a light update against what the light supports, and clamp its colors to the gamut.
+fn checked_light_update(
+    light_id: &LightId,
+    light: HueApiLightCapabilities,
+    update: &LightUpdate,
+) -> Result<LightUpdate, HueError> {
+    let mut update = update.clone();
+    if let Some(color) = &mut update.color {
+        color.xy = xy_in_gamut(color.xy, light_gamut(light_id, &light)?);
+    }
+    if let Some(color_temperature) = &mut update.color_temperature {
+        let mirek = light_mirek_schema(light_id, &light)?
+            .check(color_temperature.temperature, color_temperature.clamp)?;
+        color_temperature.mirek = mirek;
+        color_temperature.temperature = ColorTemperature::Mirek(mirek);
+    }
+    if let Some(effects) = &update.effects {
+        let supported = light.effects.as_ref().map(|e| e.effect_values.as_slice());
+        check_supported(light_id.as_str(), "effect", &effects.effect, supported)?;
+    }
+    if let Some(timed_effects) = &update.timed_effects {
+        let supported = light
+            .timed_effects
+            .as_ref()
+            .map(|e| e.effect_values.as_slice());
+        check_supported(
+            light_id.as_str(),
+            "timed effect",
+            &timed_effects.effect,
+            supported,
+        )?;
+    }
+    if let Some(alert) = &update.alert {
+        let supported = light.alert.as_ref().map(|a| a.action_values.as_slice());
+        check_supported(light_id.as_str(), "alert", &alert.action, supported)?;
+    }
+    if let Some(signaling) = &mut update.signaling {
+        let supported = light.signaling.as_ref().map(|s| s.signal_values.as_slice());
+        check_supported(light_id.as_str(), "signal", &signaling.signal, supported)?;
+        let gamut = light.color.as_ref().and_then(HueApiLightColor::gamut);
+        for color in &mut signaling.colors {
+            color.xy = xy_in_gamut(color.xy, gamut);
+        }
+    }
+    Ok(update)
+}
+
+fn light_mirek_schema(
+    light_id: &LightId,
+    light: &HueApiLightCapabilities,
+) -> Result<MirekSchema, HueError> {
+    light
+        .color_temperature
+        .as_ref()
+        .map(|color_temperature| color_temperature.mirek_schema)
+        .ok_or_else(|| {
+            HueError::InvalidParameter(format!(
+                "Light {} does not support color temperature",
+                light_id
+            ))
+        })
+}
+
+/// Hue API representation of the on and dimming state of a light
+#[derive(Deserialize, Debug)]
+struct HueApiLightBrightness {
+    on: HueApiLightOn,
+    dimming: Option<HueApiLightDimming>,
+}
+
+#[derive(Deserialize, Debug)]
+struct HueApiLightOn {
+    on: bool,
+}
+
+#[derive(Deserialize, Debug)]
+struct HueApiLightDimming {
+    brightness: f32,
+}
+
+/// The requests for a fade from the current brightness of the light, each with
+/// the time since the start of the fade to send it at.
+fn fade_plan(
+    light_id: &LightId,
+    light: HueApiLightBrightness,
+    on: bool,
+    dimming_level: Option<u8>,
+    duration: Duration,
+) -> Result<Vec<(LightUpdate, Duration)>, HueError> {
+    let current = light.dimming.ok_or_else(|| {
+        HueError::InvalidParameter(format!(
+            "Light {} does not support dimming, so it cannot fade",
+            light_id
+        ))
+    })?;
+    let from = match light.on.on {
+        true => current.brightness,
+        false => 0.0,
+    };
+    let to = match (on, dimming_level) {
+        (false, _) => 0.0,
+        (true, Some(level)) => f32::from(level.clamp(0, 100)),
+        (true, None) if light.on.on => from,
+        (true, None) => 100.0,
+    };
+    let steps = fade_steps(duration, FADE_STEP)?;
+    let mut plan = Vec::with_capacity(steps.len());
+    let mut at = Duration::ZERO;
+    for (index, step) in steps.iter().enumerate() {
+        let last = index + 1 == steps.len();
+        // The last step reaches the target exactly, also for a fade of no duration.
+        let done = match last {
+            true => 1.0,
+            false => (at + *step).as_secs_f32() / duration.as_secs_f32(),
+        };
+        let update = match (on, last) {
+            // The last step of a fade to off turns the light off as it reaches the lowest brightness.
+            (false, true) => LightUpdate::new().on(false),
+            _ => LightUpdate::new()
+                .on(true)
+                .brightness(from + (to - from) * done.min(1.0)),
+        };
+        plan.push((update.transition(*step), at));
+        at += *step;
+    }
+    Ok(plan)
+}
+
+/// The color, clamped to the gamut if it is known.
+fn xy_in_gamut(requested: CieXy, gamut: Option<Gamut>) -> CieXy {
+    let Some(gamut) = gamut else {
+        return requested;
+    };
+    let xy = gamut.clamp(requested);
+    if xy != requested {
+        info!(
+            "The light cannot show {}, using the closest color {} ({})",
+            requested,
+            xy,
+            xy.to_hex()
+        );
+    }
+    xy
+}
+
+/// Select the light with the given ID, or else the single light with a name containing the query.
+fn select_light(devices: Vec<HueDevice>, id_or_name: &str) -> Result<LightId, HueError> {
+    // Check if the input matches a light ID directly
+    for HueDevice(device_info) in &devices {
+        if let Some(light_id) = &device_info.light_id {
+            if light_id.0 == id_or_name {
+                return Ok(light_id.clone());
+            }
+        }
+    }
+
+    // If no direct ID match, search for name matches (case-insensitive substring)
+    let name_query = id_or_name.to_lowercase();
+    let mut matches = Vec::new();
+
+    // Collect devices with matching names
+    for HueDevice(device_info) in devices {
+        if let Some(light_id) = device_info.light_id.clone() {
+            if device_info.name.to_lowercase().contains(&name_query) {
+                debug!(
+                    "Found matching light: {} ({})",
+                    device_info.name, light_id.0
+                );
+                matches.push((device_info, light_id));
+            }
+        }
+    }
+
+    match matches.len() {
+        0 => Err(HueError::ResourceNotFound(format!(
+            "No light found with ID or name matching '{}'",
+            id_or_name
+        ))),
+        1 => {
+            let (device_info, light_id) = matches.remove(0);
+            info!("Using light: {} ({})", device_info.name, light_id.0);
+            Ok(light_id)
+        }
+        _ => {
+            let match_info: Vec<String> = matches
+                .iter()
+                .map(|(info, _)| format!("{} ({})", info.name, info.id))
+                .collect();
+            Err(HueError::Usage(format!(
+                    "Multiple lights found matching '{}'. Please be more specific or use the light ID directly: {}",
+                    id_or_name,
+                    match_info.join(", ")
+                )))
+        }
+    }
+}
+
+fn parse_list_devices_response(json_response: &Value) -> Result<Vec<HueDevice>, HueError> {
+    let devices = parse_resources_response::<HueApiDeviceData>(json_response)?;
+    Ok(devices
+        .into_iter()
+        .map(|d| HueDevice(DeviceInfo::from(d)))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_api_response_errors_when_error_is_present() {
+        let response_body = serde_json::json!(
+        [
+            {
+                "error": {
+                    "type": 101,
+                    "address": "/",
+                    "description": "link button not pressed"
+                }
+            }
+        ]);
+        let errors = parse_api_response_errors(&response_body);
+
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].type_value, 101);
+        assert_eq!(errors[0].address, "/");
+        assert_eq!(errors[0].description, "link button not pressed");
+        assert_eq!(
+            errors[0],
+            HueApiErrorMessage {
+                type_value: 101,
+                address: "/".to_string(),
+                description: "link button not pressed".to_string(),
+            }
+        );
+    }
+
+    #[test]
+    fn parse_api_response_errors_when_no_error_is_present() {
+        let response_body = serde_json::json!(
+        [
+            {
+                "success": {
+                    "username": "1234567890"
+                }
+            }
+        ]);
+        let errors = parse_api_response_errors(&response_body);
+        assert_eq!(errors.len(), 0);
+    }
+
+    #[test]
+    fn parse_create_key_response_with_successful_operation() {
+        let response_body = serde_json::json!(
+        [
+            {
+                "success": {
+                    "username": "1234567890",
+                    "clientkey": "0123456789ABCDEF0123456789ABCDEF"
+                }
+            }
+        ]);
+        let actual = parse_create_key_response(&response_body);
+        assert!(actual.is_ok());
+        assert_eq!(
+            HueApiCreateKeySuccessDetails {
+                user_name: "1234567890".to_string(),
+                client_key: Some("0123456789ABCDEF0123456789ABCDEF".to_string()),
+            },
+            actual.unwrap()
+        );
+    }
+
+    #[test]
+    fn parse_write_response_returns_errors_or_resources() {
+        let success = serde_json::json!(
+            {"errors": [], "data": [{"rid": "e9e1e6b0-5d3b-4a0e-9f0e-3b1e2c1d0a01", "rtype": "light"}]}
+        );
+        assert_eq!(
+            parse_write_response(&success).unwrap(),
+            vec![ResourceRef {
+                rid: String::from("e9e1e6b0-5d3b-4a0e-9f0e-3b1e2c1d0a01"),
+                rtype: ResourceType::Light,
+            }]
+        );
+
+        let failure = serde_json::json!(
+            {"errors": [{"description": "device (light) does not support dimming"}], "data": []}
+        );
+        assert!(matches!(
+            parse_write_response(&failure),
+            Err(HueError::Clip(descriptions)) if descriptions == vec!["device (light) does not support dimming"]
+        ));
+    }
+
+    #[test]
+    fn parse_list_devices_response_with_successful_operation_light_device() {
+        let response_body = serde_json::json!(
+            {"errors": [],
+             "data": [
+                {
+                  "id": "94860050-1d86-4b79-8583-1be7dce05197",
+                  "id_v1": "/lights/2",
+                  "product_data": {
+                    "model_id": "123455987123",
+                    "manufacturer_name": "Signify Netherlands B.V.",
+                    "product_name": "Space Light",
+                    "product_archetype": "foo_bar",
+                    "certified": true,
+                    "software_version": "1.1.2",
+                    "hardware_platform_type": "100b-118"
+                  },
+                  "metadata": {
+                    "name": "Space light 1",
+                    "archetype": "foo_bar"
+                  },
+                  "identify": {},
+                  "services": [
+                    {
+                      "rid": "7d5545be-626a-4d63-a2f4-4347e43b50f6",
+                      "rtype": "zigbee_connectivity"
+                    },
+                    {
+                      "rid": "53ca6e61-5e40-4760-9e2e-6d2f48594901",
+                      "rtype": "light"
+                    },
+                    {
+                      "rid": "5dbe9888-a0b7-42d4-b002-9f15cd77e419",
+                      "rtype": "entertainment"
+                    },
+                    {
+                      "rid": "7c12995f-03bc-4b31-bb55-9da9e075dc0f",
+                      "rtype": "taurus_7455"
+                    },
+                    {
+                      "rid": "5b275c9c-dd12-45a8-9d36-716c43c1d3ed",
+                      "rtype": "device_software_update"
+                    }
+                  ],
+                  "type": "device"
+                }
+                ]
+        }
+        );
+
+        let actual = parse_list_devices_response(&response_body);
+        assert!(actual.is_ok());
+        let ds = actual.unwrap();
+        assert_eq!(ds.len(), 1);
+        assert_eq!(
+            ds[0],
+            HueDevice(DeviceInfo {
+                id: "94860050-1d86-4b79-8583-1be7dce05197".to_string(),
+                id_v1: Some("/lights/2".to_string()),
+                name: "Space light 1".to_string(),
+                archetype: "foo_bar".to_string(),
+                product_data: ProductData {
+                    model_id: "123455987123".to_string(),
+                    manufacturer_name: "Signify Netherlands B.V.".to_string(),
+                    product_name: "Space Light".to_string(),
+                    product_archetype: "foo_bar".to_string(),
+                    certified: true,
+                    software_version: "1.1.2".to_string(),
+                    hardware_platform_type: Some("100b-118".to_string()),
+                },
+                identify: true,
+                services: ds[0].0.services.clone(),
+                light_id: Some(LightId("53ca6e61-5e40-4760-9e2e-6d2f48594901".to_string())),
+            })
+        );
+        assert_eq!(ds[0].0.services.len(), 5);
+        assert!(ds[0].0.has_service(&ResourceType::Entertainment));
+        assert!(!ds[0].0.has_service(&ResourceType::Motion));
+        assert_eq!(
+            ds[0].0.services[3].rtype,
+            ResourceType::Other(String::from("taurus_7455"))
+        );
+    }
+
+    #[test]
+    fn fade_plan_steps_brightness_down_and_turns_the_light_off() {
+        let light: HueApiLightBrightness = serde_json::from_value(serde_json::json!({
+            "on": {"on": true},
+            "dimming": {"brightness": 90.0}
+        }))
+        .unwrap();
+        let light_id = LightId(String::from("light"));
+        let plan = fade_plan(&light_id, light, false, None, Duration::from_secs(180)).unwrap();
+
+        let bodies: Vec<Value> = plan
+            .iter()
+            .map(|(body, _)| serde_json::to_value(body).unwrap())
+            .collect();
+        assert_eq!(
+            bodies,
+            vec![
+                serde_json::json!({"on": {"on": true}, "dimming": {"brightness": 60.0}, "dynamics": {"duration": 60000}}),
+                serde_json::json!({"on": {"on": true}, "dimming": {"brightness": 30.0}, "dynamics": {"duration": 60000}}),
+                serde_json::json!({"on": {"on": false}, "dynamics": {"duration": 60000}}),
+            ]
+        );
+        let times: Vec<Duration> = plan.iter().map(|(_, at)| *at).collect();
+        assert_eq!(
+            times,
+            vec![
+                Duration::ZERO,
+                Duration::from_secs(60),
+                Duration::from_secs(120)
+            ]
+        );
+    }
+
+    #[test]
+    fn fade_plan_of_no_duration_sets_the_target_at_once() {
+        let light: HueApiLightBrightness = serde_json::from_value(serde_json::json!({
+            "on": {"on": true},
+            "dimming": {"brightness": 90.0}
+        }))
+        .unwrap();
+        let light_id = LightId(String::from("light"));
+        let plan = fade_plan(&light_id, light, true, Some(50), Duration::ZERO).unwrap();
+
+        assert_eq!(plan.len(), 1);
+        assert_eq!(
+            serde_json::to_value(&plan[0].0).unwrap(),
+            serde_json::json!({"on": {"on": true}, "dimming": {"brightness": 50.0}, "dynamics": {"duration": 0}})
+        );
+        assert_eq!(plan[0].1, Duration::ZERO);
+    }
+}
@@ -0,0 +1,575 @@
+//! The state of a light, as read back from the bridge, and updates to it.
+//!
+//! See documentation at <https://developers.meethue.com/develop/hue-api-v2/api-reference/#resource_light_get>
+
+use crate::color::{CieXy, ColorTemperature, Gamut, GamutType, MirekSchema};
+use crate::effect::{signal_millis, AlertAction, Effect, Signal, TimedEffect};
+use crate::transition::transition_millis;
+use crate::{HueError, LightId, MAX_BRIGHTNESS_DELTA, MAX_MIREK_DELTA};
+use serde::{Deserialize, Serialize};
+use std::fmt::{Display, Formatter};
+use std::time::Duration;
+
+/// The full state of a light: whether it is on, its brightness, color and effect,
+/// together with the ranges it supports.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct LightState {
+    pub id: LightId,
+    pub name: String,
+    pub on: bool,
+    /// The brightness in percent, for lights that can be dimmed.
+    pub brightness: Option<f32>,
+    /// The lowest brightness the light can show, in percent.
+    pub min_dim_level: Option<f32>,
+    /// The color, for color lights.
+    pub color: Option<LightColor>,
+    /// The color temperature, for white lights.
+    pub color_temperature: Option<LightColorTemperature>,
+    /// The effect playing on the light, e.g. `candle` or `no_effect`.
+    pub effect: Option<String>,
+    /// `normal`, or `streaming` while an entertainment area controls the light.
+    pub mode: String,
+    pub dynamics: Option<LightDynamics>,
+    pub powerup: Option<LightPowerup>,
+}
+
+impl LightState {
+    /// The current color of the light, from its color temperature when it shows
+    /// a white and else from its color, or `None` for lights without either.
+    pub fn current_xy(&self) -> Option<CieXy> {
+        let mirek = self
+            .color_temperature
+            .as_ref()
+            .and_then(|color_temperature| color_temperature.mirek);
+        match (mirek, &self.color) {
+            (Some(mirek), _) => Some(ColorTemperature::Mirek(mirek).to_xy()),
+            (None, Some(color)) => Some(color.xy),
+            (None, None) => None,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct LightColor {
+    pub xy: CieXy,
+    pub gamut: Option<Gamut>,
+    pub gamut_type: Option<GamutType>,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct LightColorTemperature {
+    /// The color temperature, or `None` when the light shows a color instead.
+    pub mirek: Option<u16>,
+    pub mirek_schema: MirekSchema,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct LightDynamics {
+    /// `dynamic_palette` while the light changes through a palette, else `none`.
+    pub status: String,
+    /// The speed of the dynamic palette or effect (0-1).
+    pub speed: f32,
+}
+
+/// What the light does when it gets power.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct LightPowerup {
+    /// `safety`, `powerfail`, `last_on_state` or `custom`.
+    pub preset: String,
+    pub configured: bool,
+}
+
+/// Hue API representation of a light
+#[derive(Deserialize, Debug)]
+pub(crate) struct HueApiLightData {
+    id: LightId,
+    metadata: HueApiLightMetadata,
+    on: HueApiOn,
+    dimming: Option<HueApiDimming>,
+    color: Option<LightColor>,
+    color_temperature: Option<HueApiColorTemperature>,
+    effects: Option<HueApiEffects>,
+    mode: String,
+    dynamics: Option<LightDynamics>,
+    powerup: Option<LightPowerup>,
+}
+
+#[derive(Deserialize, Debug)]
+struct HueApiLightMetadata {
+    name: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct HueApiOn {
+    on: bool,
+}
+
+#[derive(Deserialize, Debug)]
+struct HueApiDimming {
+    brightness: f32,
+    min_dim_level: Option<f32>,
+}
+
+#[derive(Deserialize, Debug)]
+struct HueApiColorTemperature {
+    mirek: Option<u16>,
+    mirek_valid: bool,
+    mirek_schema: MirekSchema,
+}
+
+#[derive(Deserialize, Debug)]
+struct HueApiEffects {
+    status: String,
+}
+
+impl From<HueApiLightData> for LightState {
+    fn from(l: HueApiLightData) -> Self {
+        LightState {
+            id: l.id,
+            name: l.metadata.name,
+            on: l.on.on,
+            brightness: l.dimming.as_ref().map(|dimming| dimming.brightness),
+            min_dim_level: l.dimming.and_then(|dimming| dimming.min_dim_level),
+            color: l.color,
+            color_temperature: l.color_temperature.map(|ct| LightColorTemperature {
+                mirek: ct.mirek.filter(|_| ct.mirek_valid),
+                mirek_schema: ct.mirek_schema,
+            }),
+            effect: l.effects.map(|effects| effects.status),
+            mode: l.mode,
+            dynamics: l.dynamics,
+            powerup: l.powerup,
+        }
+    }
+}
+
+/// A change to the state of a light, for [`HueClient::update_light`](crate::HueClient::update_light).
+///
+/// Only the properties that are set are sent, so e.g. the brightness can be
+/// changed without turning the light on.
+///
+/// ```
+/// use philips_hue_lab::light::LightUpdate;
+/// use std::time::Duration;
+///
+/// let update = LightUpdate::new()
+///     .brightness(40.0)
+///     .transition(Duration::from_secs(2));
+/// assert_eq!(update.to_string(), "brightness 40%, over 2s");
+/// ```
+#[derive(Serialize, Debug, Clone, Default, PartialEq)]
+pub struct LightUpdate {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) on: Option<OnUpdate>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) dimming: Option<DimmingUpdate>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) dimming_delta: Option<DimmingDeltaUpdate>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) color: Option<ColorUpdate>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) color_temperature: Option<ColorTemperatureUpdate>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) color_temperature_delta: Option<ColorTemperatureDeltaUpdate>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) dynamics: Option<DynamicsUpdate>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) effects: Option<EffectUpdate>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) timed_effects: Option<TimedEffectUpdate>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) alert: Option<AlertUpdate>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) signaling: Option<SignalUpdate>,
+}
+
+impl LightUpdate {
+    pub fn new() -> Self {
+        LightUpdate::default()
+    }
+
+    /// Turn the light on or off.
+    pub fn on(self, on: bool) -> Self {
+        LightUpdate {
+            on: Some(OnUpdate { on }),
+            ..self
+        }
+    }
+
+    /// Set the brightness, in percent (0-100).
+    pub fn brightness(self, brightness: f32) -> Self {
+        LightUpdate {
+            dimming: Some(DimmingUpdate {
+                brightness: brightness.clamp(0.0, 100.0),
+            }),
+            ..self
+        }
+    }
+
+    /// Change the brightness by this many percent of the full brightness,
+    /// brighter when positive and dimmer when negative. Zero stops an ongoing change.
+    pub fn brightness_delta(self, delta: i8) -> Self {
+        LightUpdate {
+            dimming_delta: Some(DimmingDeltaUpdate {
+                action: DeltaAction::of(i32::from(delta)),
+                brightness_delta: f32::from(delta.unsigned_abs()),
+            }),
+            ..self
+        }
+    }
+
+    /// Set the color. It is clamped to the gamut of the light when sent.
+    pub fn color(self, xy: CieXy) -> Self {
+        LightUpdate {
+            color: Some(ColorUpdate { xy }),
+            ..self
+        }
+    }
+
+    /// Set the white color temperature, in mirek.
+    pub fn mirek(self, mirek: u16) -> Self {
+        self.color_temperature(ColorTemperature::Mirek(mirek), false)
+    }
+
+    /// Set the white color temperature. It is checked against the range of the
+    /// light when sent, and fails outside it unless `clamp` is set, in which case
+    /// the closest temperature the light can show is used.
+    pub fn color_temperature(self, temperature: ColorTemperature, clamp: bool) -> Self {
+        LightUpdate {
+            color_temperature: Some(ColorTemperatureUpdate {
+                mirek: temperature.mirek(),
+                temperature,
+                clamp,
+            }),
+            ..self
+        }
+    }
+
+    /// Change the color temperature by this many mirek,
+    /// warmer when positive and cooler when negative. Zero stops an ongoing change.
+    pub fn mirek_delta(self, delta: i16) -> Self {
+        LightUpdate {
+            color_temperature_delta: Some(ColorTemperatureDeltaUpdate {
+                action: DeltaAction::of(i32::from(delta)),
+                mirek_delta: delta.unsigned_abs(),
+            }),
+            ..self
+        }
+    }
+
+    /// Change to the new state gradually over this time,
+    /// up to [`MAX_TRANSITION`](crate::transition::MAX_TRANSITION).
+    pub fn transition(self, transition: Duration) -> Self {
+        LightUpdate {
+            dynamics: Some(DynamicsUpdate {
+                duration: transition,
+            }),
+            ..self
+        }
+    }
+
+    /// Play an effect until it is stopped with [`Effect::NoEffect`].
+    pub fn effect(self, effect: Effect) -> Self {
+        LightUpdate {
+            effects: Some(EffectUpdate { effect }),
+            ..self
+        }
+    }
+
+    /// Play a timed effect over the given duration.
+    pub fn timed_effect(self, effect: TimedEffect, duration: Duration) -> Self {
+        LightUpdate {
+            timed_effects: Some(TimedEffectUpdate { effect, duration }),
+            ..self
+        }
+    }
+
+    /// Play an alert once.
+    pub fn alert(self, action: AlertAction) -> Self {
+        LightUpdate {
+            alert: Some(AlertUpdate { action }),
+            ..self
+        }
+    }
+
+    /// Show a signal for the given duration, with the colors it takes,
+    /// see [`Signal::color_count`].
+    pub fn signal(self, signal: Signal, duration: Duration, colors: &[CieXy]) -> Self {
+        LightUpdate {
+            signaling: Some(SignalUpdate {
+                signal,
+                duration,
+                colors: colors.iter().map(|xy| ColorUpdate { xy: *xy }).collect(),
+            }),
+            ..self
+        }
+    }
+
+    /// True if nothing is set.
+    pub fn is_empty(&self) -> bool {
+        *self == LightUpdate::default()
+    }
+
+    /// True if the update has to be checked against what the light supports.
+    pub(crate) fn needs_capabilities(&self) -> bool {
+        self.color.is_some()
+            || self.color_temperature.is_some()
+            || self.effects.is_some()
+            || self.timed_effects.is_some()
+            || self.alert.is_some()
+            || self.signaling.is_some()
+    }
+
+    /// Check the values the bridge would reject, whatever the light.
+    pub(crate) fn check(&self) -> Result<(), HueError> {
+        if self.is_empty() {
+            return Err(HueError::Usage(String::from(
+                "Nothing to change in the light update",
+            )));
+        }
+        if let Some(dynamics) = &self.dynamics {
+            transition_millis(dynamics.duration)?;
+        }
+        if let Some(delta) = &self.dimming_delta {
+            if delta.brightness_delta > f32::from(MAX_BRIGHTNESS_DELTA) {
+                return Err(HueError::InvalidParameter(format!(
+                    "The brightness can change by at most {} percent, not {}",
+                    MAX_BRIGHTNESS_DELTA, delta.brightness_delta
+                )));
+            }
+        }
+        if let Some(delta) = &self.color_temperature_delta {
+            if delta.mirek_delta > MAX_MIREK_DELTA {
+                return Err(HueError::InvalidParameter(format!(
+                    "The color temperature can change by at most {} mirek, not {}",
+                    MAX_MIREK_DELTA, delta.mirek_delta
+                )));
+            }
+        }
+        if let Some(signaling) = &self.signaling {
+            signaling.signal.check_colors(&signaling.colors)?;
+            signal_millis(signaling.duration)?;
+        }
+        Ok(())
+    }
+}
+
+impl Display for LightUpdate {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let mut parts = Vec::new();
+        if let Some(on) = &self.on {
+            parts.push(String::from(if on.on { "on" } else { "off" }));
+        }
+        if let Some(dimming) = &self.dimming {
+            parts.push(format!("brightness {}%", dimming.brightness));
+        }
+        if let Some(delta) = &self.dimming_delta {
+            parts.push(format!(
+                "brightness {} {}%",
+                delta.action, delta.brightness_delta
+            ));
+        }
+        if let Some(color) = &self.color {
+            parts.push(format!("color {}", color.xy));
+        }
+        if let Some(color_temperature) = &self.color_temperature {
+            parts.push(format!(
+                "color temperature {}",
+                color_temperature.temperature
+            ));
+        }
+        if let Some(delta) = &self.color_temperature_delta {
+            parts.push(format!(
+                "color temperature {} {} mirek",
+                delta.action, delta.mirek_delta
+            ));
+        }
+        if let Some(effects) = &self.effects {
+            parts.push(format!("effect {}", effects.effect));
+        }
+        if let Some(timed_effects) = &self.timed_effects {
+            parts.push(format!(
+                "timed effect {} for {:?}",
+                timed_effects.effect, timed_effects.duration
+            ));
+        }
+        if let Some(alert) = &self.alert {
+            parts.push(format!("alert {}", alert.action));
+        }
+        if let Some(signaling) = &self.signaling {
+            parts.push(format!(
+                "signal {} for {:?}",
+                signaling.signal, signaling.duration
+            ));
+        }
+        if let Some(dynamics) = &self.dynamics {
+            parts.push(format!("over {:?}", dynamics.duration));
+        }
+        f.write_str(&parts.join(", "))
+    }
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub(crate) struct OnUpdate {
+    pub(crate) on: bool,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub(crate) struct DimmingUpdate {
+    pub(crate) brightness: f32,
+}
+
+/// The direction of a relative change.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum DeltaAction {
+    Up,
+    Down,
+    Stop,
+}
+
+impl DeltaAction {
+    fn of(delta: i32) -> Self {
+        match delta {
+            1.. => DeltaAction::Up,
+            0 => DeltaAction::Stop,
+            _ => DeltaAction::Down,
+        }
+    }
+}
+
+impl Display for DeltaAction {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            DeltaAction::Up => "up",
+            DeltaAction::Down => "down",
+            DeltaAction::Stop => "stop",
+        })
+    }
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub(crate) struct DimmingDeltaUpdate {
+    pub(crate) action: DeltaAction,
+    pub(crate) brightness_delta: f32,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub(crate) struct ColorUpdate {
+    pub(crate) xy: CieXy,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub(crate) struct ColorTemperatureUpdate {
+    pub(crate) mirek: u16,
+    /// The temperature as requested, for messages.
+    #[serde(skip)]
+    pub(crate) temperature: ColorTemperature,
+    /// Use the closest temperature the light can show when it is out of range.
+    #[serde(skip)]
+    pub(crate) clamp: bool,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub(crate) struct ColorTemperatureDeltaUpdate {
+    pub(crate) action: DeltaAction,
+    pub(crate) mirek_delta: u16,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub(crate) struct DynamicsUpdate {
+    /// The transition time, sent in milliseconds
+    #[serde(serialize_with = "serialize_millis")]
+    pub(crate) duration: Duration,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub(crate) struct EffectUpdate {
+    pub(crate) effect: Effect,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub(crate) struct TimedEffectUpdate {
+    pub(crate) effect: TimedEffect,
+    #[serde(serialize_with = "serialize_millis")]
+    pub(crate) duration: Duration,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub(crate) struct AlertUpdate {
+    pub(crate) action: AlertAction,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub(crate) struct SignalUpdate {
+    pub(crate) signal: Signal,
+    #[serde(serialize_with = "serialize_millis")]
+    pub(crate) duration: Duration,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub(crate) colors: Vec<ColorUpdate>,
+}
+
+/// Durations are sent to the bridge in milliseconds.
+fn serialize_millis<S: serde::Serializer>(
+    duration: &Duration,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.serialize_u64(u64::try_from(duration.as_millis()).unwrap_or(u64::MAX))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn light_update_sends_only_what_is_set() {
+        let update = LightUpdate::new().brightness(40.0);
+        assert_eq!(
+            serde_json::to_value(&update).unwrap(),
+            json!({"dimming": {"brightness": 40.0}})
+        );
+
+        let update = LightUpdate::new()
+            .on(true)
+            .mirek_delta(-20)
+            .transition(Duration::from_millis(500));
+        assert_eq!(
+            serde_json::to_value(&update).unwrap(),
+            json!({
+                "on": {"on": true},
+                "color_temperature_delta": {"action": "down", "mirek_delta": 20},
+                "dynamics": {"duration": 500}
+            })
+        );
+        assert_eq!(
+            update.to_string(),
+            "on, color temperature down 20 mirek, over 500ms"
+        );
+    }
+
+    #[test]
+    fn light_update_checks_the_limits_of_the_bridge() {
+        assert!(matches!(
+            LightUpdate::new().check(),
+            Err(HueError::Usage(_))
+        ));
+        assert!(LightUpdate::new().on(false).check().is_ok());
+        assert!(matches!(
+            LightUpdate::new()
+                .transition(Duration::from_secs(3 * 3600))
+                .check(),
+            Err(HueError::InvalidParameter(_))
+        ));
+        assert!(matches!(
+            LightUpdate::new().brightness_delta(-101).check(),
+            Err(HueError::InvalidParameter(_))
+        ));
+        assert!(matches!(
+            LightUpdate::new()
+                .signal(Signal::Alternating, Duration::from_secs(5), &[])
+                .check(),
+            Err(HueError::Usage(_))
+        ));
+    }
+}
@@ -1,462 +1,226 @@
-use clap::{Arg, Command};
-use reqwest::blocking;
-use serde::{Deserialize, Serialize};
-use serde_json::Value;
-use std::error::Error;
-use std::fmt::{Display, Formatter};
-
-const HUE_API_APP_NAME: &str = "philips_hue_lab";
-const HUE_API_USER_NAME: &str = "hue_lab_user";
-
-/// The Hue Bridge root CA.
-///
-/// See documentation at
-/// <https://developers.meethue.com/develop/application-design-guidance/using-https/>
-const HUE_ROOT_CA: &str = include_str!("../resources/huebridge_cacert.pem");
-
-/// IP Address of the Hue Bridge
-struct BridgeIp(String);
-
-#[derive(Deserialize, Debug)]
-struct BridgeKey {
-    #[serde(rename = "username")]
-    user_name: String,
-    #[serde(rename = "clientkey")]
-    client_key: String,
-}
-
-/// App key for the Hue API
-struct AppKey(String);
-impl From<&AppKey> for String {
-    fn from(key: &AppKey) -> Self {
-        key.0.clone()
-    }
-}
-
-#[derive(Debug)]
-struct HueError(String, Option<Box<dyn Error>>);
-impl std::fmt::Display for HueError {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match &self.1 {
-            None => write!(f, "{}", self.0),
-            Some(e) => write!(f, "{} :: {}", self.0, *e),
-        }
-    }
-}
-impl Error for HueError {}
-
-/// The body for the POST /api endpoint (create a user)
-#[derive(Serialize, Debug)]
-struct CreateUserRequestBody {
-    #[serde(rename = "devicetype")]
-    device_type: String,
-}
-impl CreateUserRequestBody {
-    fn from(app_name: &str, user_name: &str) -> Self {
-        CreateUserRequestBody {
-            device_type: format!("{}#{}", app_name, user_name),
-        }
-    }
-}
-
-fn create_key(bridge_ip: &BridgeIp) -> Result<BridgeKey, HueError> {
-    let body = CreateUserRequestBody::from(HUE_API_APP_NAME, HUE_API_USER_NAME);
-    let response =
-        post_request(&bridge_ip, "/api", &body).map_err(|e| HueError(e.to_string(), Some(e)))?;
-    let parsed = parse_create_key_response(&response)?;
-    Ok(BridgeKey {
-        user_name: HUE_API_USER_NAME.to_string(),
-        client_key: parsed.user_name,
-    })
-}
-
-fn parse_create_key_response(
-    response: &serde_json::Value,
-) -> Result<HueApiCreateKeySuccessDetails, HueError> {
-    let errors = parse_api_response_errors(&response);
-    match (errors.is_empty(), response.is_array()) {
-        (false, _) => {
-            let inner: Option<Box<dyn Error>> = errors
-                .into_iter()
-                .next()
-                .map(|e| Box::new(e) as Box<dyn Error>);
-            Err(HueError(String::from("Could not create key."), inner))
-        }
-        (true, true) => {
-            let success_details = response
-                .as_array()
-                .unwrap()
-                .get(0)
-                .unwrap()
-                .as_object()
-                .unwrap()
-                .get("success");
-            match success_details {
-                None => Err(HueError(
-                    String::from(
-                        "Could not create key. success element not found in response array.",
-                    ),
-                    None,
-                )),
-                Some(details_json) => {
-                    let result = serde_json::from_value::<HueApiCreateKeySuccessDetails>(
-                        details_json.clone(),
-                    )
-                    .map_err(|e| HueError(e.to_string(), Some(Box::new(e))))?;
-                    Ok(result)
-                }
-            }
-        }
-        // We don't expect this to be reachable under normal operation
-        (_, _) => unimplemented!(),
-    }
-}
-
-/// This is the API wire format of the Hue response for a successful create-key operation.
-#[derive(Deserialize, Debug, PartialEq)]
-struct HueApiCreateKeySuccessDetails {
-    #[serde(rename = "username")]
-    user_name: String,
-}
-
-/// This is the API wire format of the Hue Error message details.
-#[derive(Deserialize, Debug, PartialEq)]
-struct HueApiErrorMessage {
-    #[serde(rename = "type")]
-    type_value: i64,
-    address: String,
-    description: String,
-}
-
-impl Display for HueApiErrorMessage {
-    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        f.write_fmt(format_args!("{:?}", self))
+use clap::{Arg, ArgGroup, ArgMatches, Command};
+use log::{info, warn, LevelFilter};
+use philips_hue_lab::color::{CieXy, Color, ColorTemperature};
+use philips_hue_lab::config::{Config, Profile};
+use philips_hue_lab::discovery::{discover_bridges, discover_single_bridge};
+use philips_hue_lab::effect::{AlertAction, Effect, Signal, TimedEffect};
+use philips_hue_lab::group::GroupType;
+use philips_hue_lab::light::LightUpdate;
+use philips_hue_lab::resource::ResourceType;
+use philips_hue_lab::tls::{fetch_certificate_fingerprint, CertificateFingerprint};
+use philips_hue_lab::transition::{parse_duration, MAX_FADE, MAX_TRANSITION};
+use philips_hue_lab::{
+    AppKey, BridgeIp, HueClient, HueDevice, HueError, MAX_BRIGHTNESS_DELTA, MAX_MIREK_DELTA,
+};
+use std::io::{IsTerminal, Write};
+use std::ops::ControlFlow;
+use std::path::PathBuf;
+use std::process::ExitCode;
+use std::time::Duration;
+
+mod api;
+mod output;
+use output::{EventRecord, LightStatusRecord, OutputFormat, RecordWriter};
+
+/// How long to search the network when no bridge is given.
+const DISCOVERY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Environment variable with the log filter, e.g. `debug` or `philips_hue_lab=trace`.
+const LOG_ENV: &str = "HUE_LOG";
+
+/// Log progress messages by default. Each `-v` adds and each `-q` removes a level,
+/// overriding `HUE_LOG`. Logging from other crates is limited to warnings.
+fn init_logging(matches: &ArgMatches) {
+    let mut builder = env_logger::Builder::new();
+    builder
+        .filter_level(LevelFilter::Warn)
+        .filter_module("philips_hue_lab", LevelFilter::Info)
+        .parse_env(env_logger::Env::new().filter(LOG_ENV))
+        .format(|buf, record| match record.level() {
+            log::Level::Info => writeln!(buf, "{}", record.args()),
+            level => writeln!(buf, "{}: {}", level, record.args()),
+        });
+    let verbose = i16::from(matches.get_count("verbose"));
+    let quiet = i16::from(matches.get_count("quiet"));
+    if verbose != 0 || quiet != 0 {
+        let levels = [
+            LevelFilter::Off,
+            LevelFilter::Error,
+            LevelFilter::Warn,
+            LevelFilter::Info,
+            LevelFilter::Debug,
+            LevelFilter::Trace,
+        ];
+        let level = levels[(3 + verbose - quiet).clamp(0, 5) as usize];
+        builder
+            .filter_level(level.min(LevelFilter::Warn))
+            .filter_module("philips_hue_lab", level);
     }
+    builder.init();
 }
 
-impl Error for HueApiErrorMessage {}
-
-/// Parse and extract all API response errors.
-/// Returns an empty vec if there are no errors in the response.
-fn parse_api_response_errors(response: &serde_json::Value) -> Vec<HueApiErrorMessage> {
-    match response.is_array() {
-        true => response
-            .as_array()
-            .unwrap()
-            .iter()
-            .filter_map(
-                |element| match (element.is_object(), element.get("error")) {
-                    (true, Some(details)) => {
-                        let msg =
-                            serde_json::from_value::<HueApiErrorMessage>(details.clone()).unwrap();
-                        Some(msg)
-                    }
-                    _ => None,
-                },
-            )
-            .collect(),
-        false => vec![],
-    }
-}
-
-fn create_reqwest_client() -> Result<blocking::Client, Box<dyn Error>> {
-    let cert = reqwest::Certificate::from_pem(HUE_ROOT_CA.as_bytes())?;
-    let client = blocking::ClientBuilder::new()
-        .add_root_certificate(cert)
-        .danger_accept_invalid_certs(true)
-        .build()?;
-    Ok(client)
-}
-
-fn get_request(
-    bridge_ip: &BridgeIp,
-    app_key: &AppKey,
-    path: &str,
-) -> Result<serde_json::Value, Box<dyn Error>> {
-    let url = format!("https://{}{}", bridge_ip.0, path);
-    println!("Requesting: {}", url);
-    let response = create_reqwest_client()?
-        .get(&url)
-        .header("Accept", "application/json")
-        .header("hue-application-key", String::from(app_key))
-        .send()?;
-    println!("Raw response: {:?}", response);
-    if !response.status().is_success() {
-        return Err(Box::new(HueError(
-            format!(
-                "Failed to send GET request to Hue Bridge: {}",
-                &response.status()
-            ),
-            None,
-        )));
-    }
-    let result = response.json::<serde_json::Value>()?;
-    Ok(result)
-}
-
-fn post_request<T>(
-    bridge_ip: &BridgeIp,
-    path: &str,
-    body: &T,
-) -> Result<serde_json::Value, Box<dyn Error>>
-where
-    T: ?Sized + Serialize,
-{
-    let url = format!("https://{}{}", bridge_ip.0, path);
-    println!("Requesting: {}", url);
-    let body_str = serde_json::to_string(body)?;
-    println!("Body: {:?}", body_str);
-    let response = create_reqwest_client()?
-        .post(&url)
-        .header("Accept", "application/json")
-        .body(body_str)
-        .send()?;
-    println!("Raw response: {:?}", response);
-    if !response.status().is_success() {
-        return Err(Box::new(HueError(
-            format!(
-                "Failed to send POST request to Hue Bridge: {}",
-                &response.status()
-            ),
-            None,
-        )));
-    }
-    let result = response.json::<serde_json::Value>()?;
-    Ok(result)
-}
-
-/// Standard HUE device information.
-#[derive(Debug, Clone, PartialEq)]
-struct DeviceInfo {
-    id: String,
-    name: String,
-    product_name: String,
-    /// The service ID for a light device (for light devices only)
-    light_id: Option<LightId>,
-}
-
-/// A Hue device on the bridge
-#[derive(Debug, Clone, PartialEq)]
-struct HueDevice(DeviceInfo);
-
-fn list_devices(bridge_ip: &BridgeIp, api_key: &AppKey) -> Result<Vec<HueDevice>, HueError> {
-    let response = get_request(&bridge_ip, &api_key, "/clip/v2/resource/device")
-        .map_err(|e| HueError(e.to_string(), Some(e)))?;
-    let parsed = parse_list_devices_response(&response)?;
-    Ok(parsed)
-}
-
-/// Hue API representation of a device (some of the information)
-#[derive(Deserialize, Debug)]
-struct HueApiDeviceResponse {
-    errors: Vec<HueApiErrorMessage>,
-    data: Vec<HueApiDeviceData>,
-}
-
-/// Hue API representation of a device (some of the information)
-#[derive(Deserialize, Debug)]
-struct HueApiDeviceData {
-    id: String,
-    product_data: HueApiDeviceProductData,
-    metadata: HueApiDeviceMetadata,
-    services: Vec<HueApiDeviceService>,
-}
-
-/// Hue API representation of device product data (some of the information)
-#[derive(Deserialize, Debug)]
-struct HueApiDeviceProductData {
-    model_id: String,
-    product_name: String,
-}
-/// Hue API representation of device metadata (some of the information)
-#[derive(Deserialize, Debug)]
-struct HueApiDeviceMetadata {
-    name: String,
-}
-
-/// Hue API representation of device service data (some of the information)
-#[derive(Deserialize, Debug)]
-struct HueApiDeviceService {
-    rid: String,
-    rtype: String,
-}
-
-fn parse_list_devices_response(json_response: &Value) -> Result<Vec<HueDevice>, HueError> {
-    let parsed: HueApiDeviceResponse =
-        serde_json::from_value::<HueApiDeviceResponse>(json_response.clone())
-            .map_err(|e| HueError(e.to_string(), Some(Box::new(e))))?;
-    match parsed.errors.is_empty() {
-        true => Ok(parsed
-            .data
-            .into_iter()
-            .map(|d| {
-                HueDevice(DeviceInfo {
-                    id: d.id,
-                    name: d.metadata.name,
-                    product_name: d.product_data.product_name,
-                    light_id: d
-                        .services
-                        .iter()
-                        .find(|s| s.rtype == "light")
-                        .map(|s| LightId(s.rid.clone())),
-                })
-            })
-            .collect()),
-        false => Err(HueError(String::from("Response has errors"), None)),
-    }
-}
-
-/// The body for the PUT /clip/v2/resource/light/{id} endpoint
-/// See documentation at <https://developers.meethue.com/develop/hue-api-v2/core-concepts/#controlling-light>
-#[derive(Serialize, Debug)]
-struct LightControlRequestBody {
-    on: LightOnOffState,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    dimming: Option<LightDimmingState>,
-}
-
-#[derive(Serialize, Debug)]
-struct LightOnOffState {
-    on: bool,
-}
-
-#[derive(Serialize, Debug)]
-struct LightDimmingState {
-    brightness: f32,
+/// The application key from `--key` or `HUE_API_KEY`, falling back to the profile.
+fn app_key(sub_matches: &ArgMatches, profile: &Profile) -> Result<AppKey, HueError> {
+    sub_matches
+        .get_one::<String>("key")
+        .or(profile.app_key.as_ref())
+        .map(|key| AppKey(key.clone()))
+        .ok_or_else(|| {
+            HueError::Usage(String::from("No application key provided. Use --key, set HUE_API_KEY, or run create-key to store one in the profile."))
+        })
 }
 
-/// A light ID, the service ID for a light device.
-#[derive(Debug, Clone, PartialEq)]
-struct LightId(String);
-impl From<&LightId> for String {
-    fn from(light_id: &LightId) -> Self {
-        light_id.0.clone()
+/// A subcommand of `api` for one HTTP method.
+fn api_command(
+    name: &'static str,
+    about: &'static str,
+    with_body: bool,
+    app_key_arg: &Arg,
+) -> Command {
+    let command = Command::new(name)
+        .about(about)
+        .arg(app_key_arg.clone())
+        .arg(
+            Arg::new("path")
+                .help("The API path, e.g. /clip/v2/resource/light. Paths without a leading slash are relative to /clip/v2/, e.g. resource/light.")
+                .required(true)
+                .index(1),
+        )
+        .arg(
+            Arg::new("filter")
+                .help("Only print the part of the response at this JSON pointer, e.g. /data/0/on, or path, e.g. $.data[0].on")
+                .long("filter")
+                .value_name("PATH"),
+        );
+    match with_body {
+        true => command.arg(
+            Arg::new("body")
+                .help("The JSON request body, @FILE to read it from a file, or - to read it from stdin")
+                .required(true)
+                .index(2),
+        ),
+        false => command,
     }
 }
 
-fn control_light(
-    bridge_ip: &BridgeIp,
-    api_key: &AppKey,
-    light_id: &LightId,
-    on: bool,
-    dimming_level: Option<u8>,
-) -> Result<(), HueError> {
-    let dimming = dimming_level.map(|level| {
-        // Convert 0-100 scale to 0.0-100.0 brightness
-        let brightness = f32::from(level.clamp(0, 100));
-        LightDimmingState { brightness }
-    });
-
-    let body = LightControlRequestBody {
-        on: LightOnOffState { on },
-        dimming,
-    };
-
-    let path = format!("/clip/v2/resource/light/{}", String::from(light_id));
-    put_request(&bridge_ip, &api_key, &path, &body)
-        .map_err(|e| HueError(e.to_string(), Some(e)))?;
-    Ok(())
+/// The `room` or `zone` subcommand, which controls all the lights of a group at once.
+fn group_command(
+    group_type: GroupType,
+    about: &'static str,
+    app_key_arg: &Arg,
+    output_arg: &Arg,
+) -> Command {
+    Command::new(group_type.as_str())
+        .about(about)
+        .args_conflicts_with_subcommands(true)
+        .subcommand_negates_reqs(true)
+        .subcommand(
+            Command::new("list")
+                .about(format!("List the {}s with their members", group_type))
+                .arg(app_key_arg.clone())
+                .arg(output_arg.clone()),
+        )
+        .arg(app_key_arg.clone())
+        .arg(
+            Arg::new("id")
+                .help(format!("The {} ID, its grouped_light ID, or a part of its name (case-insensitive substring search).", group_type))
+                .required(true)
+                .index(1),
+        )
+        .arg(
+            Arg::new("on")
+                .help("Turn the lights on")
+                .long("on")
+                .action(clap::ArgAction::SetTrue)
+                .conflicts_with("off"),
+        )
+        .arg(
+            Arg::new("off")
+                .help("Turn the lights off")
+                .long("off")
+                .action(clap::ArgAction::SetTrue)
+                .conflicts_with("on"),
+        )
+        .arg(
+            Arg::new("dim")
+                .help("Set the dimming level (0-100)")
+                .long("dim")
+                .value_name("LEVEL")
+                .value_parser(clap::value_parser!(u8).range(0..=100)),
+        )
+        .arg(
+            Arg::new("brighter")
+                .help("Make the lights brighter by this many percent")
+                .long("brighter")
+                .value_name("PERCENT")
+                .value_parser(clap::value_parser!(u8).range(1..=i64::from(MAX_BRIGHTNESS_DELTA)))
+                .conflicts_with_all(["dim", "dimmer", "off"]),
+        )
+        .arg(
+            Arg::new("dimmer")
+                .help("Make the lights dimmer by this many percent")
+                .long("dimmer")
+                .value_name("PERCENT")
+                .value_parser(clap::value_parser!(u8).range(1..=i64::from(MAX_BRIGHTNESS_DELTA)))
+                .conflicts_with_all(["dim", "off"]),
+        )
+        .arg(
+            Arg::new("color")
+                .help("Set the color, in the same formats as for a light. Each light shows the closest color it can.")
+                .long("color")
+                .value_name("COLOR")
+                .value_parser(clap::value_parser!(Color))
+                .conflicts_with("off"),
+        )
+        .arg(
+            Arg::new("ct")
+                .help("Set the white color temperature, in Kelvin (e.g. 2700K) or mirek (e.g. 370mirek). Each light shows the closest one it can.")
+                .long("ct")
+                .value_name("TEMPERATURE")
+                .value_parser(clap::value_parser!(ColorTemperature))
+                .conflicts_with_all(["off", "color"]),
+        )
+        .arg(
+            Arg::new("transition")
+                .help("Change to the new state gradually over this time, e.g. 500ms, 10s or 5m (at most 100 minutes)")
+                .long("transition")
+                .value_name("DURATION")
+                .value_parser(parse_duration),
+        )
 }
 
-/// Send a PUT request to the Hue Bridge.
-fn put_request<T>(
-    bridge_ip: &BridgeIp,
-    app_key: &AppKey,
-    path: &str,
-    body: &T,
-) -> Result<serde_json::Value, Box<dyn Error>>
-where
-    T: ?Sized + Serialize,
-{
-    let url = format!("https://{}{}", bridge_ip.0, path);
-    println!("Requesting: {}", url);
-    let body_str = serde_json::to_string(body)?;
-    println!("Body: {:?}", body_str);
-    let response = create_reqwest_client()?
-        .put(&url)
-        .header("Accept", "application/json")
-        .header("hue-application-key", String::from(app_key))
-        .body(body_str)
-        .send()?;
-    println!("Raw response: {:?}", response);
-    if !response.status().is_success() {
-        return Err(Box::new(HueError(
-            format!(
-                "Failed to send PUT request to Hue Bridge: {}",
-                &response.status()
-            ),
-            None,
-        )));
+/// The full path for the `api` subcommand, with relative paths under /clip/v2/.
+fn api_path(path: &str) -> String {
+    match path.starts_with('/') {
+        true => path.to_string(),
+        false => format!("/clip/v2/{}", path),
     }
-    let result = response.json::<serde_json::Value>()?;
-    Ok(result)
 }
 
-/// Find a light by ID or name.
-/// First tries to match the input as a light ID.
-/// If no match is found, queries the bridge for all devices and searches for a name match.
-/// Returns the light ID if a single match is found.
-fn find_light_by_id_or_name(
-    bridge_ip: &BridgeIp,
-    api_key: &AppKey,
-    id_or_name: &str,
-) -> Result<LightId, HueError> {
-    // First, try to list all devices
-    let devices = list_devices(bridge_ip, api_key)?;
-    
-    // Check if the input matches a light ID directly
-    for HueDevice(device_info) in &devices {
-        if let Some(light_id) = &device_info.light_id {
-            if light_id.0 == id_or_name {
-                return Ok(light_id.clone());
-            }
-        }
-    }
-    
-    // If no direct ID match, search for name matches (case-insensitive substring)
-    let name_query = id_or_name.to_lowercase();
-    let mut matches = Vec::new();
-    
-    // Collect devices with matching names
-    for HueDevice(device_info) in devices {
-        if let Some(light_id) = device_info.light_id.clone() {
-            if device_info.name.to_lowercase().contains(&name_query) {
-                println!("Found matching light: {} ({})", device_info.name, light_id.0);
-                matches.push((device_info, light_id));
-            }
-        }
-    }
-    
-    match matches.len() {
-        0 => Err(HueError(format!("No light found with ID or name matching '{}'", id_or_name), None)),
-        1 => {
-            let (device_info, light_id) = matches.remove(0);
-            println!("Using light: {} ({})", device_info.name, light_id.0);
-            Ok(light_id)
-        },
-        _ => {
-            let match_info: Vec<String> = matches
-                .iter()
-                .map(|(info, _)| format!("{} ({})", info.name, info.id))
-                .collect();
-            Err(HueError(
-                format!(
-                    "Multiple lights found matching '{}'. Please be more specific or use the light ID directly: {}",
-                    id_or_name,
-                    match_info.join(", ")
-                ),
-                None,
-            ))
+fn main() -> ExitCode {
+    match run() {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            ExitCode::from(e.exit_code())
         }
     }
 }
 
-fn main() -> Result<(), Box<dyn Error>> {
+fn run() -> Result<(), HueError> {
     let app_key_arg = Arg::new("key")
-        .help("Application key for the Philips Hue API")
+        .help("Application key for the Philips Hue API. Defaults to the key in the profile.")
         .long("key")
         .value_name("KEY")
-        .required(true);
+        .env("HUE_API_KEY");
+
+    let output_arg = Arg::new("output")
+        .help("The output format")
+        .long("output")
+        .short('o')
+        .value_name("FORMAT")
+        .default_value("table")
+        .value_parser(clap::value_parser!(OutputFormat));
 
     let matches = Command::new("philips_hue_lab")
         .version(env!("CARGO_PKG_VERSION"))
@@ -465,21 +229,118 @@ fn main() -> Result<(), Box<dyn Error>> {
             Arg::new("bridge")
                 .long("bridge")
                 .value_name("IP")
-                .help("The IP address of the Hue Bridge. You can find the IP number by opening the Philips Hue app, selecting the Hue Bridge, and pressing the information icon.")
+                .help("The IP address of the Hue Bridge. You can find the IP number by opening the Philips Hue app, selecting the Hue Bridge, and pressing the information icon. If omitted, the bridge from the profile is used, or the bridge is discovered on the network.")
+                .num_args(1)
+                .env("HUE_BRIDGE_IP"),
+        )
+        .arg(
+            Arg::new("verbose")
+                .long("verbose")
+                .short('v')
+                .help("Log more details, e.g. -v for HTTP requests and responses (with the keys redacted). Can be repeated. Overrides HUE_LOG.")
+                .action(clap::ArgAction::Count)
+                .global(true),
+        )
+        .arg(
+            Arg::new("quiet")
+                .long("quiet")
+                .short('q')
+                .help("Log less, e.g. -q for only warnings and errors. Can be repeated. Overrides HUE_LOG.")
+                .action(clap::ArgAction::Count)
+                .global(true),
+        )
+        .arg(
+            Arg::new("profile")
+                .long("profile")
+                .value_name("NAME")
+                .help("The profile in the configuration file to use. Defaults to the active profile.")
+                .num_args(1)
+                .env("HUE_PROFILE"),
+        )
+        .arg(
+            Arg::new("ca-cert")
+                .long("ca-cert")
+                .value_name("FILE")
+                .help("Also trust the root CA certificates in this PEM file when verifying the bridge certificate, in addition to the Hue root CA.")
+                .num_args(1)
+                .env("HUE_CA_CERT"),
+        )
+        .arg(
+            Arg::new("connect-timeout")
+                .long("connect-timeout")
+                .value_name("SECONDS")
+                .help("How long to wait for a connection to the Hue Bridge")
+                .default_value("10")
+                .value_parser(clap::value_parser!(u64).range(1..)),
+        )
+        .arg(
+            Arg::new("request-timeout")
+                .long("request-timeout")
+                .value_name("SECONDS")
+                .help("How long to wait for the response to a request to the Hue Bridge. The watch command waits for events indefinitely.")
+                .default_value("30")
+                .value_parser(clap::value_parser!(u64).range(1..)),
+        )
+        .arg(
+            Arg::new("config")
+                .long("config")
+                .value_name("FILE")
+                .help("The configuration file with the profiles. Defaults to philips_hue_lab/config.toml in the user configuration directory, or HUE_CONFIG if set.")
                 .num_args(1),
         )
+        .subcommand(
+            Command::new("discover")
+                .about("Discover Hue Bridges on the network.")
+                .arg(
+                    Arg::new("timeout")
+                        .help("How long to search the network, in seconds")
+                        .long("timeout")
+                        .value_name("SECONDS")
+                        .default_value("5")
+                        .value_parser(clap::value_parser!(u64)),
+                )
+                .arg(output_arg.clone()),
+        )
         .subcommand(
             Command::new("create-key")
-                .about("Ask the Hue Bridge to generate an application key. Press the Link button on the bridge to authorize this operation.")
+                .about("Ask the Hue Bridge to generate an application key and save it in the profile. Press the Link button on the bridge to authorize this operation.")
+                .arg(
+                    Arg::new("wait")
+                        .help("Keep trying for up to this many seconds while waiting for the Link button to be pressed")
+                        .long("wait")
+                        .value_name("SECONDS")
+                        .default_value("0")
+                        .value_parser(clap::value_parser!(u64)),
+                )
+                .arg(
+                    Arg::new("trust-on-first-use")
+                        .help("Trust the certificate the bridge presents now and pin it in the profile, instead of verifying it against the Hue root CA. Only for older bridges with a self-signed certificate.")
+                        .long("trust-on-first-use")
+                        .action(clap::ArgAction::SetTrue),
+                )
         )
         .subcommand(
             Command::new("list")
                 .about("List all devices on the Hue Bridge.")
-                .arg(app_key_arg.clone()),
+                .arg(app_key_arg.clone())
+                .arg(output_arg.clone()),
         )
         .subcommand(
             Command::new("light")
-                .about("Control a light")
+                .about("Control a light, or show the state of lights with `light status`")
+                .args_conflicts_with_subcommands(true)
+                .subcommand_negates_reqs(true)
+                .subcommand(
+                    Command::new("status")
+                        .about("Show the state of a light, or of all lights")
+                        .arg(app_key_arg.clone())
+                        .arg(output_arg.clone())
+                        .arg(
+                            Arg::new("id")
+                                .help("The light device service ID or a part of the light name. Shows all lights when left out.")
+                                .index(1)
+                        )
+                )
                 .arg(app_key_arg.clone())
                 .arg(
                     Arg::new("id")
@@ -501,6 +362,13 @@ fn main() -> Result<(), Box<dyn Error>> {
                         .action(clap::ArgAction::SetTrue)
                         .conflicts_with("on")
                 )
+                .arg(
+                    Arg::new("toggle")
+                        .help("Turn the light off if it is on, or on if it is off")
+                        .long("toggle")
+                        .action(clap::ArgAction::SetTrue)
+                        .conflicts_with_all(["on", "off", "dim", "color", "ct", "fade"])
+                )
                 .arg(
                     Arg::new("dim")
                         .help("Set the dimming level (0-100)")
@@ -508,229 +376,609 @@ fn main() -> Result<(), Box<dyn Error>> {
                         .value_name("LEVEL")
                         .value_parser(clap::value_parser!(u8).range(0..=100))
                 )
+                .arg(
+                    Arg::new("brighter")
+                        .help("Make the light brighter by this many percent")
+                        .long("brighter")
+                        .value_name("PERCENT")
+                        .value_parser(clap::value_parser!(u8).range(1..=i64::from(MAX_BRIGHTNESS_DELTA)))
+                        .conflicts_with_all(["dim", "dimmer", "off", "fade"])
+                )
+                .arg(
+                    Arg::new("dimmer")
+                        .help("Make the light dimmer by this many percent")
+                        .long("dimmer")
+                        .value_name("PERCENT")
+                        .value_parser(clap::value_parser!(u8).range(1..=i64::from(MAX_BRIGHTNESS_DELTA)))
+                        .conflicts_with_all(["dim", "off", "fade"])
+                )
+                .arg(
+                    Arg::new("color")
+                        .help("Set the color: #rrggbb, a CSS color name such as orange, hsv(h, s%, v%) or xy(x, y)")
+                        .long("color")
+                        .value_name("COLOR")
+                        .value_parser(clap::value_parser!(Color))
+                        .conflicts_with("off")
+                )
+                .arg(
+                    Arg::new("ct")
+                        .help("Set the white color temperature, in Kelvin (e.g. 2700K) or mirek (e.g. 370mirek). Plain numbers from 1000 are Kelvin, lower ones mirek.")
+                        .long("ct")
+                        .value_name("TEMPERATURE")
+                        .value_parser(clap::value_parser!(ColorTemperature))
+                        .conflicts_with_all(["off", "color"])
+                )
+                .arg(
+                    Arg::new("clamp")
+                        .help("Use the closest color temperature the light can show when --ct is outside its range, instead of failing")
+                        .long("clamp")
+                        .action(clap::ArgAction::SetTrue)
+                        .requires("ct")
+                )
+                .arg(
+                    Arg::new("warmer")
+                        .help(format!("Make the white color temperature warmer by this many mirek (1-{})", MAX_MIREK_DELTA))
+                        .long("warmer")
+                        .value_name("MIREK")
+                        .value_parser(clap::value_parser!(u16).range(1..=i64::from(MAX_MIREK_DELTA)))
+                        .conflicts_with_all(["ct", "color", "cooler", "off", "fade"])
+                )
+                .arg(
+                    Arg::new("cooler")
+                        .help(format!("Make the white color temperature cooler by this many mirek (1-{})", MAX_MIREK_DELTA))
+                        .long("cooler")
+                        .value_name("MIREK")
+                        .value_parser(clap::value_parser!(u16).range(1..=i64::from(MAX_MIREK_DELTA)))
+                        .conflicts_with_all(["ct", "color", "off", "fade"])
+                )
+                .arg(
+                    Arg::new("transition")
+                        .help("Change to the new state gradually over this time, e.g. 500ms, 10s or 5m (at most 100 minutes, unless --fade is given)")
+                        .long("transition")
+                        .value_name("DURATION")
+                        .value_parser(parse_duration)
+                )
+                .arg(
+                    Arg::new("fade")
+                        .help("Fade the brightness in steps sent by this tool, for transitions longer than the bridge allows. Keeps running until the fade is done.")
+                        .long("fade")
+                        .action(clap::ArgAction::SetTrue)
+                        .requires("transition")
+                        .conflicts_with_all(["color", "ct", "effect", "timed-effect", "alert", "signal"])
+                )
+                .arg(
+                    Arg::new("effect")
+                        .help("Play an effect until it is stopped with no_effect: candle, fire, sparkle, prism, opal, glisten, underwater, cosmos, sunbeam or enchant")
+                        .long("effect")
+                        .value_name("EFFECT")
+                        .value_parser(clap::value_parser!(Effect))
+                )
+                .arg(
+                    Arg::new("timed-effect")
+                        .help("Play a timed effect over --duration: sunrise, sunset, or no_effect to stop it")
+                        .long("timed-effect")
+                        .value_name("EFFECT")
+                        .value_parser(clap::value_parser!(TimedEffect))
+                        .requires("duration")
+                )
+                .arg(
+                    Arg::new("alert")
+                        .help("Make the light breathe once, e.g. to identify it")
+                        .long("alert")
+                        .action(clap::ArgAction::SetTrue)
+                )
+                .arg(
+                    Arg::new("signal")
+                        .help("Show a signal for --duration: on_off, on_off_color (with one --signal-color), alternating (with two --signal-color), or no_signal to stop it")
+                        .long("signal")
+                        .value_name("SIGNAL")
+                        .value_parser(clap::value_parser!(Signal))
+                        .requires("duration")
+                )
+                .arg(
+                    Arg::new("signal-color")
+                        .help("A color for --signal, in the same formats as --color. Can be repeated.")
+                        .long("signal-color")
+                        .value_name("COLOR")
+                        .value_parser(clap::value_parser!(Color))
+                        .action(clap::ArgAction::Append)
+                        .requires("signal")
+                )
+                .arg(
+                    Arg::new("duration")
+                        .help("How long to play --timed-effect or show --signal, e.g. 30s or 20m")
+                        .long("duration")
+                        .value_name("DURATION")
+                        .value_parser(parse_duration)
+                        .requires("timed")
+                )
+                .group(ArgGroup::new("timed").args(["timed-effect", "signal"]).multiple(true))
+        )
+        .subcommand(group_command(
+            GroupType::Room,
+            "Control all the lights of a room at once, or list the rooms with `room list`",
+            &app_key_arg,
+            &output_arg,
+        ))
+        .subcommand(group_command(
+            GroupType::Zone,
+            "Control all the lights of a zone at once, or list the zones with `zone list`",
+            &app_key_arg,
+            &output_arg,
+        ))
+        .subcommand(
+            Command::new("watch")
+                .about("Print events from the Hue Bridge as they happen, e.g. motion, button presses and light changes.")
+                .arg(app_key_arg.clone())
+                .arg(output_arg.clone())
+                .arg(
+                    Arg::new("type")
+                        .help("Only show events for this resource type, e.g. light, motion or button. Can be repeated.")
+                        .long("type")
+                        .value_name("TYPE")
+                        .value_parser(clap::value_parser!(ResourceType))
+                        .action(clap::ArgAction::Append)
+                )
+                .arg(
+                    Arg::new("id")
+                        .help("Only show events for this resource ID or its owner device ID. Can be repeated.")
+                        .long("id")
+                        .value_name("ID")
+                        .action(clap::ArgAction::Append)
+                )
+                .arg(
+                    Arg::new("count")
+                        .help("Stop after printing this many events")
+                        .long("count")
+                        .value_name("N")
+                        .value_parser(clap::value_parser!(u64).range(1..))
+                )
+        )
+        .subcommand(
+            Command::new("api")
+                .about("Send a raw request to the Hue Bridge API and print the JSON response.")
+                .subcommand_required(true)
+                .subcommand(api_command("get", "Send a GET request", false, &app_key_arg))
+                .subcommand(api_command("put", "Send a PUT request", true, &app_key_arg))
+                .subcommand(api_command("post", "Send a POST request", true, &app_key_arg))
+                .subcommand(api_command("delete", "Send a DELETE request", false, &app_key_arg))
         )
         .get_matches();
+    init_logging(&matches);
+
+    if let Some(discover_matches) = matches.subcommand_matches("discover") {
+        let timeout = Duration::from_secs(*discover_matches.get_one::<u64>("timeout").unwrap());
+        let format = *discover_matches.get_one::<OutputFormat>("output").unwrap();
+        info!("Searching for Hue Bridges on the network...");
+        let bridges = discover_bridges(timeout)?;
+        let mut writer = RecordWriter::start(format, std::io::stdout());
+        for bridge in &bridges {
+            writer.write(bridge);
+        }
+        writer.finish();
+        return Ok(());
+    }
 
-    if let Some(bridge_ip) = matches.get_one::<String>("bridge") {
-        println!("Using Hue Bridge at: {}", bridge_ip);
-        let bridge = BridgeIp(String::from(bridge_ip));
-        if let Some(_sub_matches) = matches.subcommand_matches("create-key") {
-            println!("Requesting creation of a new application key on the Hue Bridge. Make sure you have pressed the link button on the bridge!");
-            let bridge_key = create_key(&bridge)?;
-            println!("Key created: {:?}", bridge_key);
-            Ok(())
-        } else if let Some(list_matches) = matches.subcommand_matches("list") {
-            let app_key = AppKey(String::from(
-                list_matches
-                    .get_one::<String>(app_key_arg.get_id().as_str())
-                    .unwrap(),
-            ));
-            println!("Requesting list of devices on the Hue Bridge...");
-            let devices = list_devices(&bridge, &app_key)?;
-            println!(
-                "{:36} | {:30} | {:20} | {:20}",
-                "Device ID", "Name", "Product Name", "Light ID"
+    let config_path = match matches.get_one::<String>("config") {
+        Some(path) => Some(PathBuf::from(path)),
+        None => Config::default_path(),
+    };
+    let mut config = match &config_path {
+        Some(path) => Config::load(path)?,
+        None => Config::default(),
+    };
+    let profile_name = config
+        .profile_name(matches.get_one::<String>("profile").map(String::as_str))
+        .to_string();
+    let profile = config.profile(&profile_name).cloned().unwrap_or_default();
+
+    // The bridge ID and pinned certificate in the profile are only for the profile's bridge.
+    let mut bridge_id = profile.bridge_id.clone();
+    let mut fingerprint = profile
+        .cert_fingerprint
+        .as_deref()
+        .map(CertificateFingerprint::parse)
+        .transpose()?;
+    if matches
+        .get_one::<String>("bridge")
+        .is_some_and(|bridge| Some(bridge) != profile.bridge.as_ref())
+    {
+        bridge_id = None;
+        fingerprint = None;
+    }
+    let bridge = match matches
+        .get_one::<String>("bridge")
+        .or(profile.bridge.as_ref())
+    {
+        Some(bridge_ip) => BridgeIp(String::from(bridge_ip)),
+        None => {
+            info!("No Hue Bridge IP address provided, searching the network...");
+            let discovered = discover_single_bridge(DISCOVERY_TIMEOUT)?;
+            info!("Discovered Hue Bridge {}", discovered.id);
+            bridge_id = Some(discovered.id.clone());
+            discovered.bridge_ip().ok_or_else(|| {
+                HueError::Discovery(format!("Hue Bridge {} has no IP address.", discovered.id))
+            })?
+        }
+    };
+    info!("Using Hue Bridge at: {}", bridge.0);
+
+    let connect_timeout = Duration::from_secs(*matches.get_one::<u64>("connect-timeout").unwrap());
+    let request_timeout = Duration::from_secs(*matches.get_one::<u64>("request-timeout").unwrap());
+    let mut builder = HueClient::builder(bridge.clone())
+        .connect_timeout(connect_timeout)
+        .request_timeout(request_timeout);
+    if let Some(path) = matches.get_one::<String>("ca-cert") {
+        let pem = std::fs::read(path).map_err(|e| HueError::Config {
+            message: format!("Could not read CA certificate file {}", path),
+            source: Box::new(e),
+        })?;
+        builder = builder.root_certificate_pem(&pem);
+    }
+    if let Some(bridge_id) = &bridge_id {
+        builder = builder.bridge_id(bridge_id);
+    }
+    if let Some(fingerprint) = &fingerprint {
+        builder = builder.pinned_certificate(fingerprint.clone());
+    }
+    if let Some(create_key_matches) = matches.subcommand_matches("create-key") {
+        let wait = Duration::from_secs(*create_key_matches.get_one::<u64>("wait").unwrap());
+        if create_key_matches.get_flag("trust-on-first-use") {
+            let trusted = fetch_certificate_fingerprint(&bridge, connect_timeout)?;
+            warn!(
+                "Trusting the Hue Bridge certificate with SHA-256 fingerprint {} without verifying it.",
+                trusted
             );
-            for HueDevice(di) in devices {
-                println!(
-                    "{:36} | {:30} | {:20} | {:20}",
-                    di.id,
-                    di.name,
-                    di.product_name,
-                    match di.light_id {
-                        Some(light_id) => String::from(&light_id),
-                        None => "".to_string(),
-                    }
-                );
+            builder = builder.pinned_certificate(trusted.clone());
+            fingerprint = Some(trusted);
+        }
+        let client = builder.build()?;
+        let bridge_key = if wait.is_zero() {
+            info!("Requesting creation of a new application key on the Hue Bridge. Make sure you have pressed the link button on the bridge!");
+            client.create_key()?
+        } else {
+            info!(
+                "Requesting creation of a new application key on the Hue Bridge. Press the link button on the bridge within {} seconds.",
+                wait.as_secs()
+            );
+            client.create_key_waiting(wait)?
+        };
+        println!("Key created: {:?}", bridge_key);
+
+        if bridge_id.is_none() || matches.get_one::<String>("bridge").is_some() {
+            match client.bridge_config() {
+                Ok(bridge_config) => bridge_id = Some(bridge_config.bridge_id.to_lowercase()),
+                Err(e) => warn!("Could not read the bridge ID: {}", e),
             }
-            Ok(())
-        } else if let Some(light_matches) = matches.subcommand_matches("light") {
-            let app_key = AppKey(String::from(
-                light_matches
-                    .get_one::<String>(app_key_arg.get_id().as_str())
-                    .unwrap(),
-            ));
-            let id_or_name = light_matches.get_one::<String>("id").unwrap();
-
-            let turn_on = match (light_matches.get_flag("on"), light_matches.get_flag("off")) {
-                (true, false) => true,
-                (false, true) => false,
-                _ => {
-                    return Err(Box::new(HueError(
-                        String::from("Must specify either --on or --off"),
-                        None,
-                    )))
-                }
-            };
-
-            // Get the dimming level if provided
-            let dimming_level = light_matches.get_one::<u8>("dim").copied();
+        }
+        let config_path = config_path.ok_or_else(|| {
+            HueError::Usage(String::from(
+                "Could not determine the configuration file location. Use --config to set it.",
+            ))
+        })?;
+        config.set_profile(
+            &profile_name,
+            Profile {
+                bridge: Some(bridge.0),
+                bridge_id,
+                app_key: Some(bridge_key.user_name),
+                client_key: bridge_key.client_key,
+                cert_fingerprint: fingerprint.map(|f| f.to_string()),
+            },
+        );
+        config.save(&config_path)?;
+        println!(
+            "Saved the key in profile '{}' in {}",
+            profile_name,
+            config_path.display()
+        );
+        Ok(())
+    } else if let Some(list_matches) = matches.subcommand_matches("list") {
+        let app_key = app_key(list_matches, &profile)?;
+        let client = builder.app_key(app_key).build()?;
+        let format = *list_matches.get_one::<OutputFormat>("output").unwrap();
+        info!("Requesting list of devices on the Hue Bridge...");
+        let devices = client.list_devices()?;
+        let mut writer = RecordWriter::start(format, std::io::stdout());
+        for HueDevice(device_info) in &devices {
+            writer.write(device_info);
+        }
+        writer.finish();
+        Ok(())
+    } else if let Some(status_matches) = matches
+        .subcommand_matches("light")
+        .and_then(|light_matches| light_matches.subcommand_matches("status"))
+    {
+        let app_key = app_key(status_matches, &profile)?;
+        let client = builder.app_key(app_key).build()?;
+        let format = *status_matches.get_one::<OutputFormat>("output").unwrap();
+        let states = match status_matches.get_one::<String>("id") {
+            Some(id_or_name) => {
+                info!("Finding light with ID or name: {}", id_or_name);
+                let light_id = client.find_light_by_id_or_name(id_or_name)?;
+                vec![client.light_state(&light_id)?]
+            }
+            None => client.light_states()?,
+        };
+        let swatch = format == OutputFormat::Table && std::io::stdout().is_terminal();
+        let mut writer = RecordWriter::start(format, std::io::stdout());
+        for state in states {
+            writer.write(&LightStatusRecord { state, swatch });
+        }
+        writer.finish();
+        Ok(())
+    } else if let Some(light_matches) = matches.subcommand_matches("light") {
+        let app_key = app_key(light_matches, &profile)?;
+        let client = builder.app_key(app_key).build()?;
+        let id_or_name = light_matches.get_one::<String>("id").unwrap();
+
+        let color = light_matches.get_one::<Color>("color");
+        let temperature = light_matches.get_one::<ColorTemperature>("ct").copied();
+        let turn_on = match (light_matches.get_flag("on"), light_matches.get_flag("off")) {
+            (true, false) => Some(true),
+            (false, true) => Some(false),
+            _ => None,
+        };
+        let effect = light_matches.get_one::<Effect>("effect").copied();
+        let timed_effect = light_matches
+            .get_one::<TimedEffect>("timed-effect")
+            .copied();
+        let alert = light_matches.get_flag("alert");
+        let signal = light_matches.get_one::<Signal>("signal").copied();
+        let signal_colors: Vec<CieXy> = light_matches
+            .get_many::<Color>("signal-color")
+            .unwrap_or_default()
+            .map(Color::to_xy)
+            .collect();
+        let duration = light_matches.get_one::<Duration>("duration").copied();
+        let toggle = light_matches.get_flag("toggle");
+        // The parser limits the deltas to MAX_BRIGHTNESS_DELTA and MAX_MIREK_DELTA, which fit in i8 and i16.
+        let brightness_delta = match (
+            light_matches.get_one::<u8>("brighter"),
+            light_matches.get_one::<u8>("dimmer"),
+        ) {
+            (Some(brighter), _) => Some(*brighter as i8),
+            (_, Some(dimmer)) => Some(-(*dimmer as i8)),
+            (None, None) => None,
+        };
+        let mirek_delta = match (
+            light_matches.get_one::<u16>("warmer"),
+            light_matches.get_one::<u16>("cooler"),
+        ) {
+            (Some(warmer), _) => Some(*warmer as i16),
+            (_, Some(cooler)) => Some(-(*cooler as i16)),
+            (None, None) => None,
+        };
+        let dimming_level = light_matches.get_one::<u8>("dim").copied();
+        let transition = light_matches.get_one::<Duration>("transition").copied();
+        let fade = light_matches.get_flag("fade");
+        if let Some(transition) = transition.filter(|t| *t > MAX_TRANSITION && !fade) {
+            return Err(HueError::Usage(format!(
+                "The transition of {:?} is longer than the bridge allows ({} minutes). Add --fade to fade in steps instead.",
+                transition,
+                MAX_TRANSITION.as_secs() / 60
+            )));
+        }
+        if let Some(transition) = transition.filter(|t| *t > MAX_FADE && fade) {
+            return Err(HueError::Usage(format!(
+                "The fade of {:?} is longer than the longest fade ({} hours)",
+                transition,
+                MAX_FADE.as_secs() / 3600
+            )));
+        }
 
-            println!(
-                "Finding light with ID or name: {}",
-                id_or_name
-            );
-            
-            let light_id = find_light_by_id_or_name(&bridge, &app_key, id_or_name)?;
-            
-            // Update the message to include dimming information
-            let state_message = match (turn_on, dimming_level) {
-                (false, _) => "off".to_string(),
-                (true, None) => "on".to_string(),
-                (true, Some(level)) => format!("on with brightness {}%", level),
-            };
-            
-            println!(
-                "Setting light {} to {}",
-                light_id.0,
-                state_message
-            );
-            
-            control_light(&bridge, &app_key, &light_id, turn_on, dimming_level)?;
-            println!("Light state updated successfully");
-            Ok(())
-        } else {
-            Err(Box::new(HueError(
-                String::from("No subcommand provided. Please provide a subcommand."),
-                None,
-            )))
+        let mut update = LightUpdate::new();
+        if let Some(on) = turn_on {
+            update = update.on(on);
+        }
+        if let Some(level) = dimming_level {
+            update = update.brightness(f32::from(level));
+        }
+        if let Some(color) = color {
+            update = update.color(color.to_xy());
+        }
+        if let Some(temperature) = temperature {
+            update = update.color_temperature(temperature, light_matches.get_flag("clamp"));
+        }
+        if let Some(delta) = brightness_delta {
+            update = update.brightness_delta(delta);
+        }
+        if let Some(delta) = mirek_delta {
+            update = update.mirek_delta(delta);
+        }
+        if let Some(transition) = transition {
+            update = update.transition(transition);
+        }
+        if let Some(effect) = effect {
+            update = update.effect(effect);
+        }
+        if let (Some(timed_effect), Some(duration)) = (timed_effect, duration) {
+            update = update.timed_effect(timed_effect, duration);
+        }
+        if alert {
+            update = update.alert(AlertAction::Breathe);
+        }
+        if let (Some(signal), Some(duration)) = (signal, duration) {
+            update = update.signal(signal, duration, &signal_colors);
+        }
+        if update.is_empty() && !toggle {
+            return Err(HueError::Usage(String::from(
+                "Nothing to change. Use --on, --off, --toggle, --dim, --color, --ct, --brighter, --dimmer, --warmer, --cooler, --effect, --timed-effect, --alert or --signal",
+            )));
         }
-    } else {
-        Err(Box::new(HueError(
-            String::from("No Hue Bridge IP address provided."),
-            None,
-        )))
-    }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+        info!("Finding light with ID or name: {}", id_or_name);
 
-    #[test]
-    fn parse_api_response_errors_when_error_is_present() {
-        let response_body = serde_json::json!(
-        [
-            {
-                "error": {
-                    "type": 101,
-                    "address": "/",
-                    "description": "link button not pressed"
-                }
-            }
-        ]);
-        let errors = parse_api_response_errors(&response_body);
+        let light_id = client.find_light_by_id_or_name(id_or_name)?;
 
-        assert_eq!(errors.len(), 1);
-        assert_eq!(errors[0].type_value, 101);
-        assert_eq!(errors[0].address, "/");
-        assert_eq!(errors[0].description, "link button not pressed");
-        assert_eq!(
-            errors[0],
-            HueApiErrorMessage {
-                type_value: 101,
-                address: "/".to_string(),
-                description: "link button not pressed".to_string(),
+        let updated = match transition {
+            Some(duration) if fade => {
+                let on = turn_on.unwrap_or(true);
+                info!(
+                    "Fading light {} {} over {:?}",
+                    light_id.0,
+                    if on { "on" } else { "off" },
+                    duration
+                );
+                client.fade_light(&light_id, on, dimming_level, duration)?
             }
-        );
-    }
-
-    #[test]
-    fn parse_api_response_errors_when_no_error_is_present() {
-        let response_body = serde_json::json!(
-        [
-            {
-                "success": {
-                    "username": "1234567890"
+            _ => {
+                if toggle {
+                    update = update.on(!client.light_state(&light_id)?.on);
                 }
+                info!("Setting light {} to {}", light_id.0, update);
+                client.update_light(&light_id, &update)?
             }
-        ]);
-        let errors = parse_api_response_errors(&response_body);
-        assert_eq!(errors.len(), 0);
-    }
-
-    #[test]
-    fn parse_create_key_response_with_successful_operation() {
-        let response_body = serde_json::json!(
-        [
-            {
-                "success": {
-                    "username": "1234567890"
-                }
+        };
+        for resource in updated {
+            println!("Updated {} {}", resource.rtype, resource.rid);
+        }
+        println!("Light state updated successfully");
+        Ok(())
+    } else if let Some((group_type, group_matches)) = [GroupType::Room, GroupType::Zone]
+        .into_iter()
+        .find_map(|group_type| {
+            matches
+                .subcommand_matches(group_type.as_str())
+                .map(|group_matches| (group_type, group_matches))
+        })
+    {
+        if let Some(list_matches) = group_matches.subcommand_matches("list") {
+            let app_key = app_key(list_matches, &profile)?;
+            let client = builder.app_key(app_key).build()?;
+            let format = *list_matches.get_one::<OutputFormat>("output").unwrap();
+            info!("Requesting list of {}s on the Hue Bridge...", group_type);
+            let groups = client.list_groups(group_type)?;
+            let mut writer = RecordWriter::start(format, std::io::stdout());
+            for group in &groups {
+                writer.write(group);
             }
-        ]);
-        let actual = parse_create_key_response(&response_body);
-        assert_eq!(actual.is_ok(), true);
-        assert_eq!(
-            HueApiCreateKeySuccessDetails {
-                user_name: "1234567890".to_string()
-            },
-            actual.unwrap()
-        );
-    }
+            writer.finish();
+            return Ok(());
+        }
+
+        let app_key = app_key(group_matches, &profile)?;
+        let client = builder.app_key(app_key).build()?;
+        let id_or_name = group_matches.get_one::<String>("id").unwrap();
 
-    #[test]
-    fn parse_list_devices_response_with_successful_operation_light_device() {
-        let response_body = serde_json::json!(
-            {"errors": [],
-             "data": [
-                {
-                  "id": "94860050-1d86-4b79-8583-1be7dce05197",
-                  "id_v1": "/lights/2",
-                  "product_data": {
-                    "model_id": "123455987123",
-                    "manufacturer_name": "Signify Netherlands B.V.",
-                    "product_name": "Space Light",
-                    "product_archetype": "foo_bar",
-                    "certified": true,
-                    "software_version": "1.1.2",
-                    "hardware_platform_type": "100b-118"
-                  },
-                  "metadata": {
-                    "name": "Space light 1",
-                    "archetype": "foo_bar"
-                  },
-                  "identify": {},
-                  "services": [
-                    {
-                      "rid": "7d5545be-626a-4d63-a2f4-4347e43b50f6",
-                      "rtype": "zigbee_connectivity"
-                    },
-                    {
-                      "rid": "53ca6e61-5e40-4760-9e2e-6d2f48594901",
-                      "rtype": "light"
-                    },
-                    {
-                      "rid": "5dbe9888-a0b7-42d4-b002-9f15cd77e419",
-                      "rtype": "entertainment"
-                    },
-                    {
-                      "rid": "7c12995f-03bc-4b31-bb55-9da9e075dc0f",
-                      "rtype": "taurus_7455"
-                    },
-                    {
-                      "rid": "5b275c9c-dd12-45a8-9d36-716c43c1d3ed",
-                      "rtype": "device_software_update"
+        let mut update = LightUpdate::new();
+        match (group_matches.get_flag("on"), group_matches.get_flag("off")) {
+            (true, _) => update = update.on(true),
+            (_, true) => update = update.on(false),
+            _ => {}
+        }
+        if let Some(level) = group_matches.get_one::<u8>("dim") {
+            update = update.brightness(f32::from(*level));
+        }
+        // The parser limits the deltas to MAX_BRIGHTNESS_DELTA, which fits in i8.
+        if let Some(brighter) = group_matches.get_one::<u8>("brighter") {
+            update = update.brightness_delta(*brighter as i8);
+        }
+        if let Some(dimmer) = group_matches.get_one::<u8>("dimmer") {
+            update = update.brightness_delta(-(*dimmer as i8));
+        }
+        if let Some(color) = group_matches.get_one::<Color>("color") {
+            update = update.color(color.to_xy());
+        }
+        if let Some(temperature) = group_matches.get_one::<ColorTemperature>("ct") {
+            update = update.mirek(temperature.mirek());
+        }
+        if let Some(transition) = group_matches.get_one::<Duration>("transition") {
+            update = update.transition(*transition);
+        }
+        if update.is_empty() {
+            return Err(HueError::Usage(String::from(
+                "Nothing to change. Use --on, --off, --dim, --brighter, --dimmer, --color or --ct",
+            )));
+        }
+
+        info!("Finding {} with ID or name: {}", group_type, id_or_name);
+        let grouped_light_id = client.find_group_by_id_or_name(group_type, id_or_name)?;
+        info!("Setting {} {} to {}", group_type, grouped_light_id, update);
+        for resource in client.update_grouped_light(&grouped_light_id, &update)? {
+            println!("Updated {} {}", resource.rtype, resource.rid);
+        }
+        println!("All lights of the {} updated successfully", group_type);
+        Ok(())
+    } else if let Some(watch_matches) = matches.subcommand_matches("watch") {
+        let app_key = app_key(watch_matches, &profile)?;
+        let client = builder.app_key(app_key).build()?;
+        let types: Vec<&ResourceType> = watch_matches
+            .get_many::<ResourceType>("type")
+            .unwrap_or_default()
+            .collect();
+        let ids: Vec<&String> = watch_matches
+            .get_many::<String>("id")
+            .unwrap_or_default()
+            .collect();
+        let mut remaining = watch_matches.get_one::<u64>("count").copied();
+        let format = *watch_matches.get_one::<OutputFormat>("output").unwrap();
+
+        info!("Watching events on the Hue Bridge. Press Ctrl+C to stop.");
+        let mut writer = RecordWriter::start(format, std::io::stdout());
+        client.watch_events(|event| {
+            for resource in &event.data {
+                let type_matches = types.is_empty() || types.contains(&&resource.rtype);
+                let id_matches = ids.is_empty()
+                    || ids.contains(&&resource.id)
+                    || resource
+                        .owner
+                        .as_ref()
+                        .is_some_and(|owner| ids.contains(&&owner.rid));
+                if !(type_matches && id_matches) {
+                    continue;
+                }
+                writer.write(&EventRecord {
+                    creation_time: event.creation_time.clone(),
+                    event_type: event.event_type,
+                    rtype: resource.rtype.clone(),
+                    id: resource.id.clone(),
+                    owner: resource.owner.clone(),
+                    change: resource.change.clone(),
+                });
+                if let Some(n) = remaining.as_mut() {
+                    *n -= 1;
+                    if *n == 0 {
+                        return ControlFlow::Break(());
                     }
-                  ],
-                  "type": "device"
                 }
-                ]
-        }
+            }
+            ControlFlow::Continue(())
+        })?;
+        writer.finish();
+        Ok(())
+    } else if let Some((method, api_matches)) = matches
+        .subcommand_matches("api")
+        .and_then(ArgMatches::subcommand)
+    {
+        let app_key = app_key(api_matches, &profile)?;
+        let client = builder.app_key(app_key).build()?;
+        let path = api_path(api_matches.get_one::<String>("path").unwrap());
+        let body = api_matches
+            .try_get_one::<String>("body")
+            .ok()
+            .flatten()
+            .map(|body| api::read_body(body))
+            .transpose()?;
+        let response = match (method, body) {
+            ("get", _) => client.get_request(&path)?,
+            ("put", Some(body)) => client.put_request(&path, &body)?,
+            ("post", Some(body)) => client.post_request(&path, &body)?,
+            ("delete", _) => client.delete_request(&path)?,
+            _ => unreachable!("the api subcommands are get, put, post and delete"),
+        };
+        let response = match api_matches.get_one::<String>("filter") {
+            Some(filter) => api::select(response, filter)?,
+            None => response,
+        };
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&response).expect("JSON values can be serialized")
         );
-
-        let actual = parse_list_devices_response(&response_body);
-        assert_eq!(actual.is_ok(), true);
-        let ds = actual.unwrap();
-        assert_eq!(ds.len(), 1);
-        assert_eq!(
-            ds[0],
-            HueDevice(DeviceInfo {
-                id: "94860050-1d86-4b79-8583-1be7dce05197".to_string(),
-                name: "Space light 1".to_string(),
-                product_name: "Space Light".to_string(),
-                light_id: Some(LightId("53ca6e61-5e40-4760-9e2e-6d2f48594901".to_string())),
-            })
-        )
+        Ok(())
+    } else {
+        Err(HueError::Usage(String::from(
+            "No subcommand provided. Please provide a subcommand.",
+        )))
     }
 }
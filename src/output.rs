@@ -0,0 +1,402 @@
+//! Output formats for the records printed by the read commands.
+//!
+//! The table format is for people; JSON, JSON Lines, CSV and YAML are for
+//! tools like `jq` and spreadsheets. Records are written as they arrive, so
+//! `watch` can stream them.
+
+use clap::ValueEnum;
+use philips_hue_lab::color::{CieXy, ColorTemperature};
+use philips_hue_lab::discovery::DiscoveredBridge;
+use philips_hue_lab::events::{HueEventType, ResourceChange, ResourceRef};
+use philips_hue_lab::group::Group;
+use philips_hue_lab::light::LightState;
+use philips_hue_lab::resource::ResourceType;
+use philips_hue_lab::DeviceInfo;
+use serde::Serialize;
+use std::io::Write;
+use std::marker::PhantomData;
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// Aligned columns
+    Table,
+    /// A JSON array
+    Json,
+    /// One JSON object per line
+    Jsonl,
+    /// Comma-separated values with a header row
+    Csv,
+    /// A YAML list
+    Yaml,
+}
+
+/// A record that can be printed in all output formats.
+/// The JSON, JSON Lines and YAML formats use the serialized record.
+pub trait Record: Serialize {
+    /// The column headers and widths in the table format, also the CSV header.
+    const COLUMNS: &'static [(&'static str, usize)];
+
+    /// The values of the columns in the table and CSV formats.
+    fn cells(&self) -> Vec<String>;
+}
+
+/// Writes records in an output format, one at a time.
+/// Like `println!`, it panics if the output cannot be written.
+pub struct RecordWriter<T, W: Write> {
+    format: OutputFormat,
+    out: W,
+    count: usize,
+    record: PhantomData<T>,
+}
+
+impl<T: Record, W: Write> RecordWriter<T, W> {
+    /// Start the output, writing the header for the formats that have one.
+    pub fn start(format: OutputFormat, out: W) -> Self {
+        let mut writer = RecordWriter {
+            format,
+            out,
+            count: 0,
+            record: PhantomData,
+        };
+        let headers: Vec<String> = T::COLUMNS.iter().map(|(h, _)| h.to_string()).collect();
+        match format {
+            OutputFormat::Table => writer.write_table_row(&headers),
+            OutputFormat::Csv => writer.write_csv_row(&headers),
+            _ => {}
+        }
+        writer
+    }
+
+    pub fn write(&mut self, record: &T) {
+        match self.format {
+            OutputFormat::Table => self.write_table_row(&record.cells()),
+            OutputFormat::Csv => self.write_csv_row(&record.cells()),
+            OutputFormat::Json => {
+                // Indent the record like a pretty-printed array element.
+                let json = serde_json::to_string_pretty(record)
+                    .expect("record serializes to JSON")
+                    .replace('\n', "\n  ");
+                let separator = if self.count == 0 { "[" } else { "," };
+                write!(self.out, "{}\n  {}", separator, json).expect("failed printing to stdout");
+            }
+            OutputFormat::Jsonl => {
+                let json = serde_json::to_string(record).expect("record serializes to JSON");
+                writeln!(self.out, "{}", json).expect("failed printing to stdout");
+            }
+            OutputFormat::Yaml => {
+                let yaml = serde_yaml::to_string(&[record]).expect("record serializes to YAML");
+                write!(self.out, "{}", yaml).expect("failed printing to stdout");
+            }
+        }
+        self.count += 1;
+        self.out.flush().expect("failed printing to stdout");
+    }
+
+    /// End the output, closing the JSON array.
+    pub fn finish(mut self) {
+        let end = match (self.format, self.count) {
+            (OutputFormat::Json, 0) => "[]\n",
+            (OutputFormat::Json, _) => "\n]\n",
+            (OutputFormat::Yaml, 0) => "[]\n",
+            _ => "",
+        };
+        write!(self.out, "{}", end).expect("failed printing to stdout");
+        self.out.flush().expect("failed printing to stdout");
+    }
+
+    fn write_table_row(&mut self, cells: &[String]) {
+        let row: Vec<String> = cells
+            .iter()
+            .zip(T::COLUMNS)
+            .map(|(cell, (_, width))| format!("{:width$}", cell, width = width))
+            .collect();
+        writeln!(self.out, "{}", row.join(" | ")).expect("failed printing to stdout");
+    }
+
+    fn write_csv_row(&mut self, cells: &[String]) {
+        let mut csv = csv::Writer::from_writer(&mut self.out);
+        csv.write_record(cells).expect("failed printing to stdout");
+        csv.flush().expect("failed printing to stdout");
+    }
+}
+
+impl Record for DeviceInfo {
+    const COLUMNS: &'static [(&'static str, usize)] = &[
+        ("Device ID", 36),
+        ("Name", 30),
+        ("Product Name", 20),
+        ("Light ID", 36),
+        ("Services", 0),
+    ];
+
+    fn cells(&self) -> Vec<String> {
+        let mut service_types: Vec<&str> = Vec::new();
+        for service in &self.services {
+            if !service_types.contains(&service.rtype.as_str()) {
+                service_types.push(service.rtype.as_str());
+            }
+        }
+        vec![
+            self.id.clone(),
+            self.name.clone(),
+            self.product_data.product_name.clone(),
+            self.light_id.as_ref().map(String::from).unwrap_or_default(),
+            service_types.join(" "),
+        ]
+    }
+}
+
+impl Record for Group {
+    const COLUMNS: &'static [(&'static str, usize)] = &[
+        ("ID", 36),
+        ("Name", 24),
+        ("Archetype", 16),
+        ("Grouped Light ID", 36),
+        ("Children", 0),
+    ];
+
+    fn cells(&self) -> Vec<String> {
+        // Count the children by type, e.g. "2 device" for a room.
+        let mut counts: Vec<(&str, usize)> = Vec::new();
+        for child in &self.children {
+            match counts
+                .iter_mut()
+                .find(|(rtype, _)| *rtype == child.rtype.as_str())
+            {
+                Some((_, count)) => *count += 1,
+                None => counts.push((child.rtype.as_str(), 1)),
+            }
+        }
+        let children: Vec<String> = counts
+            .iter()
+            .map(|(rtype, count)| format!("{} {}", count, rtype))
+            .collect();
+        vec![
+            self.id.clone(),
+            self.name.clone(),
+            self.archetype.clone(),
+            self.grouped_light_id
+                .as_ref()
+                .map(String::from)
+                .unwrap_or_default(),
+            children.join(", "),
+        ]
+    }
+}
+
+impl Record for DiscoveredBridge {
+    const COLUMNS: &'static [(&'static str, usize)] = &[
+        ("Bridge ID", 16),
+        ("Name", 30),
+        ("Address", 40),
+        ("Model", 10),
+    ];
+
+    fn cells(&self) -> Vec<String> {
+        vec![
+            self.id.clone(),
+            self.name.clone(),
+            self.bridge_ip().map(|ip| ip.0).unwrap_or_default(),
+            self.model_id.clone().unwrap_or_default(),
+        ]
+    }
+}
+
+/// A changed resource from the event stream, flattened with its event.
+#[derive(Serialize, Debug)]
+pub struct EventRecord {
+    pub creation_time: String,
+    pub event_type: HueEventType,
+    pub rtype: ResourceType,
+    pub id: String,
+    pub owner: Option<ResourceRef>,
+    pub change: ResourceChange,
+}
+
+impl Record for EventRecord {
+    const COLUMNS: &'static [(&'static str, usize)] = &[
+        ("Time", 20),
+        ("Event", 6),
+        ("Type", 20),
+        ("ID", 36),
+        ("Change", 0),
+    ];
+
+    fn cells(&self) -> Vec<String> {
+        vec![
+            self.creation_time.clone(),
+            self.event_type.to_string(),
+            self.rtype.to_string(),
+            self.id.clone(),
+            self.change.to_string(),
+        ]
+    }
+}
+
+/// The state of a light, with a swatch of its color in the table format.
+#[derive(Serialize, Debug)]
+pub struct LightStatusRecord {
+    #[serde(flatten)]
+    pub state: LightState,
+    /// Show the current color as a true-color swatch, for terminals.
+    #[serde(skip)]
+    pub swatch: bool,
+}
+
+impl Record for LightStatusRecord {
+    const COLUMNS: &'static [(&'static str, usize)] = &[
+        ("Light ID", 36),
+        ("Name", 24),
+        ("On", 3),
+        ("Brightness", 10),
+        ("Effect", 10),
+        ("Mode", 9),
+        ("Color", 0),
+    ];
+
+    fn cells(&self) -> Vec<String> {
+        let state = &self.state;
+        let mirek = state
+            .color_temperature
+            .as_ref()
+            .and_then(|color_temperature| color_temperature.mirek);
+        let color = match (state.current_xy(), mirek) {
+            (Some(_), Some(mirek)) => format!(
+                "{}K ({} mirek)",
+                ColorTemperature::Mirek(mirek).kelvin(),
+                mirek
+            ),
+            (Some(xy), None) => format!("{} {}", xy.to_hex(), xy),
+            (None, _) => String::new(),
+        };
+        let color = match (self.swatch, state.current_xy()) {
+            (true, Some(xy)) => format!("{} {}", true_color_swatch(xy), color),
+            _ => color,
+        };
+        vec![
+            String::from(&state.id),
+            state.name.clone(),
+            String::from(if state.on { "on" } else { "off" }),
+            state
+                .brightness
+                .map(|brightness| format!("{:.0}%", brightness))
+                .unwrap_or_default(),
+            state.effect.clone().unwrap_or_default(),
+            state.mode.clone(),
+            color,
+        ]
+    }
+}
+
+/// A block in the color, using the 24-bit color escape codes of the terminal.
+fn true_color_swatch(xy: CieXy) -> String {
+    let (red, green, blue) = xy.to_rgb(1.0);
+    format!("\x1b[48;2;{};{};{}m    \x1b[0m", red, green, blue)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use philips_hue_lab::{LightId, ProductData};
+
+    fn device(id: &str, name: &str, product_name: &str, services: &[(&str, &str)]) -> DeviceInfo {
+        let services: Vec<ResourceRef> = services
+            .iter()
+            .map(|(rtype, rid)| ResourceRef {
+                rid: rid.to_string(),
+                rtype: ResourceType::from(*rtype),
+            })
+            .collect();
+        DeviceInfo {
+            id: id.to_string(),
+            id_v1: None,
+            name: name.to_string(),
+            archetype: String::from("sultan_bulb"),
+            product_data: ProductData {
+                model_id: String::from("LCA001"),
+                manufacturer_name: String::from("Signify Netherlands B.V."),
+                product_name: product_name.to_string(),
+                product_archetype: String::from("sultan_bulb"),
+                certified: true,
+                software_version: String::from("1.104.2"),
+                hardware_platform_type: None,
+            },
+            identify: true,
+            light_id: services
+                .iter()
+                .find(|s| s.rtype == ResourceType::Light)
+                .map(|s| LightId(s.rid.clone())),
+            services,
+        }
+    }
+
+    fn devices() -> Vec<DeviceInfo> {
+        vec![
+            device(
+                "device-1",
+                "Kitchen, left",
+                "Hue color lamp",
+                &[("light", "light-1"), ("entertainment", "ent-1")],
+            ),
+            device(
+                "device-2",
+                "Hallway sensor",
+                "Hue motion sensor",
+                &[("motion", "motion-1"), ("device_power", "power-1")],
+            ),
+        ]
+    }
+
+    fn render(format: OutputFormat, records: &[DeviceInfo]) -> String {
+        let mut out = Vec::new();
+        let mut writer = RecordWriter::start(format, &mut out);
+        for record in records {
+            writer.write(record);
+        }
+        writer.finish();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn json_output_is_an_array() {
+        let json = render(OutputFormat::Json, &devices());
+        assert_eq!(
+            json,
+            format!("{}\n", serde_json::to_string_pretty(&devices()).unwrap())
+        );
+        assert_eq!(render(OutputFormat::Json, &[]), "[]\n");
+    }
+
+    #[test]
+    fn line_formats_have_one_record_per_line() {
+        let jsonl = render(OutputFormat::Jsonl, &devices());
+        let lines: Vec<serde_json::Value> = jsonl
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[1]["light_id"], serde_json::Value::Null);
+
+        let csv = render(OutputFormat::Csv, &devices());
+        assert_eq!(
+            csv,
+            "Device ID,Name,Product Name,Light ID,Services\n\
+             device-1,\"Kitchen, left\",Hue color lamp,light-1,light entertainment\n\
+             device-2,Hallway sensor,Hue motion sensor,,motion device_power\n"
+        );
+    }
+
+    #[test]
+    fn yaml_output_is_a_list() {
+        let yaml = render(OutputFormat::Yaml, &devices());
+        let parsed: Vec<serde_json::Value> = serde_yaml::from_str(&yaml).unwrap();
+        assert_eq!(
+            parsed,
+            serde_json::to_value(devices())
+                .unwrap()
+                .as_array()
+                .unwrap()
+                .clone()
+        );
+    }
+}
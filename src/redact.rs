@@ -0,0 +1,83 @@
+//! Redaction of application keys and client keys from logged output.
+
+use serde_json::Value;
+
+const REDACTED: &str = "<redacted>";
+
+/// Headers and JSON fields holding application keys or client keys.
+const SECRET_NAMES: [&str; 3] = ["hue-application-key", "username", "clientkey"];
+
+/// The value of a header, or a placeholder if it holds a key.
+pub(crate) fn header(name: &str, value: &[u8]) -> String {
+    match SECRET_NAMES.contains(&name.to_lowercase().as_str()) {
+        true => String::from(REDACTED),
+        false => String::from_utf8_lossy(value).to_string(),
+    }
+}
+
+/// Text with every occurrence of the secrets replaced, e.g. a URL with the key in the path.
+pub(crate) fn text(text: &str, secrets: &[&str]) -> String {
+    secrets
+        .iter()
+        .filter(|secret| !secret.is_empty())
+        .fold(text.to_string(), |text, secret| {
+            text.replace(secret, REDACTED)
+        })
+}
+
+/// A request or response body with the key fields of a JSON body and any
+/// other occurrences of the secrets replaced.
+pub(crate) fn body(body: &str, secrets: &[&str]) -> String {
+    match serde_json::from_str::<Value>(body) {
+        Ok(mut json) => {
+            redact_fields(&mut json);
+            text(&json.to_string(), secrets)
+        }
+        Err(_) => text(body, secrets),
+    }
+}
+
+fn redact_fields(value: &mut Value) {
+    match value {
+        Value::Object(object) => {
+            for (name, value) in object.iter_mut() {
+                match SECRET_NAMES.contains(&name.as_str()) && value.is_string() {
+                    true => *value = Value::String(String::from(REDACTED)),
+                    false => redact_fields(value),
+                }
+            }
+        }
+        Value::Array(elements) => elements.iter_mut().for_each(redact_fields),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn body_redacts_key_fields_and_secrets() {
+        let response =
+            r#"[{"success": {"username": "app-key-1", "clientkey": "0123456789ABCDEF"}}]"#;
+        let redacted = body(response, &[]);
+        assert!(!redacted.contains("app-key-1"));
+        assert!(!redacted.contains("0123456789ABCDEF"));
+        assert!(redacted.contains(r#""username":"<redacted>""#));
+
+        assert_eq!(
+            body("unauthorized user app-key-1", &["app-key-1"]),
+            "unauthorized user <redacted>"
+        );
+    }
+
+    #[test]
+    fn header_redacts_application_key() {
+        assert_eq!(header("hue-application-key", b"app-key-1"), REDACTED);
+        assert_eq!(header("accept", b"application/json"), "application/json");
+        assert_eq!(
+            text("https://192.168.1.2/api/app-key-1/config", &["app-key-1"]),
+            "https://192.168.1.2/api/<redacted>/config"
+        );
+    }
+}
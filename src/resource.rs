@@ -0,0 +1,187 @@
+//! The CLIP v2 resource types and typed resource IDs.
+//!
+//! Every resource on the bridge has a type, e.g. `light` or `motion`, and an ID.
+//! Devices reference their services, and services their owner device, with
+//! a [`ResourceRef`](crate::events::ResourceRef) of the two.
+//! See documentation at <https://developers.meethue.com/develop/hue-api-v2/api-reference/>
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::convert::Infallible;
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+/// A typed resource ID, e.g. a [`LightId`].
+pub trait ResourceId {
+    /// The type of the resources with this kind of ID.
+    const RESOURCE_TYPE: ResourceType;
+
+    fn as_str(&self) -> &str;
+}
+
+macro_rules! resource_types {
+    ($($variant:ident => $name:literal, $id:ident;)*) => {
+        /// The type of a CLIP v2 resource.
+        ///
+        /// Types added by newer bridge firmware are kept as [`ResourceType::Other`].
+        #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+        pub enum ResourceType {
+            $(
+                #[doc = concat!("`", $name, "`")]
+                $variant,
+            )*
+            /// A resource type this library does not know.
+            Other(String),
+        }
+
+        impl ResourceType {
+            /// All the known resource types.
+            pub const ALL: &'static [ResourceType] = &[$(ResourceType::$variant),*];
+
+            /// The name of the type in the API, e.g. `grouped_light`.
+            pub fn as_str(&self) -> &str {
+                match self {
+                    $(ResourceType::$variant => $name,)*
+                    ResourceType::Other(name) => name,
+                }
+            }
+        }
+
+        impl FromStr for ResourceType {
+            type Err = Infallible;
+
+            fn from_str(name: &str) -> Result<Self, Self::Err> {
+                Ok(match name {
+                    $($name => ResourceType::$variant,)*
+                    other => ResourceType::Other(other.to_string()),
+                })
+            }
+        }
+
+        $(
+            #[doc = concat!("The ID of a `", $name, "` resource.")]
+            #[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
+            pub struct $id(pub String);
+
+            impl ResourceId for $id {
+                const RESOURCE_TYPE: ResourceType = ResourceType::$variant;
+
+                fn as_str(&self) -> &str {
+                    &self.0
+                }
+            }
+
+            impl From<&$id> for String {
+                fn from(id: &$id) -> Self {
+                    id.0.clone()
+                }
+            }
+
+            impl Display for $id {
+                fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+                    f.write_str(&self.0)
+                }
+            }
+        )*
+    };
+}
+
+resource_types! {
+    Device => "device", DeviceId;
+    Bridge => "bridge", BridgeResourceId;
+    BridgeHome => "bridge_home", BridgeHomeId;
+    Room => "room", RoomId;
+    Zone => "zone", ZoneId;
+    Light => "light", LightId;
+    GroupedLight => "grouped_light", GroupedLightId;
+    Scene => "scene", SceneId;
+    SmartScene => "smart_scene", SmartSceneId;
+    Motion => "motion", MotionId;
+    CameraMotion => "camera_motion", CameraMotionId;
+    GroupedMotion => "grouped_motion", GroupedMotionId;
+    ConvenienceAreaMotion => "convenience_area_motion", ConvenienceAreaMotionId;
+    SecurityAreaMotion => "security_area_motion", SecurityAreaMotionId;
+    Temperature => "temperature", TemperatureId;
+    LightLevel => "light_level", LightLevelId;
+    GroupedLightLevel => "grouped_light_level", GroupedLightLevelId;
+    Button => "button", ButtonId;
+    RelativeRotary => "relative_rotary", RelativeRotaryId;
+    Contact => "contact", ContactId;
+    Tamper => "tamper", TamperId;
+    DevicePower => "device_power", DevicePowerId;
+    DeviceSoftwareUpdate => "device_software_update", DeviceSoftwareUpdateId;
+    ZigbeeConnectivity => "zigbee_connectivity", ZigbeeConnectivityId;
+    ZgpConnectivity => "zgp_connectivity", ZgpConnectivityId;
+    ZigbeeDeviceDiscovery => "zigbee_device_discovery", ZigbeeDeviceDiscoveryId;
+    Entertainment => "entertainment", EntertainmentId;
+    EntertainmentConfiguration => "entertainment_configuration", EntertainmentConfigurationId;
+    BehaviorScript => "behavior_script", BehaviorScriptId;
+    BehaviorInstance => "behavior_instance", BehaviorInstanceId;
+    Geofence => "geofence", GeofenceId;
+    GeofenceClient => "geofence_client", GeofenceClientId;
+    Geolocation => "geolocation", GeolocationId;
+    Homekit => "homekit", HomekitId;
+    Matter => "matter", MatterId;
+    MatterFabric => "matter_fabric", MatterFabricId;
+    MotionAreaCandidate => "motion_area_candidate", MotionAreaCandidateId;
+    MotionAreaConfiguration => "motion_area_configuration", MotionAreaConfigurationId;
+    PublicImage => "public_image", PublicImageId;
+    ServiceGroup => "service_group", ServiceGroupId;
+    AuthV1 => "auth_v1", AuthV1Id;
+}
+
+impl Display for ResourceType {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl From<&str> for ResourceType {
+    fn from(name: &str) -> Self {
+        match name.parse() {
+            Ok(rtype) => rtype,
+            Err(never) => match never {},
+        }
+    }
+}
+
+impl Serialize for ResourceType {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for ResourceType {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Ok(ResourceType::from(
+            String::deserialize(deserializer)?.as_str(),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resource_types_round_trip_through_their_names() {
+        for rtype in ResourceType::ALL {
+            assert_eq!(&ResourceType::from(rtype.as_str()), rtype);
+        }
+        assert_eq!(
+            ResourceType::from("grouped_light"),
+            ResourceType::GroupedLight
+        );
+        assert_eq!(
+            ResourceType::from("taurus_7455"),
+            ResourceType::Other(String::from("taurus_7455"))
+        );
+        assert_eq!(
+            serde_json::to_value(ResourceType::DevicePower).unwrap(),
+            serde_json::json!("device_power")
+        );
+        assert_eq!(
+            serde_json::from_value::<ResourceType>(serde_json::json!("taurus_7455")).unwrap(),
+            ResourceType::Other(String::from("taurus_7455"))
+        );
+    }
+}
@@ -0,0 +1,334 @@
+//! Verification of the Hue Bridge certificate.
+//!
+//! The bridge presents a certificate signed by the Signify Hue root CA, with
+//! the bridge ID as the common name. We connect to the bridge by IP address,
+//! so instead of the usual host name check the common name is compared with
+//! the bridge ID. Older bridges have self-signed certificates; for them the
+//! certificate can be pinned by its fingerprint on first use.
+//! See documentation at <https://developers.meethue.com/develop/application-design-guidance/using-https/>
+
+use crate::{BridgeIp, HueError};
+use reqwest::Url;
+use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+use rustls::client::verify_server_cert_signed_by_trust_anchor;
+use rustls::crypto::{verify_tls12_signature, verify_tls13_signature, CryptoProvider};
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::{CertificateDer, ServerName, UnixTime};
+use rustls::server::ParsedCertificate;
+use rustls::{
+    CertificateError, ClientConfig, DigitallySignedStruct, RootCertStore, SignatureScheme,
+};
+use std::fmt::{Display, Formatter};
+use std::net::TcpStream;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// The Hue Bridge root CA.
+const HUE_ROOT_CA: &str = include_str!("../resources/huebridge_cacert.pem");
+
+/// The SHA-256 fingerprint of a certificate, as lowercase hex.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CertificateFingerprint(String);
+
+impl CertificateFingerprint {
+    /// The fingerprint of a DER-encoded certificate.
+    pub fn of(der: &[u8]) -> Self {
+        let digest = ring::digest::digest(&ring::digest::SHA256, der);
+        CertificateFingerprint(
+            digest
+                .as_ref()
+                .iter()
+                .map(|byte| format!("{:02x}", byte))
+                .collect(),
+        )
+    }
+
+    /// Parse a fingerprint in hex, optionally with colons between the bytes.
+    pub fn parse(text: &str) -> Result<Self, HueError> {
+        let hex: String = text.chars().filter(|c| *c != ':').collect();
+        match hex.len() == 64 && hex.chars().all(|c| c.is_ascii_hexdigit()) {
+            true => Ok(CertificateFingerprint(hex.to_lowercase())),
+            false => Err(HueError::Usage(format!(
+                "Invalid certificate fingerprint '{}'. Expected a SHA-256 fingerprint in hex.",
+                text
+            ))),
+        }
+    }
+}
+
+impl Display for CertificateFingerprint {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// Parse the certificates in a PEM file.
+pub(crate) fn parse_pem_certificates(pem: &[u8]) -> Result<Vec<CertificateDer<'static>>, HueError> {
+    let certificates = CertificateDer::pem_slice_iter(pem)
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| HueError::Tls(Box::new(e)))?;
+    match certificates.is_empty() {
+        true => Err(HueError::Usage(String::from(
+            "No certificates found in the PEM data.",
+        ))),
+        false => Ok(certificates),
+    }
+}
+
+/// The bridge ID shared between a verifier and its client.
+pub(crate) type SharedBridgeId = Arc<Mutex<Option<String>>>;
+
+/// How the bridge certificate is checked.
+#[derive(Debug)]
+enum Check {
+    /// The certificate chain must lead to one of the root CAs, and the
+    /// common name must be the bridge ID. If the bridge ID is not known, it
+    /// is taken from the first certificate.
+    RootCa {
+        roots: RootCertStore,
+        bridge_id: SharedBridgeId,
+    },
+    /// The certificate must have this fingerprint.
+    Pinned(CertificateFingerprint),
+    /// Any certificate is accepted, and its fingerprint recorded.
+    Record(Arc<Mutex<Option<CertificateFingerprint>>>),
+}
+
+#[derive(Debug)]
+pub(crate) struct BridgeCertificateVerifier {
+    check: Check,
+    provider: Arc<CryptoProvider>,
+}
+
+impl BridgeCertificateVerifier {
+    /// Verify against the Hue root CA and the extra root certificates.
+    /// The returned handle holds the bridge ID that the common name must match.
+    pub(crate) fn root_ca(
+        extra_roots: &[CertificateDer<'static>],
+    ) -> Result<(Self, SharedBridgeId), HueError> {
+        let mut roots = RootCertStore::empty();
+        for certificate in parse_pem_certificates(HUE_ROOT_CA.as_bytes())?
+            .into_iter()
+            .chain(extra_roots.iter().cloned())
+        {
+            roots
+                .add(certificate)
+                .map_err(|e| HueError::Tls(Box::new(e)))?;
+        }
+        let bridge_id = Arc::new(Mutex::new(None));
+        let verifier = BridgeCertificateVerifier {
+            check: Check::RootCa {
+                roots,
+                bridge_id: bridge_id.clone(),
+            },
+            provider: crypto_provider(),
+        };
+        Ok((verifier, bridge_id))
+    }
+
+    pub(crate) fn pinned(fingerprint: CertificateFingerprint) -> Self {
+        BridgeCertificateVerifier {
+            check: Check::Pinned(fingerprint),
+            provider: crypto_provider(),
+        }
+    }
+
+    /// The rustls configuration for connections verified by this verifier.
+    pub(crate) fn into_client_config(self) -> ClientConfig {
+        let provider = self.provider.clone();
+        ClientConfig::builder_with_provider(provider)
+            .with_safe_default_protocol_versions()
+            .expect("the ring provider supports the default protocol versions")
+            .dangerous()
+            .with_custom_certificate_verifier(Arc::new(self))
+            .with_no_client_auth()
+    }
+}
+
+fn crypto_provider() -> Arc<CryptoProvider> {
+    Arc::new(rustls::crypto::ring::default_provider())
+}
+
+impl ServerCertVerifier for BridgeCertificateVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        intermediates: &[CertificateDer<'_>],
+        _server_name: &ServerName<'_>,
+        _ocsp_response: &[u8],
+        now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        match &self.check {
+            Check::RootCa { roots, bridge_id } => {
+                let certificate = ParsedCertificate::try_from(end_entity)?;
+                verify_server_cert_signed_by_trust_anchor(
+                    &certificate,
+                    roots,
+                    intermediates,
+                    now,
+                    self.provider.signature_verification_algorithms.all,
+                )?;
+                let common_name = common_name(end_entity).ok_or(
+                    rustls::Error::InvalidCertificate(CertificateError::BadEncoding),
+                )?;
+                let mut bridge_id = bridge_id.lock().unwrap();
+                match bridge_id.as_deref() {
+                    Some(id) if !common_name.eq_ignore_ascii_case(id) => {
+                        log::debug!(
+                            "Certificate common name {} does not match bridge ID {}",
+                            common_name,
+                            id
+                        );
+                        return Err(rustls::Error::InvalidCertificate(
+                            CertificateError::NotValidForName,
+                        ));
+                    }
+                    Some(_) => {}
+                    // Later connections must be to the same bridge. The client
+                    // checks the name against the bridge configuration.
+                    None => *bridge_id = Some(common_name),
+                }
+            }
+            Check::Pinned(fingerprint) => {
+                if CertificateFingerprint::of(end_entity) != *fingerprint {
+                    return Err(rustls::Error::InvalidCertificate(
+                        CertificateError::ApplicationVerificationFailure,
+                    ));
+                }
+            }
+            Check::Record(fingerprint) => {
+                *fingerprint.lock().unwrap() = Some(CertificateFingerprint::of(end_entity));
+            }
+        }
+        Ok(ServerCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        verify_tls12_signature(
+            message,
+            cert,
+            dss,
+            &self.provider.signature_verification_algorithms,
+        )
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        verify_tls13_signature(
+            message,
+            cert,
+            dss,
+            &self.provider.signature_verification_algorithms,
+        )
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.provider
+            .signature_verification_algorithms
+            .supported_schemes()
+    }
+}
+
+/// The subject common name of a DER-encoded certificate.
+fn common_name(der: &[u8]) -> Option<String> {
+    let (_, certificate) = x509_parser::parse_x509_certificate(der).ok()?;
+    let name = certificate
+        .subject()
+        .iter_common_name()
+        .next()?
+        .as_str()
+        .ok()?
+        .to_string();
+    Some(name)
+}
+
+/// Connect to the bridge and return the fingerprint of its certificate, without verifying it.
+///
+/// This is for trust on first use: pin the fingerprint with
+/// [`HueClientBuilder::pinned_certificate`](crate::HueClientBuilder::pinned_certificate)
+/// to only accept this certificate from then on.
+pub fn fetch_certificate_fingerprint(
+    bridge_ip: &BridgeIp,
+    timeout: Duration,
+) -> Result<CertificateFingerprint, HueError> {
+    let url = Url::parse(&format!("https://{}/", bridge_ip.0))
+        .map_err(|e| HueError::Usage(format!("Invalid bridge address '{}': {}", bridge_ip.0, e)))?;
+    let host = url.host_str().unwrap_or_default().trim_matches(['[', ']']);
+    let server_name = ServerName::try_from(host.to_string())
+        .map_err(|e| HueError::Usage(format!("Invalid bridge address '{}': {}", bridge_ip.0, e)))?;
+    let address = url
+        .socket_addrs(|| Some(443))
+        .map_err(|e| HueError::Transport(Box::new(e)))?
+        .into_iter()
+        .next()
+        .ok_or_else(|| {
+            HueError::Usage(format!(
+                "Could not resolve bridge address '{}'",
+                bridge_ip.0
+            ))
+        })?;
+
+    let recorded = Arc::new(Mutex::new(None));
+    let verifier = BridgeCertificateVerifier {
+        check: Check::Record(recorded.clone()),
+        provider: crypto_provider(),
+    };
+    let mut connection =
+        rustls::ClientConnection::new(Arc::new(verifier.into_client_config()), server_name)
+            .map_err(|e| HueError::Tls(Box::new(e)))?;
+    let mut stream = TcpStream::connect_timeout(&address, timeout)
+        .map_err(|e| HueError::Transport(Box::new(e)))?;
+    stream
+        .set_read_timeout(Some(timeout))
+        .and_then(|_| stream.set_write_timeout(Some(timeout)))
+        .map_err(|e| HueError::Transport(Box::new(e)))?;
+    while connection.is_handshaking() {
+        connection
+            .complete_io(&mut stream)
+            .map_err(|e| HueError::Tls(Box::new(e)))?;
+    }
+    let fingerprint = recorded.lock().unwrap().take();
+    fingerprint
+        .ok_or_else(|| HueError::Tls(Box::from("The Hue Bridge did not present a certificate")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fingerprint_parse_accepts_colons_and_upper_case() {
+        let fingerprint = CertificateFingerprint::of(b"certificate");
+        let with_colons: Vec<String> = fingerprint
+            .to_string()
+            .to_uppercase()
+            .as_bytes()
+            .chunks(2)
+            .map(|pair| String::from_utf8_lossy(pair).to_string())
+            .collect();
+        assert_eq!(
+            CertificateFingerprint::parse(&with_colons.join(":")).unwrap(),
+            fingerprint
+        );
+        assert!(CertificateFingerprint::parse("00:11").is_err());
+    }
+
+    #[test]
+    fn hue_root_ca_is_parsed() {
+        let certificates = parse_pem_certificates(HUE_ROOT_CA.as_bytes()).unwrap();
+        assert_eq!(certificates.len(), 1);
+        assert_eq!(
+            common_name(&certificates[0]).as_deref(),
+            Some("root-bridge")
+        );
+    }
+}
@@ -0,0 +1,145 @@
+//! Transitions between light states.
+//!
+//! The bridge fades a light to its new state over the `dynamics.duration` of
+//! the request, up to [`MAX_TRANSITION`]. Longer fades are done by the client
+//! in steps, each a request with its own transition, sent no faster than the
+//! bridge accepts them.
+//! See documentation at <https://developers.meethue.com/develop/hue-api-v2/core-concepts/#limitations>
+
+use crate::HueError;
+use std::time::Duration;
+
+/// The longest transition the bridge accepts in `dynamics.duration`.
+pub const MAX_TRANSITION: Duration = Duration::from_millis(6_000_000);
+
+/// The longest fade the client does in steps.
+pub const MAX_FADE: Duration = Duration::from_secs(24 * 3600);
+
+/// The shortest time between requests to a light, as the bridge handles about 10 per second.
+pub const MIN_STEP_INTERVAL: Duration = Duration::from_millis(100);
+
+/// The length of each step of a client-side fade.
+pub(crate) const FADE_STEP: Duration = Duration::from_secs(60);
+
+/// How long to wait before retrying a step the bridge rejected with HTTP 429.
+pub(crate) const RATE_LIMIT_BACKOFF: Duration = Duration::from_secs(1);
+
+/// How many times to retry a rate limited step.
+pub(crate) const RATE_LIMIT_RETRIES: u32 = 3;
+
+/// Parse a duration with a unit: `500ms`, `10s`, `1.5s`, `5m` or `2h`.
+pub fn parse_duration(text: &str) -> Result<Duration, HueError> {
+    let invalid = || {
+        HueError::Usage(format!(
+            "Invalid duration '{}'. Use a number with a unit, e.g. 500ms, 10s, 5m or 2h.",
+            text
+        ))
+    };
+    let trimmed = text.trim();
+    let split = trimmed
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .ok_or_else(invalid)?;
+    let (number, unit) = trimmed.split_at(split);
+    let number = number.parse::<f64>().map_err(|_| invalid())?;
+    let seconds = match unit.trim() {
+        "ms" => number / 1000.0,
+        "s" => number,
+        "m" | "min" => number * 60.0,
+        "h" => number * 3600.0,
+        _ => return Err(invalid()),
+    };
+    Duration::try_from_secs_f64(seconds).map_err(|_| invalid())
+}
+
+/// The `dynamics.duration` of a request, in milliseconds.
+pub(crate) fn transition_millis(transition: Duration) -> Result<u32, HueError> {
+    match u32::try_from(transition.as_millis()) {
+        Ok(millis) if transition <= MAX_TRANSITION => Ok(millis),
+        _ => Err(HueError::InvalidParameter(format!(
+            "The transition of {:?} is longer than the bridge allows ({} minutes). Use a fade instead.",
+            transition,
+            MAX_TRANSITION.as_secs() / 60
+        ))),
+    }
+}
+
+/// The durations of the steps of a fade, each at least `step` and
+/// [`MIN_STEP_INTERVAL`] but shorter than twice that, except that a shorter
+/// fade is a single step. Fades are limited to [`MAX_FADE`].
+pub(crate) fn fade_steps(total: Duration, step: Duration) -> Result<Vec<Duration>, HueError> {
+    let too_long = || {
+        HueError::InvalidParameter(format!(
+            "The fade of {:?} is longer than the longest fade ({} hours)",
+            total,
+            MAX_FADE.as_secs() / 3600
+        ))
+    };
+    if total > MAX_FADE {
+        return Err(too_long());
+    }
+    let step = step.max(MIN_STEP_INTERVAL);
+    let count =
+        u32::try_from((total.as_nanos() / step.as_nanos()).max(1)).map_err(|_| too_long())?;
+    let length = total / count;
+    // The last step takes up the rounding, so the steps add up to the total.
+    let mut steps = vec![length; count as usize];
+    steps[count as usize - 1] = total - length * (count - 1);
+    Ok(steps)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_duration_accepts_units() {
+        assert_eq!(parse_duration("500ms").unwrap(), Duration::from_millis(500));
+        assert_eq!(parse_duration("10s").unwrap(), Duration::from_secs(10));
+        assert_eq!(parse_duration("1.5s").unwrap(), Duration::from_millis(1500));
+        assert_eq!(parse_duration("5m").unwrap(), Duration::from_secs(300));
+        assert_eq!(parse_duration("2h").unwrap(), Duration::from_secs(7200));
+        for invalid in ["10", "s", "10 days", "-1s", "1..5s"] {
+            assert!(parse_duration(invalid).is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn transition_millis_is_limited_by_the_bridge() {
+        assert_eq!(transition_millis(Duration::from_secs(10)).unwrap(), 10_000);
+        assert_eq!(transition_millis(MAX_TRANSITION).unwrap(), 6_000_000);
+        for transition in [Duration::from_secs(3 * 3600), Duration::MAX] {
+            assert!(matches!(
+                transition_millis(transition),
+                Err(HueError::InvalidParameter(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn fade_steps_add_up_and_respect_the_rate_limit() {
+        let steps = fade_steps(Duration::from_secs(3 * 3600), FADE_STEP).unwrap();
+        assert_eq!(steps.len(), 180);
+        assert!(steps.iter().all(|step| *step == FADE_STEP));
+
+        let steps = fade_steps(Duration::from_millis(1050), Duration::from_millis(10)).unwrap();
+        assert_eq!(steps.len(), 10);
+        assert!(steps.iter().all(|step| *step >= MIN_STEP_INTERVAL));
+        assert_eq!(steps.iter().sum::<Duration>(), Duration::from_millis(1050));
+
+        assert_eq!(
+            fade_steps(Duration::from_millis(30), FADE_STEP).unwrap(),
+            vec![Duration::from_millis(30)]
+        );
+    }
+
+    #[test]
+    fn fade_steps_reject_fades_longer_than_the_limit() {
+        assert_eq!(fade_steps(MAX_FADE, FADE_STEP).unwrap().len(), 24 * 60);
+        for total in [MAX_FADE + Duration::from_secs(1), Duration::MAX] {
+            assert!(matches!(
+                fade_steps(total, MIN_STEP_INTERVAL),
+                Err(HueError::InvalidParameter(_))
+            ));
+        }
+    }
+}
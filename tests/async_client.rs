@@ -0,0 +1,91 @@
+#![cfg(feature = "async")]
+
+mod mock_bridge;
+
+use mock_bridge::*;
+use philips_hue_lab::events::HueEvent;
+use philips_hue_lab::{AppKey, AsyncHueClient, BridgeIp, HueClient, HueError, LightId};
+use serde_json::json;
+use std::ops::ControlFlow;
+
+async fn client(bridge: &MockBridge) -> AsyncHueClient {
+    HueClient::builder(BridgeIp(bridge.address()))
+        .root_certificate_pem(bridge.ca_certificate_pem().as_bytes())
+        .app_key(AppKey(String::from(MOCK_APP_KEY)))
+        .build_async()
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn list_devices_returns_light_ids() {
+    let bridge = MockBridge::start();
+    let devices = client(&bridge).await.list_devices().await.unwrap();
+    let kitchen = devices
+        .iter()
+        .find(|d| d.0.id == KITCHEN_DEVICE_ID)
+        .unwrap();
+    assert_eq!(
+        kitchen.0.light_id,
+        Some(LightId(KITCHEN_LIGHT_ID.to_string()))
+    );
+}
+
+#[tokio::test]
+async fn control_light_puts_new_state_on_one_connection() {
+    let bridge = MockBridge::start();
+    let client = client(&bridge).await;
+    let light_id = client.find_light_by_id_or_name("desk").await.unwrap();
+    client
+        .control_light(&light_id, true, Some(25), None)
+        .await
+        .unwrap();
+
+    let path = format!("/clip/v2/resource/light/{}", DESK_LIGHT_ID);
+    assert_eq!(
+        bridge.requests_to("PUT", &path)[0].json(),
+        json!({"on": {"on": true}, "dimming": {"brightness": 25.0}})
+    );
+    let requests = bridge.requests();
+    assert!(requests
+        .iter()
+        .all(|r| r.connection == requests[0].connection));
+}
+
+#[tokio::test]
+async fn control_light_fails_on_errors_in_response() {
+    let bridge = MockBridge::start();
+    let err = client(&bridge)
+        .await
+        .control_light(&LightId(PLUG_LIGHT_ID.to_string()), true, Some(25), None)
+        .await
+        .unwrap_err();
+    assert!(matches!(err, HueError::Clip(_)));
+}
+
+#[tokio::test]
+async fn event_stream_yields_typed_events() {
+    let bridge = MockBridge::start();
+    bridge.state().events = vec![
+        update_event(json!({"id": KITCHEN_LIGHT_ID, "on": {"on": true}, "type": "light"})),
+        update_event(json!({"id": MOTION_ID, "motion": {"motion": true}, "type": "motion"})),
+    ];
+    let mut stream = client(&bridge).await.event_stream().await.unwrap();
+    let mut events: Vec<HueEvent> = Vec::new();
+    while let Some(event) = stream.next_event().await {
+        events.push(event.unwrap());
+    }
+    assert_eq!(events.len(), 2);
+    assert_eq!(events[1].data[0].id, MOTION_ID);
+
+    let mut seen = 0;
+    client(&bridge)
+        .await
+        .watch_events(|_| {
+            seen += 1;
+            ControlFlow::Break(())
+        })
+        .await
+        .unwrap();
+    assert_eq!(seen, 1);
+}
@@ -0,0 +1,956 @@
+mod mock_bridge;
+
+use mock_bridge::*;
+use philips_hue_lab::config::Config;
+use philips_hue_lab::tls::CertificateFingerprint;
+use serde_json::json;
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Output, Stdio};
+use std::time::Duration;
+
+fn run(bridge: &MockBridge, args: &[&str]) -> Output {
+    let config_dir = tempfile::tempdir().unwrap();
+    run_with_config(
+        bridge,
+        &config_dir.path().join("config.toml"),
+        &["--bridge", &bridge.address()],
+        args,
+    )
+}
+
+/// Run the CLI with the given configuration file, trusting the mock bridge root CA.
+fn run_with_config(
+    bridge: &MockBridge,
+    config: &Path,
+    global_args: &[&str],
+    args: &[&str],
+) -> Output {
+    let ca_cert = config.with_file_name("mock-bridge-ca.pem");
+    std::fs::write(&ca_cert, bridge.ca_certificate_pem()).unwrap();
+    command(config)
+        .env("HUE_CA_CERT", &ca_cert)
+        .args(global_args)
+        .args(args)
+        .output()
+        .expect("failed to run philips_hue_lab")
+}
+
+/// The CLI with the given configuration file and without any settings from the environment.
+fn command(config: &Path) -> Command {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_philips_hue_lab"));
+    command
+        .env_remove("HUE_BRIDGE_IP")
+        .env_remove("HUE_API_KEY")
+        .env_remove("HUE_PROFILE")
+        .env_remove("HUE_LOG")
+        .env_remove("HUE_CA_CERT")
+        .env("HUE_CONFIG", config);
+    command
+}
+
+fn stdout(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stdout).to_string()
+}
+
+#[test]
+fn list_prints_devices() {
+    let bridge = MockBridge::start();
+    let output = run(&bridge, &["list", "--key", MOCK_APP_KEY]);
+    assert!(output.status.success());
+    let out = stdout(&output);
+    assert!(out.contains("Hallway sensor"));
+    assert!(out.contains(KITCHEN_LIGHT_ID));
+}
+
+#[test]
+fn list_prints_json_without_diagnostics() {
+    let bridge = MockBridge::start();
+    let output = run(
+        &bridge,
+        &["list", "--key", MOCK_APP_KEY, "--output", "json"],
+    );
+    assert!(output.status.success());
+    let devices: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let kitchen = devices
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|d| d["id"] == KITCHEN_DEVICE_ID)
+        .unwrap();
+    assert_eq!(kitchen["name"], "Kitchen");
+    assert_eq!(kitchen["light_id"], KITCHEN_LIGHT_ID);
+    assert_eq!(kitchen["manufacturer_name"], "Signify Netherlands B.V.");
+    assert_eq!(
+        kitchen["services"],
+        json!([{"rid": KITCHEN_LIGHT_ID, "rtype": "light"}])
+    );
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Requesting"));
+}
+
+#[test]
+fn list_prints_csv() {
+    let bridge = MockBridge::start();
+    let output = run(&bridge, &["list", "--key", MOCK_APP_KEY, "-o", "csv"]);
+    assert!(output.status.success());
+    let out = stdout(&output);
+    let mut lines = out.lines();
+    assert_eq!(
+        lines.next(),
+        Some("Device ID,Name,Product Name,Light ID,Services")
+    );
+    assert!(lines.clone().any(|line| line
+        == format!(
+            "{},Kitchen,Hue color lamp,{},light",
+            KITCHEN_DEVICE_ID, KITCHEN_LIGHT_ID
+        )));
+    assert!(lines.any(|line| line
+        == format!(
+            "{},Hallway sensor,Hue motion sensor,,motion device_power",
+            MOTION_DEVICE_ID
+        )));
+}
+
+#[test]
+fn light_turns_light_on_with_brightness() {
+    let bridge = MockBridge::start();
+    let output = run(
+        &bridge,
+        &[
+            "light",
+            "--key",
+            MOCK_APP_KEY,
+            "Desk",
+            "--on",
+            "--dim",
+            "40",
+        ],
+    );
+    assert!(output.status.success());
+    assert!(stdout(&output).contains("Light state updated successfully"));
+
+    let state = bridge.state();
+    let light = state.resource("light", DESK_LIGHT_ID).unwrap();
+    assert_eq!(light["on"]["on"], true);
+    assert_eq!(light["dimming"]["brightness"], 40.0);
+
+    // Finding and updating the light share one connection.
+    assert!(state
+        .requests
+        .iter()
+        .all(|r| r.connection == state.requests[0].connection));
+}
+
+#[test]
+fn light_sets_color() {
+    let bridge = MockBridge::start();
+    let output = run(
+        &bridge,
+        &[
+            "light",
+            "--key",
+            MOCK_APP_KEY,
+            KITCHEN_LIGHT_ID,
+            "--on",
+            "--color",
+            "#ff0000",
+        ],
+    );
+    assert!(output.status.success());
+
+    let state = bridge.state();
+    let light = state.resource("light", KITCHEN_LIGHT_ID).unwrap();
+    assert_eq!(light["on"]["on"], true);
+    let x = light["color"]["xy"]["x"].as_f64().unwrap();
+    let y = light["color"]["xy"]["y"].as_f64().unwrap();
+    // Pure red is outside the gamut of the light, so its red corner is used.
+    assert_eq!((x, y), (0.6915, 0.3083));
+}
+
+#[test]
+fn light_color_fails_for_light_without_color() {
+    let bridge = MockBridge::start();
+    let output = run(
+        &bridge,
+        &[
+            "light",
+            "--key",
+            MOCK_APP_KEY,
+            DESK_LIGHT_ID,
+            "--color",
+            "red",
+        ],
+    );
+    assert_eq!(output.status.code(), Some(10));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("does not support color"));
+    assert!(bridge.requests().iter().all(|r| r.method != "PUT"));
+}
+
+#[test]
+fn light_rejects_invalid_color() {
+    let bridge = MockBridge::start();
+    let output = run(
+        &bridge,
+        &[
+            "light",
+            "--key",
+            MOCK_APP_KEY,
+            "Kitchen",
+            "--color",
+            "mauve",
+        ],
+    );
+    assert_eq!(output.status.code(), Some(2));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Invalid color 'mauve'"));
+    assert!(bridge.requests().is_empty());
+}
+
+#[test]
+fn light_sets_color_temperature() {
+    let bridge = MockBridge::start();
+    let output = run(
+        &bridge,
+        &["light", "--key", MOCK_APP_KEY, "Desk", "--ct", "2700K"],
+    );
+    assert!(output.status.success());
+    let state = bridge.state();
+    let light = state.resource("light", DESK_LIGHT_ID).unwrap();
+    // Without --on the light is left off.
+    assert_eq!(light["on"]["on"], false);
+    assert_eq!(light["color_temperature"]["mirek"], 370);
+    // The light is read once, to check the update against its range.
+    let path = format!("/clip/v2/resource/light/{}", DESK_LIGHT_ID);
+    let light_gets = state
+        .requests
+        .iter()
+        .filter(|r| r.method == "GET" && r.path == path)
+        .count();
+    assert_eq!(light_gets, 1);
+}
+
+#[test]
+fn light_dims_without_changing_the_power_state() {
+    let bridge = MockBridge::start();
+    let output = run(
+        &bridge,
+        &["light", "--key", MOCK_APP_KEY, "Desk", "--dim", "30"],
+    );
+    assert!(output.status.success());
+    let path = format!("/clip/v2/resource/light/{}", DESK_LIGHT_ID);
+    assert_eq!(
+        bridge.requests_to("PUT", &path)[0].json(),
+        json!({"dimming": {"brightness": 30.0}})
+    );
+    let state = bridge.state();
+    let light = state.resource("light", DESK_LIGHT_ID).unwrap();
+    assert_eq!(light["on"]["on"], false);
+    assert_eq!(light["dimming"]["brightness"], 30.0);
+}
+
+#[test]
+fn light_color_temperature_outside_range_fails_or_is_clamped() {
+    let bridge = MockBridge::start();
+    let output = run(
+        &bridge,
+        &["light", "--key", MOCK_APP_KEY, "Desk", "--ct", "1800K"],
+    );
+    assert_eq!(output.status.code(), Some(10));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("153-454 mirek"));
+    assert!(bridge.requests().iter().all(|r| r.method != "PUT"));
+
+    let output = run(
+        &bridge,
+        &[
+            "light",
+            "--key",
+            MOCK_APP_KEY,
+            "Desk",
+            "--ct",
+            "1800K",
+            "--clamp",
+        ],
+    );
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("using 454 mirek"));
+    let state = bridge.state();
+    let light = state.resource("light", DESK_LIGHT_ID).unwrap();
+    assert_eq!(light["color_temperature"]["mirek"], 454);
+}
+
+#[test]
+fn light_color_temperature_fails_for_light_without_it() {
+    let bridge = MockBridge::start();
+    let output = run(
+        &bridge,
+        &[
+            "light",
+            "--key",
+            MOCK_APP_KEY,
+            PLUG_LIGHT_ID,
+            "--ct",
+            "2700K",
+        ],
+    );
+    assert_eq!(output.status.code(), Some(10));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("does not support color temperature"));
+}
+
+#[test]
+fn light_status_shows_one_or_all_lights() {
+    let bridge = MockBridge::start();
+    let output = run(&bridge, &["light", "status", "--key", MOCK_APP_KEY, "Desk"]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let lines: Vec<&str> = stdout.lines().collect();
+    assert_eq!(lines.len(), 2);
+    assert!(lines[0].starts_with("Light ID"));
+    assert!(lines[1].contains("Desk lamp"));
+    assert!(lines[1].contains("100%"));
+    assert!(lines[1].ends_with("2732K (366 mirek)"));
+    // Standard output is not a terminal, so there is no swatch.
+    assert!(!stdout.contains('\x1b'));
+
+    let output = run(
+        &bridge,
+        &["light", "status", "--key", MOCK_APP_KEY, "--output", "json"],
+    );
+    assert!(output.status.success());
+    let lights: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(lights.as_array().unwrap().len(), 3);
+    let kitchen = lights
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|light| light["id"] == KITCHEN_LIGHT_ID)
+        .unwrap();
+    assert_eq!(kitchen["color"]["gamut_type"], "C");
+    assert_eq!(
+        kitchen["color_temperature"]["mirek_schema"]["mirek_maximum"],
+        454
+    );
+    assert_eq!(kitchen["powerup"]["preset"], "safety");
+}
+
+#[test]
+fn room_and_zone_list_show_their_members() {
+    let bridge = MockBridge::start();
+    let output = run(&bridge, &["room", "list", "--key", MOCK_APP_KEY]);
+    assert!(output.status.success());
+    let stdout = stdout(&output);
+    let lines: Vec<&str> = stdout.lines().collect();
+    assert_eq!(lines.len(), 2);
+    assert!(lines[0].starts_with("ID"));
+    assert!(lines[1].starts_with(KITCHEN_ROOM_ID));
+    assert!(lines[1].contains(KITCHEN_GROUPED_LIGHT_ID));
+    assert!(lines[1].ends_with("2 device"));
+
+    let output = run(
+        &bridge,
+        &["zone", "list", "--key", MOCK_APP_KEY, "--output", "json"],
+    );
+    assert!(output.status.success());
+    let zones: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(zones[0]["name"], "Downstairs");
+    assert_eq!(zones[0]["grouped_light_id"], DOWNSTAIRS_GROUPED_LIGHT_ID);
+    assert_eq!(
+        zones[0]["children"][1],
+        json!({"rid": DESK_LIGHT_ID, "rtype": "light"})
+    );
+}
+
+#[test]
+fn room_controls_all_its_lights_with_one_request() {
+    let bridge = MockBridge::start();
+    let output = run(
+        &bridge,
+        &[
+            "room",
+            "--key",
+            MOCK_APP_KEY,
+            "kitchen",
+            "--on",
+            "--dim",
+            "40",
+            "--ct",
+            "2700K",
+            "--transition",
+            "2s",
+        ],
+    );
+    assert!(output.status.success());
+    assert!(stdout(&output).contains("All lights of the room updated successfully"));
+    let path = format!(
+        "/clip/v2/resource/grouped_light/{}",
+        KITCHEN_GROUPED_LIGHT_ID
+    );
+    assert_eq!(
+        bridge.requests_to("PUT", &path)[0].json(),
+        json!({
+            "on": {"on": true},
+            "dimming": {"brightness": 40.0},
+            "color_temperature": {"mirek": 370},
+            "dynamics": {"duration": 2000}
+        })
+    );
+    assert_eq!(
+        bridge
+            .requests()
+            .iter()
+            .filter(|r| r.method == "PUT")
+            .count(),
+        1
+    );
+
+    let output = run(
+        &bridge,
+        &["zone", "--key", MOCK_APP_KEY, DOWNSTAIRS_ZONE_ID, "--off"],
+    );
+    assert!(output.status.success());
+    let state = bridge.state();
+    let grouped_light = state
+        .resource("grouped_light", DOWNSTAIRS_GROUPED_LIGHT_ID)
+        .unwrap();
+    assert_eq!(grouped_light["on"]["on"], false);
+    drop(state);
+
+    let output = run(&bridge, &["room", "--key", MOCK_APP_KEY, "Cellar", "--on"]);
+    assert_eq!(output.status.code(), Some(8));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("No room found"));
+}
+
+#[test]
+fn light_toggles_and_adjusts_relative_to_the_current_state() {
+    let bridge = MockBridge::start();
+    let light = |args: &[&str]| {
+        let mut command = vec!["light", "--key", MOCK_APP_KEY, "Desk"];
+        command.extend(args);
+        run(&bridge, &command)
+    };
+    assert!(light(&["--toggle"]).status.success());
+    assert!(light(&["--dimmer", "40"]).status.success());
+    assert!(light(&["--warmer", "20"]).status.success());
+    {
+        let state = bridge.state();
+        let desk = state.resource("light", DESK_LIGHT_ID).unwrap();
+        assert_eq!(desk["on"]["on"], true);
+        assert_eq!(desk["dimming"]["brightness"], 60.0);
+        assert_eq!(desk["color_temperature"]["mirek"], 386);
+    }
+    assert!(light(&["--brighter", "10", "--cooler", "40"])
+        .status
+        .success());
+    assert!(light(&["--toggle"]).status.success());
+    {
+        let state = bridge.state();
+        let desk = state.resource("light", DESK_LIGHT_ID).unwrap();
+        assert_eq!(desk["on"]["on"], false);
+        assert_eq!(desk["dimming"]["brightness"], 70.0);
+        assert_eq!(desk["color_temperature"]["mirek"], 346);
+    }
+
+    assert_eq!(light(&["--brighter", "0"]).status.code(), Some(2));
+    assert_eq!(light(&["--toggle", "--on"]).status.code(), Some(2));
+}
+
+#[test]
+fn light_plays_effect_the_light_supports() {
+    let bridge = MockBridge::start();
+    let output = run(
+        &bridge,
+        &["light", "--key", MOCK_APP_KEY, "Desk", "--effect", "candle"],
+    );
+    assert!(output.status.success());
+    assert_eq!(
+        bridge.state().resource("light", DESK_LIGHT_ID).unwrap()["effects"]["effect"],
+        "candle"
+    );
+
+    let output = run(
+        &bridge,
+        &["light", "--key", MOCK_APP_KEY, "Desk", "--effect", "prism"],
+    );
+    assert_eq!(output.status.code(), Some(10));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("does not support the effect prism"));
+}
+
+#[test]
+fn light_shows_signal() {
+    let bridge = MockBridge::start();
+    let output = run(
+        &bridge,
+        &[
+            "light",
+            "--key",
+            MOCK_APP_KEY,
+            KITCHEN_LIGHT_ID,
+            "--signal",
+            "on_off_color",
+            "--signal-color",
+            "red",
+            "--duration",
+            "5s",
+        ],
+    );
+    assert!(output.status.success());
+    let state = bridge.state();
+    let light = state.resource("light", KITCHEN_LIGHT_ID).unwrap();
+    assert_eq!(light["signaling"]["signal"], "on_off_color");
+    assert_eq!(light["signaling"]["duration"], 5000);
+    drop(state);
+
+    // The signal takes one color, and needs a duration.
+    for args in [
+        vec!["--signal", "on_off_color", "--duration", "5s"],
+        vec!["--signal", "on_off"],
+    ] {
+        let mut command = vec!["light", "--key", MOCK_APP_KEY, KITCHEN_LIGHT_ID];
+        command.extend(args);
+        assert_eq!(run(&bridge, &command).status.code(), Some(2));
+    }
+}
+
+#[test]
+fn light_sets_transition() {
+    let bridge = MockBridge::start();
+    let output = run(
+        &bridge,
+        &[
+            "light",
+            "--key",
+            MOCK_APP_KEY,
+            "Desk",
+            "--on",
+            "--dim",
+            "40",
+            "--transition",
+            "500ms",
+        ],
+    );
+    assert!(output.status.success());
+    let path = format!("/clip/v2/resource/light/{}", DESK_LIGHT_ID);
+    assert_eq!(
+        bridge.requests_to("PUT", &path)[0].json()["dynamics"],
+        json!({"duration": 500})
+    );
+}
+
+#[test]
+fn light_transition_longer_than_the_bridge_allows_needs_fade() {
+    let bridge = MockBridge::start();
+    let output = run(
+        &bridge,
+        &[
+            "light",
+            "--key",
+            MOCK_APP_KEY,
+            "Desk",
+            "--off",
+            "--transition",
+            "3h",
+        ],
+    );
+    assert_eq!(output.status.code(), Some(2));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("--fade"));
+    assert!(bridge.requests().iter().all(|r| r.method != "PUT"));
+
+    let output = run(
+        &bridge,
+        &[
+            "light",
+            "--key",
+            MOCK_APP_KEY,
+            "Desk",
+            "--off",
+            "--transition",
+            "soon",
+        ],
+    );
+    assert_eq!(output.status.code(), Some(2));
+}
+
+#[test]
+fn light_fade_longer_than_a_day_is_rejected() {
+    let bridge = MockBridge::start();
+    let output = run(
+        &bridge,
+        &[
+            "light",
+            "--key",
+            MOCK_APP_KEY,
+            "Desk",
+            "--off",
+            "--transition",
+            "25h",
+            "--fade",
+        ],
+    );
+    assert_eq!(output.status.code(), Some(2));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("24 hours"));
+    assert!(bridge.requests().iter().all(|r| r.method != "PUT"));
+}
+
+#[test]
+fn light_fails_when_bridge_reports_errors() {
+    let bridge = MockBridge::start();
+    let output = run(
+        &bridge,
+        &[
+            "light",
+            "--key",
+            MOCK_APP_KEY,
+            PLUG_LIGHT_ID,
+            "--on",
+            "--dim",
+            "40",
+        ],
+    );
+    assert_eq!(output.status.code(), Some(11));
+    assert!(!stdout(&output).contains("Light state updated successfully"));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("does not support dimming"));
+}
+
+#[test]
+fn light_fails_for_unknown_name() {
+    let bridge = MockBridge::start();
+    let output = run(
+        &bridge,
+        &["light", "--key", MOCK_APP_KEY, "Garage", "--off"],
+    );
+    assert_eq!(output.status.code(), Some(8));
+    assert!(bridge.requests().iter().all(|r| r.method != "PUT"));
+}
+
+#[test]
+fn api_get_prints_filtered_response() {
+    let bridge = MockBridge::start();
+    let path = format!("/clip/v2/resource/light/{}", KITCHEN_LIGHT_ID);
+    let output = run(&bridge, &["api", "get", "--key", MOCK_APP_KEY, &path]);
+    assert!(output.status.success());
+    let response: serde_json::Value = serde_json::from_str(&stdout(&output)).unwrap();
+    assert_eq!(response["data"][0]["id"], KITCHEN_LIGHT_ID);
+    // Pretty-printed.
+    assert!(stdout(&output).lines().count() > 1);
+
+    let output = run(
+        &bridge,
+        &[
+            "api",
+            "get",
+            "--key",
+            MOCK_APP_KEY,
+            "resource/light",
+            "--filter",
+            "$.data[0].type",
+        ],
+    );
+    assert!(output.status.success());
+    assert_eq!(stdout(&output).trim(), r#""light""#);
+}
+
+#[test]
+fn api_put_reads_body_from_file() {
+    let bridge = MockBridge::start();
+    let dir = tempfile::tempdir().unwrap();
+    let body = dir.path().join("body.json");
+    std::fs::write(&body, r#"{"on": {"on": true}}"#).unwrap();
+    let path = format!("resource/light/{}", PLUG_LIGHT_ID);
+    let output = run(
+        &bridge,
+        &[
+            "api",
+            "put",
+            "--key",
+            MOCK_APP_KEY,
+            &path,
+            &format!("@{}", body.display()),
+            "--filter",
+            "/data/0/rid",
+        ],
+    );
+    assert!(output.status.success());
+    assert_eq!(stdout(&output).trim(), format!(r#""{}""#, PLUG_LIGHT_ID));
+    assert_eq!(
+        bridge.state().resource("light", PLUG_LIGHT_ID).unwrap()["on"]["on"],
+        true
+    );
+}
+
+#[test]
+fn api_post_reads_body_from_stdin_and_delete_removes_resource() {
+    let bridge = MockBridge::start();
+    let dir = tempfile::tempdir().unwrap();
+    let config = dir.path().join("config.toml");
+    let ca_cert = dir.path().join("mock-bridge-ca.pem");
+    std::fs::write(&ca_cert, bridge.ca_certificate_pem()).unwrap();
+    let mut child = command(&config)
+        .env("HUE_CA_CERT", &ca_cert)
+        .args(["--bridge", &bridge.address(), "api", "post"])
+        .args(["--key", MOCK_APP_KEY, "resource/zone", "-"])
+        .args(["--filter", "/data/0/rid"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(br#"{"metadata": {"name": "Upstairs"}, "children": []}"#)
+        .unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success());
+    let zone_id: String = serde_json::from_str(&stdout(&output)).unwrap();
+    assert_eq!(
+        bridge.state().resource("zone", &zone_id).unwrap()["metadata"]["name"],
+        "Upstairs"
+    );
+    let post = &bridge.requests_to("POST", "/clip/v2/resource/zone")[0];
+    assert_eq!(post.header("hue-application-key"), Some(MOCK_APP_KEY));
+
+    let path = format!("resource/zone/{}", zone_id);
+    let output = run(&bridge, &["api", "delete", "--key", MOCK_APP_KEY, &path]);
+    assert!(output.status.success());
+    assert!(bridge.state().resource("zone", &zone_id).is_none());
+}
+
+#[test]
+fn api_rejects_invalid_body() {
+    let bridge = MockBridge::start();
+    let output = run(
+        &bridge,
+        &["api", "put", "--key", MOCK_APP_KEY, "resource/light", "{on"],
+    );
+    assert_eq!(output.status.code(), Some(2));
+    assert!(bridge.requests().iter().all(|r| r.method != "PUT"));
+}
+
+#[test]
+fn create_key_prints_key() {
+    let bridge = MockBridge::start();
+    let output = run(&bridge, &["create-key"]);
+    assert!(output.status.success());
+    assert!(stdout(&output).contains(MOCK_APP_KEY));
+}
+
+#[test]
+fn watch_prints_filtered_events() {
+    let bridge = MockBridge::start();
+    bridge.state().events = vec![
+        update_event(json!({"id": KITCHEN_LIGHT_ID, "on": {"on": true}, "type": "light"})),
+        update_event(json!({"id": MOTION_ID, "motion": {"motion": true}, "type": "motion"})),
+        update_event(
+            json!({"id": DESK_LIGHT_ID, "dimming": {"brightness": 10.0}, "type": "light"}),
+        ),
+    ];
+    let output = run(
+        &bridge,
+        &[
+            "watch",
+            "--key",
+            MOCK_APP_KEY,
+            "--type",
+            "light",
+            "--count",
+            "1",
+        ],
+    );
+    assert!(output.status.success());
+    let out = stdout(&output);
+    assert!(out.contains(KITCHEN_LIGHT_ID));
+    assert!(out.contains("on=true"));
+    assert!(!out.contains(MOTION_ID));
+    assert!(!out.contains(DESK_LIGHT_ID));
+}
+
+#[test]
+fn watch_rejects_count_of_zero() {
+    let bridge = MockBridge::start();
+    let output = run(&bridge, &["watch", "--key", MOCK_APP_KEY, "--count", "0"]);
+    assert_eq!(output.status.code(), Some(2));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("--count"));
+    assert!(bridge.requests().is_empty());
+}
+
+#[test]
+fn watch_prints_json_lines() {
+    let bridge = MockBridge::start();
+    bridge.state().events = vec![
+        update_event(json!({"id": KITCHEN_LIGHT_ID, "on": {"on": true}, "type": "light"})),
+        update_event(json!({"id": MOTION_ID, "motion": {"motion": true}, "type": "motion"})),
+    ];
+    let output = run(
+        &bridge,
+        &["watch", "--key", MOCK_APP_KEY, "--output", "jsonl"],
+    );
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let events: Vec<serde_json::Value> = stdout(&output)
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert_eq!(events.len(), 2);
+    assert_eq!(events[0]["rtype"], "light");
+    assert_eq!(events[0]["change"], json!({"on": {"on": true}}));
+    assert_eq!(events[1]["change"], json!({"motion": {"motion": true}}));
+}
+
+#[test]
+fn create_key_waits_for_link_button() {
+    let bridge = MockBridge::start();
+    bridge.state().link_button_pressed = false;
+    let output = std::thread::scope(|scope| {
+        scope.spawn(|| {
+            std::thread::sleep(Duration::from_millis(1500));
+            bridge.state().link_button_pressed = true;
+        });
+        run(&bridge, &["create-key", "--wait", "10"])
+    });
+    assert!(output.status.success());
+    assert!(bridge.requests_to("POST", "/api").len() > 1);
+}
+
+#[test]
+fn create_key_without_wait_fails_when_link_button_not_pressed() {
+    let bridge = MockBridge::start();
+    bridge.state().link_button_pressed = false;
+    let output = run(&bridge, &["create-key"]);
+    assert_eq!(output.status.code(), Some(7));
+    assert_eq!(bridge.requests_to("POST", "/api").len(), 1);
+}
+
+#[test]
+fn create_key_saves_profile_used_by_other_commands() {
+    let bridge = MockBridge::start();
+    let config_dir = tempfile::tempdir().unwrap();
+    let config_path = config_dir.path().join("config.toml");
+
+    let output = run_with_config(
+        &bridge,
+        &config_path,
+        &["--bridge", &bridge.address(), "--profile", "home"],
+        &["create-key"],
+    );
+    assert!(output.status.success());
+    let config = Config::load(&config_path).unwrap();
+    assert_eq!(config.active_profile.as_deref(), Some("home"));
+    let profile = config.profile("home").unwrap();
+    assert_eq!(profile.bridge, Some(bridge.address()));
+    assert_eq!(profile.bridge_id.as_deref(), Some(MOCK_BRIDGE_ID));
+    assert_eq!(profile.app_key.as_deref(), Some(MOCK_APP_KEY));
+    assert_eq!(profile.client_key.as_deref(), Some(MOCK_CLIENT_KEY));
+
+    // Neither --bridge nor --key: both come from the active profile.
+    let output = run_with_config(&bridge, &config_path, &[], &["list"]);
+    assert!(output.status.success());
+    assert!(stdout(&output).contains("Hallway sensor"));
+}
+
+#[test]
+fn list_rejects_untrusted_bridge_certificate() {
+    let bridge = MockBridge::start();
+    let config_dir = tempfile::tempdir().unwrap();
+    let output = command(&config_dir.path().join("config.toml"))
+        .args(["--bridge", &bridge.address(), "list", "--key", MOCK_APP_KEY])
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(4));
+    assert!(bridge.requests().is_empty());
+}
+
+#[test]
+fn create_key_trust_on_first_use_pins_certificate() {
+    let bridge = MockBridge::start();
+    let config_dir = tempfile::tempdir().unwrap();
+    let config_path = config_dir.path().join("config.toml");
+    let output = command(&config_path)
+        .args([
+            "--bridge",
+            &bridge.address(),
+            "create-key",
+            "--trust-on-first-use",
+        ])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let config = Config::load(&config_path).unwrap();
+    let fingerprint = CertificateFingerprint::of(bridge.certificate_der()).to_string();
+    assert_eq!(
+        config.profile("default").unwrap().cert_fingerprint,
+        Some(fingerprint)
+    );
+
+    // The pinned certificate is trusted without the root CA.
+    let output = command(&config_path).arg("list").output().unwrap();
+    assert!(output.status.success());
+    assert!(stdout(&output).contains("Hallway sensor"));
+}
+
+#[test]
+fn environment_variables_override_profile() {
+    let bridge = MockBridge::start();
+    let config_dir = tempfile::tempdir().unwrap();
+    let config_path = config_dir.path().join("config.toml");
+    let ca_cert = config_dir.path().join("mock-bridge-ca.pem");
+    std::fs::write(&ca_cert, bridge.ca_certificate_pem()).unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_philips_hue_lab"))
+        .env("HUE_CONFIG", &config_path)
+        .env("HUE_CA_CERT", &ca_cert)
+        .env("HUE_BRIDGE_IP", bridge.address())
+        .env("HUE_API_KEY", MOCK_APP_KEY)
+        .env_remove("HUE_PROFILE")
+        .arg("list")
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert!(stdout(&output).contains("Hallway sensor"));
+}
+
+#[test]
+fn list_without_key_fails() {
+    let bridge = MockBridge::start();
+    let output = run(&bridge, &["list"]);
+    assert_eq!(output.status.code(), Some(2));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("No application key provided"));
+}
+
+#[test]
+fn list_with_wrong_key_exits_with_unauthorized_code() {
+    let bridge = MockBridge::start();
+    let output = run(&bridge, &["list", "--key", "wrong"]);
+    assert_eq!(output.status.code(), Some(6));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Unauthorized"));
+}
+
+#[test]
+fn verbose_logs_http_traffic_with_keys_redacted() {
+    let bridge = MockBridge::start();
+    let output = run(&bridge, &["-v", "list", "--key", MOCK_APP_KEY]);
+    assert!(output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("Request: GET https://"));
+    assert!(stderr.contains("hue-application-key: <redacted>"));
+    assert!(!stderr.contains(MOCK_APP_KEY));
+
+    let output = run(&bridge, &["create-key", "--verbose"]);
+    assert!(output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("Response: 200 OK"));
+    assert!(!stderr.contains(MOCK_APP_KEY));
+    assert!(!stderr.contains(MOCK_CLIENT_KEY));
+}
+
+#[test]
+fn quiet_hides_progress_messages() {
+    let bridge = MockBridge::start();
+    let output = run(&bridge, &["list", "--key", MOCK_APP_KEY]);
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Using Hue Bridge"));
+
+    let output = run(&bridge, &["-q", "list", "--key", MOCK_APP_KEY]);
+    assert!(output.status.success());
+    assert!(output.stderr.is_empty());
+    assert!(stdout(&output).contains("Hallway sensor"));
+}
@@ -0,0 +1,762 @@
+mod mock_bridge;
+
+use mock_bridge::*;
+use philips_hue_lab::color::{Color, ColorTemperature, Gamut, MirekSchema};
+use philips_hue_lab::effect::{AlertAction, Effect, Signal, TimedEffect};
+use philips_hue_lab::events::{HueEvent, MotionChange, MotionReport, ResourceChange, ResourceRef};
+use philips_hue_lab::group::GroupType;
+use philips_hue_lab::light::{LightColorTemperature, LightPowerup, LightUpdate};
+use philips_hue_lab::resource::{MotionId, ResourceType};
+use philips_hue_lab::tls::{fetch_certificate_fingerprint, CertificateFingerprint};
+use philips_hue_lab::{AppKey, BridgeIp, HueClient, HueClientBuilder, HueError, LightId};
+use serde_json::json;
+use std::ops::ControlFlow;
+use std::time::Duration;
+
+/// A client builder trusting the mock bridge root CA.
+fn builder(bridge: &MockBridge) -> HueClientBuilder {
+    HueClient::builder(BridgeIp(bridge.address()))
+        .root_certificate_pem(bridge.ca_certificate_pem().as_bytes())
+}
+
+fn client(bridge: &MockBridge) -> HueClient {
+    builder(bridge)
+        .app_key(AppKey(String::from(MOCK_APP_KEY)))
+        .build()
+        .unwrap()
+}
+
+#[test]
+fn get_request_sends_application_key() {
+    let bridge = MockBridge::start();
+    let response = client(&bridge)
+        .get_request("/clip/v2/resource/device")
+        .unwrap();
+    assert_eq!(response["data"].as_array().unwrap().len(), 4);
+
+    let requests = bridge.requests_to("GET", "/clip/v2/resource/device");
+    assert_eq!(requests.len(), 1);
+    assert_eq!(
+        requests[0].header("hue-application-key"),
+        Some(MOCK_APP_KEY)
+    );
+}
+
+#[test]
+fn get_request_fails_on_http_error_status() {
+    let bridge = MockBridge::start();
+    bridge.inject(Injection::new(
+        "GET",
+        "/clip/v2/resource/device",
+        503,
+        json!({"errors": [{"description": "service unavailable"}], "data": []}),
+    ));
+    let result = client(&bridge).get_request("/clip/v2/resource/device");
+    assert!(result.unwrap_err().to_string().contains("503"));
+}
+
+#[test]
+fn list_devices_returns_light_ids() {
+    let bridge = MockBridge::start();
+    let devices = client(&bridge).list_devices().unwrap();
+    let kitchen = devices
+        .iter()
+        .find(|d| d.0.id == KITCHEN_DEVICE_ID)
+        .unwrap();
+    assert_eq!(kitchen.0.name, "Kitchen");
+    assert_eq!(
+        kitchen.0.light_id,
+        Some(LightId(KITCHEN_LIGHT_ID.to_string()))
+    );
+    assert_eq!(kitchen.0.product_data.model_id, "LCA001");
+
+    let sensor = devices.iter().find(|d| d.0.id == MOTION_DEVICE_ID).unwrap();
+    assert_eq!(sensor.0.light_id, None);
+    assert!(sensor.0.has_service(&ResourceType::Motion));
+    assert!(sensor.0.has_service(&ResourceType::DevicePower));
+}
+
+#[test]
+fn get_resource_returns_the_resource() {
+    let bridge = MockBridge::start();
+    let client = client(&bridge);
+    let light: serde_json::Value = client
+        .get_resource(&ResourceType::Light, KITCHEN_LIGHT_ID)
+        .unwrap();
+    assert_eq!(light["id"], KITCHEN_LIGHT_ID);
+    assert_eq!(light["type"], "light");
+
+    let motion: serde_json::Value = client
+        .get_resource_by_id(&MotionId(MOTION_ID.to_string()))
+        .unwrap();
+    assert_eq!(motion["owner"]["rid"], MOTION_DEVICE_ID);
+    let path = format!("/clip/v2/resource/motion/{}", MOTION_ID);
+    assert_eq!(bridge.requests_to("GET", &path).len(), 1);
+
+    assert!(matches!(
+        client
+            .get_resource::<serde_json::Value>(&ResourceType::Light, "missing")
+            .unwrap_err(),
+        HueError::ResourceNotFound(_)
+    ));
+}
+
+#[test]
+fn list_resources_returns_all_of_the_type() {
+    let bridge = MockBridge::start();
+    let lights: Vec<serde_json::Value> = client(&bridge)
+        .list_resources(&ResourceType::Light)
+        .unwrap();
+    assert_eq!(lights.len(), 3);
+    assert!(lights.iter().all(|light| light["type"] == "light"));
+}
+
+#[test]
+fn list_devices_with_wrong_key_fails() {
+    let bridge = MockBridge::start();
+    let client = builder(&bridge)
+        .app_key(AppKey(String::from("wrong")))
+        .build()
+        .unwrap();
+    assert!(matches!(
+        client.list_devices().unwrap_err(),
+        HueError::Unauthorized(_)
+    ));
+}
+
+#[test]
+fn light_state_reads_the_full_state() {
+    let bridge = MockBridge::start();
+    let client = client(&bridge);
+    let desk = client
+        .light_state(&LightId(DESK_LIGHT_ID.to_string()))
+        .unwrap();
+    assert_eq!(desk.name, "Desk lamp");
+    assert!(!desk.on);
+    assert_eq!(desk.brightness, Some(100.0));
+    assert_eq!(desk.min_dim_level, Some(0.2));
+    assert_eq!(desk.color, None);
+    assert_eq!(
+        desk.color_temperature,
+        Some(LightColorTemperature {
+            mirek: Some(366),
+            mirek_schema: MirekSchema {
+                mirek_minimum: 153,
+                mirek_maximum: 454
+            }
+        })
+    );
+    assert_eq!(desk.effect.as_deref(), Some("no_effect"));
+    assert_eq!(desk.mode, "normal");
+    assert_eq!(
+        desk.powerup,
+        Some(LightPowerup {
+            preset: String::from("safety"),
+            configured: true
+        })
+    );
+
+    let states = client.light_states().unwrap();
+    assert_eq!(states.len(), 3);
+    let kitchen = states
+        .iter()
+        .find(|state| state.id.0 == KITCHEN_LIGHT_ID)
+        .unwrap();
+    assert_eq!(kitchen.color.as_ref().unwrap().gamut, Some(Gamut::C));
+    let plug = states
+        .iter()
+        .find(|state| state.id.0 == PLUG_LIGHT_ID)
+        .unwrap();
+    assert_eq!(plug.brightness, None);
+    assert_eq!(plug.current_xy(), None);
+}
+
+#[test]
+fn control_light_puts_new_state() {
+    let bridge = MockBridge::start();
+    let updated = client(&bridge)
+        .control_light(&LightId(DESK_LIGHT_ID.to_string()), true, Some(25), None)
+        .unwrap();
+    assert_eq!(
+        updated,
+        vec![ResourceRef {
+            rid: DESK_LIGHT_ID.to_string(),
+            rtype: ResourceType::Light,
+        }]
+    );
+
+    let path = format!("/clip/v2/resource/light/{}", DESK_LIGHT_ID);
+    let requests = bridge.requests_to("PUT", &path);
+    assert_eq!(
+        requests[0].json(),
+        json!({"on": {"on": true}, "dimming": {"brightness": 25.0}})
+    );
+    let state = bridge.state();
+    let light = state.resource("light", DESK_LIGHT_ID).unwrap();
+    assert_eq!(light["on"]["on"], true);
+    assert_eq!(light["dimming"]["brightness"], 25.0);
+}
+
+#[test]
+fn set_light_color_puts_xy_color() {
+    let bridge = MockBridge::start();
+    client(&bridge)
+        .set_light_color(
+            &LightId(KITCHEN_LIGHT_ID.to_string()),
+            &Color::parse("xy(0.3, 0.4)").unwrap(),
+            None,
+            None,
+        )
+        .unwrap();
+
+    let path = format!("/clip/v2/resource/light/{}", KITCHEN_LIGHT_ID);
+    assert_eq!(
+        bridge.requests_to("PUT", &path)[0].json(),
+        json!({"on": {"on": true}, "color": {"xy": {"x": 0.3, "y": 0.4}}})
+    );
+    let state = bridge.state();
+    let light = state.resource("light", KITCHEN_LIGHT_ID).unwrap();
+    assert_eq!(light["color"]["xy"], json!({"x": 0.3, "y": 0.4}));
+}
+
+#[test]
+fn light_gamut_is_read_from_the_light() {
+    let bridge = MockBridge::start();
+    let client = client(&bridge);
+    assert_eq!(
+        client
+            .light_gamut(&LightId(KITCHEN_LIGHT_ID.to_string()))
+            .unwrap(),
+        Some(Gamut::C)
+    );
+
+    // Without the gamut, the gamut type is used.
+    {
+        let mut state = bridge.state();
+        let light = state.resource_mut("light", KITCHEN_LIGHT_ID).unwrap();
+        light["color"] = json!({"xy": {"x": 0.4573, "y": 0.41}, "gamut_type": "B"});
+    }
+    assert_eq!(
+        client
+            .light_gamut(&LightId(KITCHEN_LIGHT_ID.to_string()))
+            .unwrap(),
+        Some(Gamut::B)
+    );
+
+    assert!(matches!(
+        client.light_gamut(&LightId(DESK_LIGHT_ID.to_string())),
+        Err(HueError::InvalidParameter(_))
+    ));
+}
+
+#[test]
+fn set_light_color_temperature_puts_mirek_within_schema() {
+    let bridge = MockBridge::start();
+    let client = client(&bridge);
+    let desk = LightId(DESK_LIGHT_ID.to_string());
+    assert_eq!(
+        client.light_mirek_schema(&desk).unwrap(),
+        MirekSchema {
+            mirek_minimum: 153,
+            mirek_maximum: 454
+        }
+    );
+    client
+        .set_light_color_temperature(&desk, ColorTemperature::Mirek(200), Some(80), false, None)
+        .unwrap();
+    let path = format!("/clip/v2/resource/light/{}", DESK_LIGHT_ID);
+    assert_eq!(
+        bridge.requests_to("PUT", &path)[0].json(),
+        json!({"on": {"on": true}, "dimming": {"brightness": 80.0}, "color_temperature": {"mirek": 200}})
+    );
+
+    assert!(matches!(
+        client.set_light_color_temperature(
+            &desk,
+            ColorTemperature::Kelvin(10000),
+            None,
+            false,
+            None
+        ),
+        Err(HueError::InvalidParameter(_))
+    ));
+}
+
+#[test]
+fn control_light_sends_transition_as_dynamics_duration() {
+    let bridge = MockBridge::start();
+    let client = client(&bridge);
+    let desk = LightId(DESK_LIGHT_ID.to_string());
+    client
+        .control_light(&desk, false, None, Some(Duration::from_millis(1500)))
+        .unwrap();
+    let path = format!("/clip/v2/resource/light/{}", DESK_LIGHT_ID);
+    assert_eq!(
+        bridge.requests_to("PUT", &path)[0].json(),
+        json!({"on": {"on": false}, "dynamics": {"duration": 1500}})
+    );
+
+    assert!(matches!(
+        client.control_light(&desk, false, None, Some(Duration::from_secs(3 * 3600))),
+        Err(HueError::InvalidParameter(_))
+    ));
+    assert_eq!(bridge.requests_to("PUT", &path).len(), 1);
+}
+
+#[test]
+fn update_light_sends_only_the_fields_that_are_set() {
+    let bridge = MockBridge::start();
+    let client = client(&bridge);
+    let kitchen = LightId(KITCHEN_LIGHT_ID.to_string());
+    let update = LightUpdate::new()
+        .color(Color::parse("#ff0000").unwrap().to_xy())
+        .transition(Duration::from_millis(400));
+    client.update_light(&kitchen, &update).unwrap();
+    let path = format!("/clip/v2/resource/light/{}", KITCHEN_LIGHT_ID);
+    // The color is clamped to the gamut of the light, and the power state is left alone.
+    assert_eq!(
+        bridge.requests_to("PUT", &path)[0].json(),
+        json!({"color": {"xy": {"x": 0.6915, "y": 0.3083}}, "dynamics": {"duration": 400}})
+    );
+
+    assert!(matches!(
+        client.update_light(&kitchen, &LightUpdate::new()),
+        Err(HueError::Usage(_))
+    ));
+    assert_eq!(bridge.requests_to("PUT", &path).len(), 1);
+}
+
+#[test]
+fn grouped_light_of_a_zone_is_found_and_updated() {
+    let bridge = MockBridge::start();
+    let client = client(&bridge);
+    let zones = client.list_groups(GroupType::Zone).unwrap();
+    assert_eq!(zones.len(), 1);
+    assert_eq!(zones[0].rtype, ResourceType::Zone);
+    assert_eq!(zones[0].children.len(), 2);
+
+    let grouped_light = client
+        .find_group_by_id_or_name(GroupType::Zone, "downstairs")
+        .unwrap();
+    assert_eq!(grouped_light.0, DOWNSTAIRS_GROUPED_LIGHT_ID);
+    let updated = client
+        .update_grouped_light(&grouped_light, &LightUpdate::new().brightness_delta(25))
+        .unwrap();
+    assert_eq!(updated[0].rtype, ResourceType::GroupedLight);
+    let path = format!(
+        "/clip/v2/resource/grouped_light/{}",
+        DOWNSTAIRS_GROUPED_LIGHT_ID
+    );
+    assert_eq!(
+        bridge.requests_to("PUT", &path)[0].json(),
+        json!({"dimming_delta": {"action": "up", "brightness_delta": 25.0}})
+    );
+
+    assert!(matches!(
+        client.update_grouped_light(&grouped_light, &LightUpdate::new().effect(Effect::Candle)),
+        Err(HueError::InvalidParameter(_))
+    ));
+    assert!(matches!(
+        client.find_group_by_id_or_name(GroupType::Room, "downstairs"),
+        Err(HueError::ResourceNotFound(_))
+    ));
+}
+
+#[test]
+fn fade_light_retries_rate_limited_steps() {
+    let bridge = MockBridge::start();
+    let path = format!("/clip/v2/resource/light/{}", DESK_LIGHT_ID);
+    bridge.inject(Injection::new("PUT", &path, 429, json!({"errors": [], "data": []})).times(1));
+    client(&bridge)
+        .fade_light(
+            &LightId(DESK_LIGHT_ID.to_string()),
+            true,
+            Some(40),
+            Duration::from_millis(300),
+        )
+        .unwrap();
+
+    let requests = bridge.requests_to("PUT", &path);
+    assert_eq!(requests.len(), 2);
+    assert_eq!(
+        requests[1].json(),
+        json!({"on": {"on": true}, "dimming": {"brightness": 40.0}, "dynamics": {"duration": 300}})
+    );
+}
+
+#[test]
+fn fade_light_fails_for_light_without_dimming() {
+    let bridge = MockBridge::start();
+    assert!(matches!(
+        client(&bridge).fade_light(
+            &LightId(PLUG_LIGHT_ID.to_string()),
+            false,
+            None,
+            Duration::from_secs(1)
+        ),
+        Err(HueError::InvalidParameter(_))
+    ));
+}
+
+#[test]
+fn toggle_light_flips_the_light() {
+    let bridge = MockBridge::start();
+    let client = client(&bridge);
+    let desk = LightId(DESK_LIGHT_ID.to_string());
+    client.toggle_light(&desk, None).unwrap();
+    assert!(client.light_state(&desk).unwrap().on);
+    client.toggle_light(&desk, None).unwrap();
+    assert!(!client.light_state(&desk).unwrap().on);
+}
+
+#[test]
+fn adjust_light_sends_deltas() {
+    let bridge = MockBridge::start();
+    let client = client(&bridge);
+    let desk = LightId(DESK_LIGHT_ID.to_string());
+    client
+        .adjust_light(&desk, Some(-30), Some(50), None)
+        .unwrap();
+    let path = format!("/clip/v2/resource/light/{}", DESK_LIGHT_ID);
+    assert_eq!(
+        bridge.requests_to("PUT", &path)[0].json(),
+        json!({
+            "dimming_delta": {"action": "down", "brightness_delta": 30.0},
+            "color_temperature_delta": {"action": "up", "mirek_delta": 50}
+        })
+    );
+    let state = client.light_state(&desk).unwrap();
+    assert_eq!(state.brightness, Some(70.0));
+    assert_eq!(state.color_temperature.unwrap().mirek, Some(416));
+
+    client.adjust_light(&desk, Some(0), None, None).unwrap();
+    assert_eq!(
+        bridge.requests_to("PUT", &path)[1].json(),
+        json!({"dimming_delta": {"action": "stop", "brightness_delta": 0.0}})
+    );
+
+    assert!(matches!(
+        client.adjust_light(&desk, Some(-101), None, None),
+        Err(HueError::InvalidParameter(_))
+    ));
+    assert!(matches!(
+        client.adjust_light(&desk, None, Some(400), None),
+        Err(HueError::InvalidParameter(_))
+    ));
+    assert!(matches!(
+        client.adjust_light(&LightId(PLUG_LIGHT_ID.to_string()), Some(10), None, None),
+        Err(HueError::Clip(_))
+    ));
+}
+
+#[test]
+fn set_light_effect_is_checked_against_the_light() {
+    let bridge = MockBridge::start();
+    let client = client(&bridge);
+    let desk = LightId(DESK_LIGHT_ID.to_string());
+    client.set_light_effect(&desk, Effect::Candle).unwrap();
+    let path = format!("/clip/v2/resource/light/{}", DESK_LIGHT_ID);
+    assert_eq!(
+        bridge.requests_to("PUT", &path)[0].json(),
+        json!({"effects": {"effect": "candle"}})
+    );
+
+    assert!(matches!(
+        client.set_light_effect(&desk, Effect::Prism),
+        Err(HueError::InvalidParameter(message)) if message.contains("no_effect, candle, fire")
+    ));
+    assert!(matches!(
+        client.set_light_effect(&LightId(PLUG_LIGHT_ID.to_string()), Effect::Candle),
+        Err(HueError::InvalidParameter(_))
+    ));
+    assert_eq!(bridge.requests_to("PUT", &path).len(), 1);
+}
+
+#[test]
+fn timed_effects_and_alerts_are_put() {
+    let bridge = MockBridge::start();
+    let client = client(&bridge);
+    client
+        .set_light_timed_effect(
+            &LightId(DESK_LIGHT_ID.to_string()),
+            TimedEffect::Sunrise,
+            Duration::from_secs(20 * 60),
+        )
+        .unwrap();
+    client
+        .alert_light(&LightId(PLUG_LIGHT_ID.to_string()), AlertAction::Breathe)
+        .unwrap();
+
+    let desk_path = format!("/clip/v2/resource/light/{}", DESK_LIGHT_ID);
+    assert_eq!(
+        bridge.requests_to("PUT", &desk_path)[0].json(),
+        json!({"timed_effects": {"effect": "sunrise", "duration": 1200000}})
+    );
+    let plug_path = format!("/clip/v2/resource/light/{}", PLUG_LIGHT_ID);
+    assert_eq!(
+        bridge.requests_to("PUT", &plug_path)[0].json(),
+        json!({"alert": {"action": "breathe"}})
+    );
+}
+
+#[test]
+fn signal_light_puts_signal_with_colors() {
+    let bridge = MockBridge::start();
+    let client = client(&bridge);
+    let kitchen = LightId(KITCHEN_LIGHT_ID.to_string());
+    let colors = [
+        Color::parse("xy(0.3, 0.4)").unwrap(),
+        Color::parse("xy(0.2, 0.2)").unwrap(),
+    ];
+    client
+        .signal_light(
+            &kitchen,
+            Signal::Alternating,
+            Duration::from_secs(10),
+            &colors,
+        )
+        .unwrap();
+    let path = format!("/clip/v2/resource/light/{}", KITCHEN_LIGHT_ID);
+    assert_eq!(
+        bridge.requests_to("PUT", &path)[0].json(),
+        json!({"signaling": {
+            "signal": "alternating",
+            "duration": 10000,
+            "colors": [{"xy": {"x": 0.3, "y": 0.4}}, {"xy": {"x": 0.2, "y": 0.2}}]
+        }})
+    );
+
+    assert!(matches!(
+        client.signal_light(
+            &kitchen,
+            Signal::OnOffColor,
+            Duration::from_secs(10),
+            &colors
+        ),
+        Err(HueError::Usage(_))
+    ));
+    assert!(matches!(
+        client.signal_light(
+            &LightId(PLUG_LIGHT_ID.to_string()),
+            Signal::OnOffColor,
+            Duration::from_secs(10),
+            &colors[..1]
+        ),
+        Err(HueError::InvalidParameter(_))
+    ));
+}
+
+#[test]
+fn control_light_fails_on_errors_in_response() {
+    let bridge = MockBridge::start();
+    let err = client(&bridge)
+        .control_light(&LightId(PLUG_LIGHT_ID.to_string()), true, Some(25), None)
+        .unwrap_err();
+    assert!(
+        matches!(err, HueError::Clip(ref descriptions) if descriptions[0].contains("does not support dimming"))
+    );
+    let state = bridge.state();
+    let plug = state.resource("light", PLUG_LIGHT_ID).unwrap();
+    assert_eq!(plug["on"]["on"], false);
+}
+
+#[test]
+fn requests_reuse_one_connection() {
+    let bridge = MockBridge::start();
+    let client = client(&bridge);
+    let light_id = client.find_light_by_id_or_name("desk").unwrap();
+    client.control_light(&light_id, false, None, None).unwrap();
+    client.list_devices().unwrap();
+
+    let requests = bridge.requests();
+    assert_eq!(requests.len(), 4);
+    assert!(requests
+        .iter()
+        .all(|r| r.connection == requests[0].connection));
+}
+
+#[test]
+fn request_timeout_fails_slow_requests() {
+    let bridge = MockBridge::start();
+    let client = builder(&bridge)
+        .app_key(AppKey(String::from(MOCK_APP_KEY)))
+        .bridge_id(MOCK_BRIDGE_ID)
+        .request_timeout(Duration::from_millis(200))
+        .build()
+        .unwrap();
+    bridge.state().response_delay = Duration::from_secs(2);
+    assert!(matches!(
+        client.list_devices().unwrap_err(),
+        HueError::Transport(_)
+    ));
+}
+
+#[test]
+fn find_light_by_id_or_name_rejects_ambiguous_names() {
+    let bridge = MockBridge::start();
+    let client = client(&bridge);
+    assert_eq!(
+        client.find_light_by_id_or_name("desk").unwrap(),
+        LightId(DESK_LIGHT_ID.to_string())
+    );
+    let err = client.find_light_by_id_or_name("kitchen").unwrap_err();
+    assert!(err.to_string().contains("Multiple lights found"));
+}
+
+#[test]
+fn client_rejects_certificate_from_untrusted_ca() {
+    let bridge = MockBridge::start();
+    let err = HueClient::new(
+        BridgeIp(bridge.address()),
+        AppKey(String::from(MOCK_APP_KEY)),
+    )
+    .err()
+    .unwrap();
+    assert!(matches!(err, HueError::Tls(_)), "{:?}", err);
+    assert!(bridge.requests().is_empty());
+}
+
+#[test]
+fn client_rejects_certificate_for_other_bridge() {
+    let bridge = MockBridge::start();
+    let client = builder(&bridge)
+        .app_key(AppKey(String::from(MOCK_APP_KEY)))
+        .bridge_id("001788fffe000000")
+        .build()
+        .unwrap();
+    assert!(matches!(
+        client.list_devices().unwrap_err(),
+        HueError::Tls(_)
+    ));
+
+    let client = builder(&bridge)
+        .app_key(AppKey(String::from(MOCK_APP_KEY)))
+        .bridge_id(MOCK_BRIDGE_ID.to_uppercase())
+        .build()
+        .unwrap();
+    assert!(client.list_devices().is_ok());
+    assert!(bridge.requests_to("GET", "/api/0/config").is_empty());
+}
+
+#[test]
+fn pinned_certificate_is_trusted_without_root_ca() {
+    let bridge = MockBridge::start();
+    let fingerprint =
+        fetch_certificate_fingerprint(&BridgeIp(bridge.address()), Duration::from_secs(5)).unwrap();
+    assert_eq!(
+        fingerprint,
+        CertificateFingerprint::of(bridge.certificate_der())
+    );
+
+    let client = HueClient::builder(BridgeIp(bridge.address()))
+        .app_key(AppKey(String::from(MOCK_APP_KEY)))
+        .pinned_certificate(fingerprint)
+        .build()
+        .unwrap();
+    assert_eq!(client.list_devices().unwrap().len(), 4);
+
+    let client = HueClient::builder(BridgeIp(bridge.address()))
+        .app_key(AppKey(String::from(MOCK_APP_KEY)))
+        .pinned_certificate(CertificateFingerprint::of(b"another certificate"))
+        .build()
+        .unwrap();
+    assert!(matches!(
+        client.list_devices().unwrap_err(),
+        HueError::Tls(_)
+    ));
+}
+
+#[test]
+fn create_key_fails_when_link_button_not_pressed() {
+    let bridge = MockBridge::start();
+    bridge.state().link_button_pressed = false;
+    let client = builder(&bridge).build().unwrap();
+    assert!(matches!(
+        client.create_key().unwrap_err(),
+        HueError::LinkButtonNotPressed
+    ));
+}
+
+#[test]
+fn create_key_fails_on_unexpected_responses() {
+    let bridge = MockBridge::start();
+    bridge.inject(Injection::new("POST", "/api", 200, json!({"unexpected": true})).times(1));
+    bridge
+        .inject(Injection::new("POST", "/api", 200, json!([{"error": {"kind": "odd"}}])).times(1));
+    let client = builder(&bridge).build().unwrap();
+
+    let err = client.create_key().unwrap_err();
+    assert!(matches!(err, HueError::Parse { .. }));
+    assert_eq!(err.exit_code(), 12);
+    let err = client.create_key().unwrap_err();
+    assert!(matches!(err, HueError::Api { type_code: 0, .. }));
+}
+
+#[test]
+fn create_key_requests_client_key() {
+    let bridge = MockBridge::start();
+    let client = builder(&bridge).build().unwrap();
+    let key = client.create_key().unwrap();
+    assert_eq!(key.app_key(), AppKey(String::from(MOCK_APP_KEY)));
+    assert_eq!(key.client_key.as_deref(), Some(MOCK_CLIENT_KEY));
+    assert_eq!(
+        bridge.requests_to("POST", "/api")[0].json()["generateclientkey"],
+        true
+    );
+}
+
+#[test]
+fn event_stream_yields_typed_events() {
+    let bridge = MockBridge::start();
+    bridge.state().events = vec![
+        update_event(json!({"id": KITCHEN_LIGHT_ID, "on": {"on": true}, "type": "light"})),
+        update_event(json!({"id": MOTION_ID, "motion": {"motion": true}, "type": "motion"})),
+    ];
+    let events: Vec<HueEvent> = client(&bridge)
+        .event_stream()
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap();
+    assert_eq!(events.len(), 2);
+    assert_eq!(events[0].data[0].id, KITCHEN_LIGHT_ID);
+    assert_eq!(
+        events[1].data[0].change,
+        ResourceChange::Motion(MotionChange {
+            motion: MotionReport { motion: true }
+        })
+    );
+}
+
+#[test]
+fn watch_events_skips_messages_it_cannot_decode() {
+    let bridge = MockBridge::start();
+    bridge.state().events = vec![
+        json!({"not": "a list of events"}),
+        update_event(json!({"id": KITCHEN_LIGHT_ID, "on": {"on": true}, "type": "light"})),
+    ];
+    let mut seen = Vec::new();
+    client(&bridge)
+        .watch_events(|event| {
+            seen.push(event.data[0].id.clone());
+            ControlFlow::Continue(())
+        })
+        .unwrap();
+    assert_eq!(seen, vec![KITCHEN_LIGHT_ID.to_string()]);
+}
+
+#[test]
+fn watch_events_stops_when_callback_breaks() {
+    let bridge = MockBridge::start();
+    bridge.state().events = vec![
+        update_event(json!({"id": KITCHEN_LIGHT_ID, "on": {"on": true}, "type": "light"})),
+        update_event(json!({"id": DESK_LIGHT_ID, "on": {"on": true}, "type": "light"})),
+    ];
+    let mut seen = Vec::new();
+    client(&bridge)
+        .watch_events(|event| {
+            seen.push(event.data[0].id.clone());
+            ControlFlow::Break(())
+        })
+        .unwrap();
+    assert_eq!(seen, vec![KITCHEN_LIGHT_ID.to_string()]);
+}
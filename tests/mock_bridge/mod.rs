@@ -0,0 +1,820 @@
+//! A mock Hue Bridge for offline integration tests.
+//!
+//! The mock serves HTTPS on a random localhost port and implements enough of the
+//! bridge API for the CLI and library flows: `POST /api` (create key),
+//! `GET /api/0/config`, `GET`/`PUT`/`POST`/`DELETE` on `/clip/v2/resource/{type}[/{id}]`, and
+//! the `/eventstream/clip/v2` event stream.
+//!
+//! The bridge state can be inspected and changed through [`MockBridge::state`],
+//! and canned error responses can be injected with [`MockBridge::inject`].
+//! All requests are recorded and available from [`MockBridge::requests`].
+//!
+//! Like a real bridge, the certificate is signed by a root CA and has the bridge
+//! ID as common name. Clients must trust [`MockBridge::ca_certificate_pem`], or
+//! pin [`MockBridge::certificate_der`].
+#![allow(dead_code)]
+
+use rcgen::{BasicConstraints, CertificateParams, DnType, IsCa, KeyPair};
+use rustls::pki_types::{CertificateDer, PrivateKeyDer, PrivatePkcs8KeyDer};
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::thread;
+use std::time::Duration;
+
+pub const MOCK_APP_KEY: &str = "mock-app-key";
+pub const MOCK_CLIENT_KEY: &str = "0123456789ABCDEF0123456789ABCDEF";
+pub const MOCK_BRIDGE_ID: &str = "001788fffe4f8a2b";
+
+pub const KITCHEN_DEVICE_ID: &str = "94860050-1d86-4b79-8583-1be7dce05197";
+pub const KITCHEN_LIGHT_ID: &str = "53ca6e61-5e40-4760-9e2e-6d2f48594901";
+pub const DESK_DEVICE_ID: &str = "3f1a6f1e-8e0b-4a65-9c1c-0b3d5fbf1a10";
+pub const DESK_LIGHT_ID: &str = "b2b5a6e0-8f1d-4c4e-a3c2-6f3e4d2c1b0a";
+pub const PLUG_DEVICE_ID: &str = "c7a2e9d4-31b8-4a7e-9e0d-5f6a7b8c9d0e";
+pub const PLUG_LIGHT_ID: &str = "e1d2c3b4-a5f6-4789-8a9b-0c1d2e3f4a5b";
+pub const MOTION_DEVICE_ID: &str = "a0b1c2d3-e4f5-4a6b-8c7d-9e0f1a2b3c4d";
+pub const MOTION_ID: &str = "f0e1d2c3-b4a5-4697-8877-665544332211";
+pub const MOTION_POWER_ID: &str = "d4c3b2a1-0f9e-4d8c-b7a6-958473625140";
+pub const KITCHEN_ROOM_ID: &str = "6b2f0c4e-9a1d-4e3b-8f7a-2c5d8e1f4a6b";
+pub const KITCHEN_GROUPED_LIGHT_ID: &str = "8d4e2a1c-7b3f-4c9e-a5d6-1e2f3a4b5c6d";
+pub const DOWNSTAIRS_ZONE_ID: &str = "2a9c7e5b-3d1f-4b8a-9c6e-7f0a1b2c3d4e";
+pub const DOWNSTAIRS_GROUPED_LIGHT_ID: &str = "5e7f9a1b-2c4d-4e6f-8a0b-3c5d7e9f1a2b";
+
+/// A request received by the mock bridge.
+#[derive(Debug, Clone)]
+pub struct RecordedRequest {
+    pub method: String,
+    pub path: String,
+    pub headers: Vec<(String, String)>,
+    pub body: String,
+    /// Sequence number of the TLS connection the request arrived on.
+    pub connection: usize,
+}
+
+impl RecordedRequest {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+
+    pub fn json(&self) -> Value {
+        serde_json::from_str(&self.body).expect("request body is not JSON")
+    }
+}
+
+/// A canned response returned instead of the normal handling for matching requests.
+#[derive(Debug, Clone)]
+pub struct Injection {
+    pub method: String,
+    pub path: String,
+    pub status: u16,
+    pub body: Value,
+    /// Number of requests to answer with this response, `None` for all of them.
+    pub times: Option<usize>,
+}
+
+impl Injection {
+    pub fn new(method: &str, path: &str, status: u16, body: Value) -> Self {
+        Injection {
+            method: method.to_string(),
+            path: path.to_string(),
+            status,
+            body,
+            times: None,
+        }
+    }
+
+    pub fn times(mut self, times: usize) -> Self {
+        self.times = Some(times);
+        self
+    }
+}
+
+/// The mutable state of the mock bridge.
+pub struct BridgeState {
+    pub app_key: String,
+    pub link_button_pressed: bool,
+    pub config: Value,
+    /// CLIP v2 resources by resource type.
+    pub resources: BTreeMap<String, Vec<Value>>,
+    pub injections: Vec<Injection>,
+    pub requests: Vec<RecordedRequest>,
+    /// Messages sent on the event stream, each a JSON array of events.
+    /// The stream is closed after the last message.
+    pub events: Vec<Value>,
+    /// How long to wait before answering each request, to test timeouts.
+    pub response_delay: Duration,
+}
+
+impl BridgeState {
+    pub fn resource(&self, rtype: &str, id: &str) -> Option<&Value> {
+        self.resources
+            .get(rtype)
+            .and_then(|rs| rs.iter().find(|r| r["id"] == id))
+    }
+
+    pub fn resource_mut(&mut self, rtype: &str, id: &str) -> Option<&mut Value> {
+        self.resources
+            .get_mut(rtype)
+            .and_then(|rs| rs.iter_mut().find(|r| r["id"] == id))
+    }
+
+    pub fn add_resource(&mut self, resource: Value) {
+        let rtype = resource["type"]
+            .as_str()
+            .expect("resource type")
+            .to_string();
+        self.resources.entry(rtype).or_default().push(resource);
+    }
+}
+
+pub struct MockBridge {
+    addr: SocketAddr,
+    ca_certificate_pem: String,
+    certificate_der: Vec<u8>,
+    state: Arc<Mutex<BridgeState>>,
+    shutdown: Arc<AtomicBool>,
+}
+
+impl MockBridge {
+    /// Start a mock bridge with the default fixture devices.
+    pub fn start() -> MockBridge {
+        let certificates = generate_certificates(MOCK_BRIDGE_ID);
+        let certificate_der = certificates.chain[0].to_vec();
+        let tls_config = Arc::new(
+            rustls::ServerConfig::builder_with_provider(Arc::new(
+                rustls::crypto::ring::default_provider(),
+            ))
+            .with_safe_default_protocol_versions()
+            .unwrap()
+            .with_no_client_auth()
+            .with_single_cert(certificates.chain, certificates.key)
+            .expect("mock bridge TLS configuration"),
+        );
+
+        let listener = TcpListener::bind("127.0.0.1:0").expect("bind mock bridge");
+        let addr = listener.local_addr().unwrap();
+        let state = Arc::new(Mutex::new(default_state()));
+        let shutdown = Arc::new(AtomicBool::new(false));
+
+        let accept_state = state.clone();
+        let accept_shutdown = shutdown.clone();
+        thread::spawn(move || {
+            let connections = AtomicUsize::new(0);
+            for stream in listener.incoming() {
+                if accept_shutdown.load(Ordering::SeqCst) {
+                    break;
+                }
+                let Ok(stream) = stream else { continue };
+                let connection = connections.fetch_add(1, Ordering::SeqCst);
+                let state = accept_state.clone();
+                let tls_config = tls_config.clone();
+                thread::spawn(move || serve_connection(stream, tls_config, state, connection));
+            }
+        });
+
+        MockBridge {
+            addr,
+            ca_certificate_pem: certificates.ca_pem,
+            certificate_der,
+            state,
+            shutdown,
+        }
+    }
+
+    /// The address to pass as `--bridge`, e.g. `127.0.0.1:51234`.
+    pub fn address(&self) -> String {
+        self.addr.to_string()
+    }
+
+    /// The root CA that signed the bridge certificate, in PEM format.
+    pub fn ca_certificate_pem(&self) -> &str {
+        &self.ca_certificate_pem
+    }
+
+    /// The bridge certificate, for pinning.
+    pub fn certificate_der(&self) -> &[u8] {
+        &self.certificate_der
+    }
+
+    pub fn state(&self) -> MutexGuard<'_, BridgeState> {
+        self.state.lock().unwrap()
+    }
+
+    pub fn inject(&self, injection: Injection) {
+        self.state().injections.push(injection);
+    }
+
+    pub fn requests(&self) -> Vec<RecordedRequest> {
+        self.state().requests.clone()
+    }
+
+    /// The recorded requests with the given method and path.
+    pub fn requests_to(&self, method: &str, path: &str) -> Vec<RecordedRequest> {
+        self.requests()
+            .into_iter()
+            .filter(|r| r.method == method && r.path == path)
+            .collect()
+    }
+}
+
+impl Drop for MockBridge {
+    fn drop(&mut self) {
+        self.shutdown.store(true, Ordering::SeqCst);
+        // Wake up the accept loop so it can observe the shutdown flag.
+        let _ = TcpStream::connect(self.addr);
+    }
+}
+
+struct Certificates {
+    /// The bridge certificate followed by the root CA.
+    chain: Vec<CertificateDer<'static>>,
+    key: PrivateKeyDer<'static>,
+    ca_pem: String,
+}
+
+/// Generate a root CA and a bridge certificate signed by it.
+/// Like a real bridge, the certificate common name is the bridge ID.
+fn generate_certificates(bridge_id: &str) -> Certificates {
+    let ca_key = KeyPair::generate().unwrap();
+    let mut ca_params = CertificateParams::new(Vec::new()).unwrap();
+    ca_params
+        .distinguished_name
+        .push(DnType::CommonName, "root-bridge");
+    ca_params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+    let ca_cert = ca_params.self_signed(&ca_key).unwrap();
+
+    let bridge_key = KeyPair::generate().unwrap();
+    let mut bridge_params = CertificateParams::new(Vec::new()).unwrap();
+    bridge_params
+        .distinguished_name
+        .push(DnType::CommonName, bridge_id);
+    let bridge_cert = bridge_params
+        .signed_by(&bridge_key, &ca_cert, &ca_key)
+        .unwrap();
+
+    Certificates {
+        chain: vec![bridge_cert.der().clone(), ca_cert.der().clone()],
+        key: PrivateKeyDer::Pkcs8(PrivatePkcs8KeyDer::from(bridge_key.serialize_der())),
+        ca_pem: ca_cert.pem(),
+    }
+}
+
+fn serve_connection(
+    stream: TcpStream,
+    tls_config: Arc<rustls::ServerConfig>,
+    state: Arc<Mutex<BridgeState>>,
+    connection: usize,
+) {
+    let Ok(tls) = rustls::ServerConnection::new(tls_config) else {
+        return;
+    };
+    let mut reader = BufReader::new(rustls::StreamOwned::new(tls, stream));
+    loop {
+        let Some(request) = read_request(&mut reader, connection) else {
+            return;
+        };
+        if request.method == "GET" && request.path == "/eventstream/clip/v2" {
+            let stream = reader.get_mut();
+            serve_event_stream(stream, &state, request);
+            // Close the TLS session properly, so the end of the stream is not a truncation.
+            stream.conn.send_close_notify();
+            let _ = stream.flush();
+            return;
+        }
+        let delay = state.lock().unwrap().response_delay;
+        thread::sleep(delay);
+        let (status, body) = handle_request(&state, request);
+        let body = body.to_string();
+        let response = format!(
+            "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{}",
+            status,
+            reason_phrase(status),
+            body.len(),
+            body
+        );
+        let stream = reader.get_mut();
+        if stream.write_all(response.as_bytes()).is_err() || stream.flush().is_err() {
+            return;
+        }
+    }
+}
+
+/// Send the queued event messages as server-sent events and close the connection.
+fn serve_event_stream<W: Write>(
+    stream: &mut W,
+    state: &Mutex<BridgeState>,
+    request: RecordedRequest,
+) {
+    let mut state = state.lock().unwrap();
+    state.requests.push(request.clone());
+    if request.header("hue-application-key") != Some(state.app_key.as_str()) {
+        let body = clip_error("unauthorized user").to_string();
+        let _ = write!(
+            stream,
+            "HTTP/1.1 403 Forbidden\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            body.len(),
+            body
+        );
+        return;
+    }
+    let mut response = String::from(
+        "HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nConnection: close\r\n\r\n: hi\n\n",
+    );
+    for (index, message) in state.events.iter().enumerate() {
+        response.push_str(&format!("id: 1700000000:{}\ndata: {}\n\n", index, message));
+    }
+    let _ = stream.write_all(response.as_bytes());
+    let _ = stream.flush();
+}
+
+/// An event stream message with a single update event for a resource.
+pub fn update_event(resource: Value) -> Value {
+    json!([{
+        "creationtime": "2024-01-01T12:00:00Z",
+        "id": "b9e6f5a4-3c2d-4e1f-8a7b-6c5d4e3f2a1b",
+        "type": "update",
+        "data": [resource]
+    }])
+}
+
+fn read_request<R: BufRead>(reader: &mut R, connection: usize) -> Option<RecordedRequest> {
+    let mut request_line = String::new();
+    if reader.read_line(&mut request_line).ok()? == 0 {
+        return None;
+    }
+    let mut parts = request_line.split_whitespace();
+    let method = parts.next()?.to_string();
+    let path = parts.next()?.to_string();
+
+    let mut headers = Vec::new();
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line).ok()? == 0 {
+            return None;
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            headers.push((name.trim().to_string(), value.trim().to_string()));
+        }
+    }
+
+    let content_length = headers
+        .iter()
+        .find(|(n, _)| n.eq_ignore_ascii_case("content-length"))
+        .and_then(|(_, v)| v.parse::<usize>().ok())
+        .unwrap_or(0);
+    let mut body = vec![0; content_length];
+    reader.read_exact(&mut body).ok()?;
+
+    Some(RecordedRequest {
+        method,
+        path,
+        headers,
+        body: String::from_utf8_lossy(&body).to_string(),
+        connection,
+    })
+}
+
+fn handle_request(state: &Mutex<BridgeState>, request: RecordedRequest) -> (u16, Value) {
+    let mut state = state.lock().unwrap();
+    state.requests.push(request.clone());
+
+    if let Some(index) = state
+        .injections
+        .iter()
+        .position(|i| i.method == request.method && i.path == request.path)
+    {
+        let injection = state.injections[index].clone();
+        match injection.times {
+            Some(1) => {
+                state.injections.remove(index);
+            }
+            Some(n) => state.injections[index].times = Some(n - 1),
+            None => {}
+        }
+        return (injection.status, injection.body);
+    }
+
+    let segments: Vec<&str> = request
+        .path
+        .trim_start_matches('/')
+        .split('/')
+        .filter(|s| !s.is_empty())
+        .collect();
+    match (request.method.as_str(), segments.as_slice()) {
+        ("POST", ["api"]) => create_key(&state, &request),
+        ("GET", ["api", _, "config"]) => (200, state.config.clone()),
+        (method, ["clip", "v2", "resource", rest @ ..]) => {
+            if request.header("hue-application-key") != Some(state.app_key.as_str()) {
+                return (403, clip_error("unauthorized user"));
+            }
+            match (method, rest) {
+                ("GET", [rtype]) => (
+                    200,
+                    json!({"errors": [], "data": state.resources.get(*rtype).cloned().unwrap_or_default()}),
+                ),
+                ("GET", [rtype, id]) => match state.resource(rtype, id) {
+                    Some(resource) => (200, json!({"errors": [], "data": [resource]})),
+                    None => (404, clip_error("Not Found")),
+                },
+                ("PUT", [rtype, id]) => {
+                    let Ok(mut update) = serde_json::from_str::<Value>(&request.body) else {
+                        return (400, clip_error("Invalid JSON body"));
+                    };
+                    match state.resource_mut(rtype, id) {
+                        Some(resource) => {
+                            apply_deltas(resource, &mut update);
+                            // Like the bridge, reject properties the resource does not support,
+                            // e.g. dimming on a plug.
+                            let unsupported: Vec<Value> = update
+                                .as_object()
+                                .into_iter()
+                                .flat_map(|update| update.keys())
+                                .filter(|key| resource.get(key.as_str()).is_none())
+                                .map(|key| {
+                                    json!({"description": format!("device ({}) does not support {}", rtype, key)})
+                                })
+                                .collect();
+                            if !unsupported.is_empty() {
+                                return (207, json!({"errors": unsupported, "data": []}));
+                            }
+                            merge(resource, &update);
+                            (
+                                200,
+                                json!({"errors": [], "data": [{"rid": id, "rtype": rtype}]}),
+                            )
+                        }
+                        None => (404, clip_error("Not Found")),
+                    }
+                }
+                ("POST", [rtype]) => {
+                    let Ok(Value::Object(mut resource)) =
+                        serde_json::from_str::<Value>(&request.body)
+                    else {
+                        return (400, clip_error("Invalid JSON body"));
+                    };
+                    let count = state.resources.values().map(Vec::len).sum::<usize>();
+                    let id = format!("00000000-0000-4000-8000-{:012}", count);
+                    resource.insert(String::from("id"), json!(id));
+                    resource.insert(String::from("type"), json!(rtype));
+                    state.add_resource(Value::Object(resource));
+                    (
+                        200,
+                        json!({"errors": [], "data": [{"rid": id, "rtype": rtype}]}),
+                    )
+                }
+                ("DELETE", [rtype, id]) => {
+                    let resources = state.resources.entry(rtype.to_string()).or_default();
+                    match resources.iter().position(|r| r["id"] == *id) {
+                        Some(index) => {
+                            resources.remove(index);
+                            (
+                                200,
+                                json!({"errors": [], "data": [{"rid": id, "rtype": rtype}]}),
+                            )
+                        }
+                        None => (404, clip_error("Not Found")),
+                    }
+                }
+                _ => (405, clip_error("Method Not Allowed")),
+            }
+        }
+        _ => (404, clip_error("Not Found")),
+    }
+}
+
+fn create_key(state: &BridgeState, request: &RecordedRequest) -> (u16, Value) {
+    let Ok(body) = serde_json::from_str::<Value>(&request.body) else {
+        return (
+            200,
+            json!([{"error": {"type": 2, "address": "/", "description": "body contains invalid JSON"}}]),
+        );
+    };
+    if !state.link_button_pressed {
+        return (
+            200,
+            json!([{"error": {"type": 101, "address": "", "description": "link button not pressed"}}]),
+        );
+    }
+    let mut success = json!({"username": state.app_key});
+    if body["generateclientkey"] == true {
+        success["clientkey"] = json!(MOCK_CLIENT_KEY);
+    }
+    (200, json!([{ "success": success }]))
+}
+
+fn clip_error(description: &str) -> Value {
+    json!({"errors": [{"description": description}], "data": []})
+}
+
+/// Like the bridge, turn relative changes of the brightness and color temperature
+/// into new values, within the ranges of the light. Deltas for properties the
+/// resource does not have are left in the update, to be rejected.
+fn apply_deltas(resource: &Value, update: &mut Value) {
+    let Some(update) = update.as_object_mut() else {
+        return;
+    };
+    if let (Some(dimming), Some(delta)) = (resource.get("dimming"), update.get("dimming_delta")) {
+        let minimum = dimming["min_dim_level"].as_f64().unwrap_or(0.0);
+        let brightness =
+            dimming["brightness"].as_f64().unwrap_or(0.0) + signed_delta(delta, "brightness_delta");
+        update.remove("dimming_delta");
+        update.insert(
+            String::from("dimming"),
+            json!({"brightness": brightness.clamp(minimum, 100.0)}),
+        );
+    }
+    if let (Some(color_temperature), Some(delta)) = (
+        resource.get("color_temperature"),
+        update.get("color_temperature_delta"),
+    ) {
+        let schema = &color_temperature["mirek_schema"];
+        let minimum = schema["mirek_minimum"].as_f64().unwrap_or(153.0);
+        let maximum = schema["mirek_maximum"].as_f64().unwrap_or(500.0);
+        let mirek = color_temperature["mirek"].as_f64().unwrap_or(minimum)
+            + signed_delta(delta, "mirek_delta");
+        update.remove("color_temperature_delta");
+        update.insert(
+            String::from("color_temperature"),
+            json!({"mirek": mirek.clamp(minimum, maximum) as u64, "mirek_valid": true}),
+        );
+    }
+}
+
+/// The change of a delta action, negative for `down` and zero for `stop`.
+fn signed_delta(delta: &Value, name: &str) -> f64 {
+    let value = delta[name].as_f64().unwrap_or(0.0);
+    match delta["action"].as_str() {
+        Some("up") => value,
+        Some("down") => -value,
+        _ => 0.0,
+    }
+}
+
+/// Recursively merge a JSON update into a resource, like the bridge applies a PUT.
+fn merge(target: &mut Value, update: &Value) {
+    match (target, update) {
+        (Value::Object(target), Value::Object(update)) => {
+            for (key, value) in update {
+                merge(target.entry(key.clone()).or_insert(Value::Null), value);
+            }
+        }
+        (target, update) => *target = update.clone(),
+    }
+}
+
+fn reason_phrase(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        207 => "Multi-Status",
+        400 => "Bad Request",
+        403 => "Forbidden",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        429 => "Too Many Requests",
+        500 => "Internal Server Error",
+        503 => "Service Unavailable",
+        _ => "Unknown",
+    }
+}
+
+fn default_state() -> BridgeState {
+    let mut state = BridgeState {
+        app_key: MOCK_APP_KEY.to_string(),
+        link_button_pressed: true,
+        config: json!({
+            "name": "Mock Bridge",
+            "datastoreversion": "163",
+            "swversion": "1962097030",
+            "apiversion": "1.62.0",
+            "mac": "00:17:88:4f:8a:2b",
+            "bridgeid": MOCK_BRIDGE_ID.to_uppercase(),
+            "factorynew": false,
+            "replacesbridgeid": null,
+            "modelid": "BSB002",
+            "starterkitid": ""
+        }),
+        resources: BTreeMap::new(),
+        injections: Vec::new(),
+        requests: Vec::new(),
+        events: Vec::new(),
+        response_delay: Duration::ZERO,
+    };
+
+    state.add_resource(device(
+        KITCHEN_DEVICE_ID,
+        "Kitchen",
+        "Hue color lamp",
+        "LCA001",
+        &[("light", KITCHEN_LIGHT_ID)],
+    ));
+    state.add_resource(color_light(KITCHEN_LIGHT_ID, KITCHEN_DEVICE_ID, "Kitchen"));
+    state.add_resource(device(
+        DESK_DEVICE_ID,
+        "Desk lamp",
+        "Hue white ambiance lamp",
+        "LTA001",
+        &[("light", DESK_LIGHT_ID)],
+    ));
+    state.add_resource(ambiance_light(DESK_LIGHT_ID, DESK_DEVICE_ID, "Desk lamp"));
+    state.add_resource(device(
+        PLUG_DEVICE_ID,
+        "Kitchen plug",
+        "Hue smart plug",
+        "LOM007",
+        &[("light", PLUG_LIGHT_ID)],
+    ));
+    state.add_resource(on_off_light(PLUG_LIGHT_ID, PLUG_DEVICE_ID, "Kitchen plug"));
+    state.add_resource(device(
+        MOTION_DEVICE_ID,
+        "Hallway sensor",
+        "Hue motion sensor",
+        "SML001",
+        &[("motion", MOTION_ID), ("device_power", MOTION_POWER_ID)],
+    ));
+    state.add_resource(json!({
+        "id": MOTION_ID,
+        "owner": {"rid": MOTION_DEVICE_ID, "rtype": "device"},
+        "enabled": true,
+        "motion": {"motion": false, "motion_valid": true},
+        "type": "motion"
+    }));
+    state.add_resource(json!({
+        "id": MOTION_POWER_ID,
+        "owner": {"rid": MOTION_DEVICE_ID, "rtype": "device"},
+        "power_state": {"battery_state": "normal", "battery_level": 87},
+        "type": "device_power"
+    }));
+    state.add_resource(group(
+        KITCHEN_ROOM_ID,
+        "room",
+        "Kitchen",
+        "kitchen",
+        &[("device", KITCHEN_DEVICE_ID), ("device", PLUG_DEVICE_ID)],
+        KITCHEN_GROUPED_LIGHT_ID,
+    ));
+    state.add_resource(grouped_light(
+        KITCHEN_GROUPED_LIGHT_ID,
+        KITCHEN_ROOM_ID,
+        "room",
+    ));
+    state.add_resource(group(
+        DOWNSTAIRS_ZONE_ID,
+        "zone",
+        "Downstairs",
+        "downstairs",
+        &[("light", KITCHEN_LIGHT_ID), ("light", DESK_LIGHT_ID)],
+        DOWNSTAIRS_GROUPED_LIGHT_ID,
+    ));
+    state.add_resource(grouped_light(
+        DOWNSTAIRS_GROUPED_LIGHT_ID,
+        DOWNSTAIRS_ZONE_ID,
+        "zone",
+    ));
+    state
+}
+
+/// A room or zone with its `grouped_light` service.
+pub fn group(
+    id: &str,
+    rtype: &str,
+    name: &str,
+    archetype: &str,
+    children: &[(&str, &str)],
+    grouped_light: &str,
+) -> Value {
+    json!({
+        "id": id,
+        "metadata": {"name": name, "archetype": archetype},
+        "children": children
+            .iter()
+            .map(|(rtype, rid)| json!({"rid": rid, "rtype": rtype}))
+            .collect::<Vec<_>>(),
+        "services": [{"rid": grouped_light, "rtype": "grouped_light"}],
+        "type": rtype
+    })
+}
+
+pub fn grouped_light(id: &str, owner: &str, owner_type: &str) -> Value {
+    json!({
+        "id": id,
+        "owner": {"rid": owner, "rtype": owner_type},
+        "on": {"on": false},
+        "dimming": {"brightness": 0.0},
+        "color_temperature": {},
+        "color": {},
+        "dynamics": {},
+        "alert": {"action_values": ["breathe"]},
+        "signaling": {"signal_values": ["no_signal", "on_off", "on_off_color", "alternating"]},
+        "type": "grouped_light"
+    })
+}
+
+pub fn device(
+    id: &str,
+    name: &str,
+    product_name: &str,
+    model_id: &str,
+    services: &[(&str, &str)],
+) -> Value {
+    json!({
+        "id": id,
+        "id_v1": "/lights/1",
+        "product_data": {
+            "model_id": model_id,
+            "manufacturer_name": "Signify Netherlands B.V.",
+            "product_name": product_name,
+            "product_archetype": "sultan_bulb",
+            "certified": true,
+            "software_version": "1.104.2",
+            "hardware_platform_type": "100b-112"
+        },
+        "metadata": {"name": name, "archetype": "sultan_bulb"},
+        "identify": {},
+        "services": services
+            .iter()
+            .map(|(rtype, rid)| json!({"rid": rid, "rtype": rtype}))
+            .collect::<Vec<_>>(),
+        "type": "device"
+    })
+}
+
+pub fn on_off_light(id: &str, owner: &str, name: &str) -> Value {
+    json!({
+        "id": id,
+        "owner": {"rid": owner, "rtype": "device"},
+        "metadata": {"name": name, "archetype": "plug"},
+        "on": {"on": false},
+        "dynamics": {"status": "none", "status_values": ["none"], "speed": 0.0, "speed_valid": false},
+        "alert": {"action_values": ["breathe"]},
+        "signaling": {"signal_values": ["no_signal", "on_off"]},
+        "powerup": {"preset": "safety", "configured": true, "on": {"mode": "on", "on": {"on": true}}},
+        "mode": "normal",
+        "type": "light"
+    })
+}
+
+pub fn ambiance_light(id: &str, owner: &str, name: &str) -> Value {
+    let mut light = on_off_light(id, owner, name);
+    light["metadata"]["archetype"] = json!("table_shade");
+    light["dimming"] = json!({"brightness": 100.0, "min_dim_level": 0.2});
+    light["color_temperature"] = json!({
+        "mirek": 366,
+        "mirek_valid": true,
+        "mirek_schema": {"mirek_minimum": 153, "mirek_maximum": 454}
+    });
+    light["effects"] = json!({
+        "effect": "no_effect",
+        "status": "no_effect",
+        "status_values": ["no_effect", "candle", "fire"],
+        "effect_values": ["no_effect", "candle", "fire"]
+    });
+    light["timed_effects"] = json!({
+        "effect": "no_effect",
+        "status": "no_effect",
+        "status_values": ["no_effect", "sunrise", "sunset"],
+        "effect_values": ["no_effect", "sunrise", "sunset"]
+    });
+    light
+}
+
+pub fn color_light(id: &str, owner: &str, name: &str) -> Value {
+    let mut light = ambiance_light(id, owner, name);
+    light["metadata"]["archetype"] = json!("sultan_bulb");
+    light["color"] = json!({
+        "xy": {"x": 0.4573, "y": 0.41},
+        "gamut": {
+            "red": {"x": 0.6915, "y": 0.3083},
+            "green": {"x": 0.17, "y": 0.7},
+            "blue": {"x": 0.1532, "y": 0.0475}
+        },
+        "gamut_type": "C"
+    });
+    let effects = json!([
+        "no_effect",
+        "candle",
+        "fire",
+        "prism",
+        "sparkle",
+        "opal",
+        "glisten",
+        "underwater",
+        "cosmos",
+        "sunbeam",
+        "enchant"
+    ]);
+    light["effects"]["status_values"] = effects.clone();
+    light["effects"]["effect_values"] = effects;
+    light["signaling"]["signal_values"] =
+        json!(["no_signal", "on_off", "on_off_color", "alternating"]);
+    light
+}